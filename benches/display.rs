@@ -0,0 +1,156 @@
+extern crate chip8_opcode;
+
+use chip8_opcode::memory::*;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+// The bitboard Display against the byte display it replaced, drawing
+// sprites and handing the screen to a backend.
+//
+//   cargo bench --bench display
+
+const W: usize = DISPLAY_VISIBLE_WIDTH;
+const H: usize = DISPLAY_VISIBLE_HEIGHT;
+
+// 10 bytes per row and 4 rows of padding, drawn pixel by pixel
+struct ByteDisplay {
+    memory: [[u8; W / 8 + 2]; H + 4],
+}
+
+impl ByteDisplay {
+    fn new() -> Self {
+        ByteDisplay { memory: [[0; W / 8 + 2]; H + 4] }
+    }
+
+    fn apply_sprites(&mut self, x: u8, y: u8, sprites: &[u8], wrap: bool) -> u8 {
+        let mut collision = 0u8;
+        let x = x as usize % W;
+        let y = y as usize % H;
+
+        for (s, sprite) in sprites.iter().enumerate() {
+            let mut r = y + s;
+            if r >= H {
+                if !wrap {
+                    break;
+                }
+                r %= H;
+            }
+
+            for bit in 0..8 {
+                if sprite & (0x80 >> bit) == 0 {
+                    continue;
+                }
+
+                let mut c = x + bit;
+                if c >= W {
+                    if !wrap {
+                        break;
+                    }
+                    c %= W;
+                }
+
+                let byte = &mut self.memory[r][c / 8];
+                let mask = 0x80 >> (c % 8);
+                collision |= (*byte & mask != 0) as u8;
+                *byte ^= mask;
+            }
+        }
+        collision
+    }
+
+    // what the SDL backend did with it: a new point list on every draw
+    fn points(&self) -> Vec<(i32, i32)> {
+        let mut points = Vec::with_capacity(W * H);
+        for (r, row) in self.memory.iter().enumerate() {
+            for (c, byte) in row.iter().enumerate() {
+                for b in 0..8 {
+                    if byte & (0x80 >> b) != 0 {
+                        points.push(((c * 8 + b) as i32, r as i32));
+                    }
+                }
+            }
+        }
+        points
+    }
+}
+
+// Every start position a byte can hold, a 15 row sprite at each
+fn positions() -> Vec<(u8, u8)> {
+    (0..=255u8).flat_map(|x| (0..=255u8).map(move |y| (x, y))).collect()
+}
+
+const SPRITE: [u8; 15] = [0xFF, 0x81, 0xC3, 0xA5, 0x99, 0x18, 0x3C, 0x7E,
+                          0x01, 0x80, 0x55, 0xAA, 0x0F, 0xF0, 0x66];
+
+fn report(what: &str, n: usize, secs: f64) {
+    println!("{:<28} {:>8.1} ns", what, secs * 1e9 / n as f64);
+}
+
+fn time<F: FnMut() -> usize>(what: &str, mut f: F) -> f64 {
+    // one round to warm up
+    f();
+    let start = Instant::now();
+    let n = f();
+    let secs = start.elapsed().as_secs_f64();
+    report(what, n, secs);
+    secs * 1e9 / n as f64
+}
+
+fn bench_draw(wrap: bool) {
+    let pos = positions();
+    let mode = if wrap { "wrapped" } else { "clipped" };
+
+    let old = time(&format!("byte DXYF {}", mode), || {
+        let mut d = ByteDisplay::new();
+        for _ in 0..10 {
+            for &(x, y) in &pos {
+                black_box(d.apply_sprites(x, y, &SPRITE, wrap));
+            }
+        }
+        10 * pos.len()
+    });
+    let new = time(&format!("bitboard DXYF {}", mode), || {
+        let mut d = Display::new();
+        for _ in 0..10 {
+            for &(x, y) in &pos {
+                black_box(d.apply_sprites(x, y, &SPRITE, wrap));
+            }
+        }
+        10 * pos.len()
+    });
+    println!("{:<28} {:>8.1}x", "speedup", old / new);
+}
+
+// A screen half full of sprites, scanned as a backend does per draw
+fn bench_scan() {
+    let mut bytes = ByteDisplay::new();
+    let mut bits = Display::new();
+    for i in 0..40u8 {
+        bytes.apply_sprites(i * 13, i * 7, &SPRITE, false);
+        bits.apply_sprites(i * 13, i * 7, &SPRITE, false);
+    }
+
+    const N: usize = 100_000;
+    let old = time("byte scan to points", || {
+        for _ in 0..N {
+            black_box(bytes.points());
+        }
+        N
+    });
+    let new = time("bitboard scan to runs", || {
+        for _ in 0..N {
+            let mut runs = 0;
+            bits.get_video_buf().unwrap().for_each_run(|x, y, len| runs += x + y + len);
+            black_box(runs);
+        }
+        N
+    });
+    println!("{:<28} {:>8.1}x", "speedup", old / new);
+}
+
+fn main() {
+    bench_draw(false);
+    bench_draw(true);
+    bench_scan();
+}
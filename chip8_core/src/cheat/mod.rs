@@ -0,0 +1,196 @@
+use memory::*;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+// RAM search narrows down the addresses a game keeps a value at, e.g. the
+// lives left, by comparing memory with the last snapshot; cheats freeze
+// such addresses to a value.
+
+// How a candidate's value must relate to the last snapshot to stay.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Relation {
+    Same,
+    Changed,
+    Increased,
+    Decreased,
+    // equal to the value, whatever the snapshot held
+    Equal(u8),
+}
+
+impl Relation {
+    fn holds(self, old: u8, new: u8) -> bool {
+        match self {
+            Relation::Same => new == old,
+            Relation::Changed => new != old,
+            Relation::Increased => new > old,
+            Relation::Decreased => new < old,
+            Relation::Equal(v) => new == v,
+        }
+    }
+}
+
+fn parse_byte(s: &str) -> Option<u8> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+// same, changed, inc, dec or a value, decimal or hex with 0x
+impl FromStr for Relation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rel = match s {
+            "same" => Relation::Same,
+            "changed" => Relation::Changed,
+            "inc" => Relation::Increased,
+            "dec" => Relation::Decreased,
+            _ => Relation::Equal(parse_byte(s).ok_or_else(|| {
+                format!("unknown filter '{}', expected same, changed, inc, dec or a value", s)
+            })?),
+        };
+        Ok(rel)
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Relation::Same => f.pad("same"),
+            Relation::Changed => f.pad("changed"),
+            Relation::Increased => f.pad("inc"),
+            Relation::Decreased => f.pad("dec"),
+            Relation::Equal(v) => f.pad(&v.to_string()),
+        }
+    }
+}
+
+pub struct RamSearch {
+    // memory as of the last snapshot
+    values: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    // Every address is a candidate to begin with.
+    pub fn new(mem: &mut dyn CpuMemory) -> Self {
+        let mut search = RamSearch {
+            values: Vec::new(),
+            candidates: (0..mem.size()).map(|a| a as u16).collect(),
+        };
+        search.snapshot(mem);
+        search
+    }
+
+    pub fn snapshot(&mut self, mem: &mut dyn CpuMemory) {
+        self.values = (0..mem.size()).map(|a| mem.get_u8(a as u16).unwrap()).collect();
+    }
+
+    // Keeps the candidates whose value relates to the snapshot as asked and
+    // takes a new snapshot. Returns the candidates left.
+    pub fn filter(&mut self, mem: &mut dyn CpuMemory, rel: Relation) -> usize {
+        let values = &self.values;
+        self.candidates.retain(|&a| {
+            let new = mem.get_u8(a).unwrap();
+            rel.holds(values[a as usize], new)
+        });
+        self.snapshot(mem);
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    // the value at `addr` as of the last snapshot
+    pub fn value(&self, addr: u16) -> u8 {
+        self.values[addr as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cheat {
+    pub addr: u16,
+    pub value: u8,
+}
+
+// The addresses frozen to a value, written again after every frame.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Freezes the addresses of a list in the Display format, e.g.
+    // "02F3=05,0300=FF"; addresses and values are hex. An address already
+    // frozen takes the new value.
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        for item in spec.split(',').filter(|i| !i.is_empty()) {
+            let mut it = item.splitn(2, '=');
+            let addr = u16::from_str_radix(it.next().unwrap_or(""), 16).ok();
+            let value = it.next().and_then(|v| u8::from_str_radix(v, 16).ok());
+            match (addr, value) {
+                (Some(addr), Some(value)) => self.freeze(addr, value),
+                _ => return Err(format!("invalid cheat '{}', expected ADDR=VALUE in hex", item)),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn freeze(&mut self, addr: u16, value: u8) {
+        self.unfreeze(addr);
+        self.cheats.push(Cheat { addr, value });
+    }
+
+    pub fn unfreeze(&mut self, addr: u16) {
+        self.cheats.retain(|c| c.addr != addr);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    pub fn iter(&self) -> ::core::slice::Iter<'_, Cheat> {
+        self.cheats.iter()
+    }
+
+    // Addresses outside of memory are left out.
+    pub fn write_to(&self, mem: &mut dyn CpuMemory) {
+        for c in &self.cheats {
+            mem.set_u8(c.addr, c.value);
+        }
+    }
+}
+
+impl fmt::Display for Cheats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, c) in self.cheats.iter().enumerate() {
+            if n > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{:04X}={:02X}", c.addr, c.value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Cheats {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cheats = Cheats::new();
+        cheats.apply(s)?;
+        Ok(cheats)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,82 @@
+use super::*;
+
+fn set(mem: &mut Memory, values: &[(u16, u8)]) {
+    for &(addr, val) in values {
+        mem.set_u8(addr, val).unwrap();
+    }
+}
+
+#[test]
+fn relations() {
+    assert!(Relation::Same.holds(3, 3));
+    assert!(Relation::Changed.holds(3, 4));
+    assert!(Relation::Increased.holds(3, 4) && !Relation::Increased.holds(3, 3));
+    assert!(Relation::Decreased.holds(3, 2) && !Relation::Decreased.holds(3, 3));
+    assert!(Relation::Equal(7).holds(3, 7));
+}
+
+#[test]
+fn relation_names() {
+    assert_eq!("dec".parse(), Ok(Relation::Decreased));
+    assert_eq!("12".parse(), Ok(Relation::Equal(12)));
+    assert_eq!("0x1F".parse(), Ok(Relation::Equal(0x1F)));
+    assert!("less".parse::<Relation>().is_err());
+    assert!("300".parse::<Relation>().is_err());
+    for rel in &[Relation::Same, Relation::Changed, Relation::Increased, Relation::Equal(9)] {
+        assert_eq!(rel.to_string().parse(), Ok(*rel));
+    }
+}
+
+#[test]
+fn search_narrows_down_to_the_counter() {
+    let mut mem = Memory::new();
+    set(&mut mem, &[(0x300, 3), (0x301, 3), (0x302, 9)]);
+    let mut search = RamSearch::new(&mut mem);
+    assert_eq!(search.candidates().len(), MEM_SIZE);
+
+    // a life lost, 0x301 drops for another reason
+    set(&mut mem, &[(0x300, 2), (0x301, 0)]);
+    assert_eq!(search.filter(&mut mem, Relation::Decreased), 2);
+
+    set(&mut mem, &[(0x301, 1)]);
+    search.filter(&mut mem, Relation::Equal(2));
+    assert_eq!(search.candidates(), &[0x300]);
+    assert_eq!(search.value(0x300), 2);
+}
+
+#[test]
+fn filters_compare_with_the_last_snapshot() {
+    let mut mem = Memory::new();
+    let mut search = RamSearch::new(&mut mem);
+    set(&mut mem, &[(0x300, 5)]);
+    search.filter(&mut mem, Relation::Changed);
+    assert_eq!(search.candidates(), &[0x300]);
+    assert_eq!(search.filter(&mut mem, Relation::Same), 1);
+}
+
+#[test]
+fn cheats_parse_and_print() {
+    let cheats: Cheats = "2f3=5,0300=FF".parse().unwrap();
+    assert_eq!(cheats.to_string(), "02F3=05,0300=FF");
+    assert!("300".parse::<Cheats>().is_err());
+    assert!("300=100".parse::<Cheats>().is_err());
+    assert!("".parse::<Cheats>().unwrap().is_empty());
+}
+
+#[test]
+fn refreezing_takes_the_new_value() {
+    let mut cheats: Cheats = "300=01,301=02".parse().unwrap();
+    cheats.apply("300=09").unwrap();
+    assert_eq!(cheats.to_string(), "0301=02,0300=09");
+    cheats.unfreeze(0x301);
+    assert_eq!(cheats.to_string(), "0300=09");
+}
+
+#[test]
+fn cheats_are_written_to_memory() {
+    let mut mem = Memory::new();
+    // past the end of memory is left out
+    let cheats: Cheats = "300=07,FFFF=01".parse().unwrap();
+    cheats.write_to(&mut mem);
+    assert_eq!(mem.get_u8(0x300), Some(7));
+}
@@ -0,0 +1,914 @@
+use memory::*;
+use stack::*;
+use cpu_ops::*;
+use media_if::*;
+use utils::*;
+use quirks::*;
+#[cfg(feature = "std")]
+use disasm::*;
+#[cfg(feature = "std")]
+use profile::*;
+use cheat::*;
+
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use alloc::string::String;
+
+// `cycles` is what the instruction costs on the COSMAC VIP in 1802
+// machine cycles, charged before it executes.
+type Cycles<M, V, F> = fn(&CPU<M, V, F>, ArgOctets) -> u32;
+type Executor<M, V, F> = fn(&mut CPU<M, V, F>, ArgOctets) -> Result<(), Fault>;
+
+struct OpCodeHandler<M, V, F> {
+    name: &'static str,
+    cycles: Cycles<M, V, F>,
+    executor: Executor<M, V, F>,
+}
+
+// fn pointers are Clone whatever the components are
+impl<M, V, F> Clone for OpCodeHandler<M, V, F> {
+    fn clone(&self) -> Self {
+        OpCodeHandler { name: self.name, cycles: self.cycles, executor: self.executor }
+    }
+}
+
+#[derive(Clone)]
+struct Isa<M, V, F> {
+    hmap: BTreeMap<Id, OpCodeHandler<M, V, F>>,
+}
+
+impl<M, V, F> Isa<M, V, F> {
+    fn new() -> Isa<M, V, F> {
+        Isa {
+            hmap: BTreeMap::new(),
+        }
+    }
+
+    fn register_opcode(&mut self, id: Id, handler: OpCodeHandler<M, V, F>) -> &mut Self {
+        self.hmap.insert(id, handler);
+        self
+    }
+}
+
+const NUM_GP_REGS: usize = 16;
+const PC_START_ADDR: u16 = 0x200;
+const VF: usize = 0xF;
+
+// XorShift must not be seeded with all zeros, so the seed is spread with
+// splitmix64 first
+fn rng_from_seed(seed: u64) -> XorShift128 {
+    let mut state = seed;
+    let mut bytes = [0u8; 16];
+
+    for chunk in bytes.chunks_mut(8) {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        for (i, b) in chunk.iter_mut().enumerate() {
+            *b = (z >> (i * 8)) as u8;
+        }
+    }
+
+    XorShift128::from_seed(bytes)
+}
+// The VIP interpreter draws a byte at a time: every sprite row costs
+// more when it is not aligned to a byte, as it is shifted into place bit
+// by bit. While DXYN waits for the vblank it only idles in a short loop.
+fn drw_cycles<M, V, F>(ctx: &CPU<M, V, F>, arg: ArgOctets) -> u32 {
+    if ctx.quirks.vblank && !ctx.vblank {
+        return 6;
+    }
+    let shift = (ctx.regs[arg.1 as usize] % 8) as u32;
+    68 + arg.3 as u32 * (46 + 20 * shift)
+}
+
+// Generic over the memory, the display and the frontend, so that a CPU can
+// own them or borrow them with &mut.
+#[derive(Clone)]
+pub struct CPU<M, V, F> {
+    ireg: u16,
+    pc: u16,
+    regs: [u8; NUM_GP_REGS],
+    delay_reg: u8,
+    sound_reg: u8,
+    stack: Stack,
+    isa: Isa<M, V, F>,
+    quirks: Quirks,
+    rng: XorShift128,
+    #[cfg(feature = "std")]
+    trace: bool,
+    #[cfg(feature = "std")]
+    profile: Option<Profile>,
+    cheats: Cheats,
+    // a frame ended since the last DXYN, for the vblank quirk
+    vblank: bool,
+    // the key FX0A saw pressed, it waits for the release
+    key_down: Option<u8>,
+    // VIP machine cycles charged since the CPU was created
+    cycles: u64,
+    cpu_mem: M,
+    gfx_mem: V,
+    media_if: F,
+}
+
+impl<M: CpuMemory, V: VideoMemory, F: MediaIf> CPU<M, V, F>
+{
+    pub fn new(cpu_mem: M, gfx_mem: V, media_if: F) -> CPU<M, V, F> {
+        let mut cpu = CPU
+        {
+            ireg: 0,
+            pc: PC_START_ADDR,
+            regs: [0; NUM_GP_REGS],
+            delay_reg: 0,
+            sound_reg: 0,
+            stack: Stack::default(),
+            isa: Isa::new(),
+            quirks: Quirks::default(),
+            // hosts pick a seed with set_seed, there is no entropy here
+            rng: rng_from_seed(0),
+            #[cfg(feature = "std")]
+            trace: false,
+            #[cfg(feature = "std")]
+            profile: None,
+            cheats: Cheats::new(),
+            vblank: false,
+            key_down: None,
+            cycles: 0,
+            cpu_mem,
+            gfx_mem,
+            media_if,
+        };
+
+        cpu.isa.register_opcode(
+            0x0000,
+            OpCodeHandler {
+                name: "INV",
+                cycles: |_, _| 12,
+                executor: |_ctx: &mut Self, _arg: ArgOctets| {
+                    #[cfg(feature = "std")]
+                    println!("invalid");
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode( 
+            0x00E0,
+            OpCodeHandler { 
+                name: "CLS",
+                cycles: |_, _| 24,
+                executor: |ctx: &mut Self, _arg: ArgOctets| {
+                    ctx.gfx_mem.clear();
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x00EE,
+            OpCodeHandler {
+                name: "RET",
+                cycles: |_, _| 23,
+                executor: |ctx: &mut Self, _arg: ArgOctets| {
+                    ctx.pc = ctx.stack.pop()
+                        .ok_or(Fault::StackUnderflow { pc: ctx.inst_addr() })?;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x1000,
+            OpCodeHandler {
+                name: "JP",
+                cycles: |_, _| 23,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.pc = to_addr((arg.1, arg.2, arg.3));
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x2000,
+            OpCodeHandler {
+                name: "CALL",
+                cycles: |_, _| 23,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.stack.push(ctx.pc)
+                        .ok_or(Fault::StackOverflow { pc: ctx.inst_addr() })?;
+                    ctx.pc = to_addr((arg.1, arg.2, arg.3));
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x3000,
+            OpCodeHandler {
+                name: "SE_BYTE",
+                cycles: |_, _| 12,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    if ctx.regs[arg.1 as usize] == to_u8((arg.2, arg.3)) {
+                        ctx.skip();
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x4000,
+            OpCodeHandler {
+                name: "SNE_BYTE",
+                cycles: |_, _| 12,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    if ctx.regs[arg.1 as usize] != to_u8((arg.2, arg.3)) {
+                        ctx.skip();
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x5000,
+            OpCodeHandler {
+                name: "SE_REG",
+                cycles: |_, _| 16,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    if ctx.regs[arg.1 as usize] == ctx.regs[arg.2 as usize] {
+                        ctx.skip();
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x6000,
+            OpCodeHandler {
+                name: "LD_BYTE",
+                cycles: |_, _| 6,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.regs[arg.1 as usize] = to_u8((arg.2, arg.3));
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x7000,
+            OpCodeHandler {
+                name: "ADD_BYTE",
+                cycles: |_, _| 10,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let vx = ctx.regs[arg.1 as usize];
+                    let vy = to_u8((arg.2, arg.3));
+                    let res = vx.overflowing_add(vy);
+
+                    ctx.regs[arg.1 as usize] = res.0;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x8000,
+            OpCodeHandler {
+                name: "LD",
+                cycles: |_, _| 44,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.regs[arg.1 as usize] = ctx.regs[arg.2 as usize];
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x8001,
+            OpCodeHandler {
+                name: "OR",
+                cycles: |_, _| 44,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.regs[arg.1 as usize] |= ctx.regs[arg.2 as usize];
+                    if ctx.quirks.logic {
+                        ctx.regs[VF] = 0;
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x8002,
+            OpCodeHandler {
+                name: "AND",
+                cycles: |_, _| 44,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.regs[arg.1 as usize] &= ctx.regs[arg.2 as usize];
+                    if ctx.quirks.logic {
+                        ctx.regs[VF] = 0;
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x8003,
+            OpCodeHandler {
+                name: "XOR",
+                cycles: |_, _| 44,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.regs[arg.1 as usize] ^= ctx.regs[arg.2 as usize];
+                    if ctx.quirks.logic {
+                        ctx.regs[VF] = 0;
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x8004,
+            OpCodeHandler {
+                name: "ADD",
+                cycles: |_, _| 44,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let vx = ctx.regs[arg.1 as usize];
+                    let vy = ctx.regs[arg.2 as usize];
+                    let res = vx.overflowing_add(vy);
+
+                    ctx.regs[arg.1 as usize] = res.0;
+                    ctx.regs[VF] = res.1 as u8;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x8005,
+            OpCodeHandler {
+                name: "SUB",
+                cycles: |_, _| 44,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let vx = ctx.regs[arg.1 as usize];
+                    let vy = ctx.regs[arg.2 as usize];
+                    let res = vx.overflowing_sub(vy);
+
+                    ctx.regs[arg.1 as usize] = res.0;
+                    ctx.regs[VF] = !res.1 as u8;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x8006,
+            OpCodeHandler {
+                name: "SHR",
+                cycles: |_, _| 44,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let src = if ctx.quirks.shift { arg.1 } else { arg.2 };
+                    let v = ctx.regs[src as usize];
+
+                    ctx.regs[arg.1 as usize] = v >> 1;
+                    ctx.regs[VF] = v & 0x1;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x8007,
+            OpCodeHandler {
+                name: "SUBN",
+                cycles: |_, _| 44,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let vx = ctx.regs[arg.1 as usize];
+                    let vy = ctx.regs[arg.2 as usize];
+                    let res = vy.overflowing_sub(vx);
+
+                    ctx.regs[arg.1 as usize] = res.0;
+                    ctx.regs[VF] = !res.1 as u8;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x800E,
+            OpCodeHandler {
+                name: "SHL",
+                cycles: |_, _| 44,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let src = if ctx.quirks.shift { arg.1 } else { arg.2 };
+                    let v = ctx.regs[src as usize];
+
+                    ctx.regs[arg.1 as usize] = v << 1;
+                    ctx.regs[VF] = v >> 7;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0x9000,
+            OpCodeHandler {
+                name: "SNE_REG",
+                cycles: |_, _| 16,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    if ctx.regs[arg.1 as usize] != ctx.regs[arg.2 as usize] {
+                        ctx.skip();
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xA000,
+            OpCodeHandler {
+                name: "LD_I",
+                cycles: |_, _| 12,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.ireg = ctx.address(to_addr((arg.1, arg.2, arg.3)) as usize)?;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xB000,
+            OpCodeHandler {
+                name: "LD_V0",
+                cycles: |_, _| 23,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let offset = if ctx.quirks.jump { ctx.regs[arg.1 as usize] } else { ctx.regs[0] };
+                    let target = to_addr((arg.1, arg.2, arg.3)) as usize + offset as usize;
+                    ctx.pc = ctx.address(target)?;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xC000,
+            OpCodeHandler {
+                name: "RND",
+                cycles: |_, _| 36,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let x = ctx.rng.next_u8();
+                    ctx.regs[arg.1 as usize] = x & to_u8((arg.2, arg.3));
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xD000,
+            OpCodeHandler {
+                name: "DRW",
+                cycles: drw_cycles,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    // executed again until the frame ends
+                    if ctx.quirks.vblank && !ctx.vblank {
+                        ctx.pc = ctx.inst_addr();
+                        return Ok(());
+                    }
+                    ctx.vblank = false;
+
+                    let x = ctx.regs[arg.1 as usize];
+                    let y = ctx.regs[arg.2 as usize];
+                    let mut sprites = [0u8; 15];
+                    for (i, s) in sprites.iter_mut().enumerate().take(arg.3 as usize) {
+                        *s = ctx.load(ctx.ireg as usize + i)?;
+                    }
+
+                    ctx.regs[VF] = ctx.gfx_mem.apply_sprites(x, y, &sprites[..arg.3 as usize], ctx.quirks.wrap).unwrap();
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xE09E,
+            OpCodeHandler {
+                name: "SKP_VX",
+                cycles: |_, _| 16,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    if ctx.media_if.is_key_pressed(ctx.regs[arg.1 as usize] & 0xF) {
+                        ctx.skip();
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xE0A1,
+            OpCodeHandler {
+                name: "SKNP_VX",
+                cycles: |_, _| 16,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    if !ctx.media_if.is_key_pressed(ctx.regs[arg.1 as usize] & 0xF) {
+                        ctx.skip();
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xF007,
+            OpCodeHandler {
+                name: "LD_VX_DT",
+                cycles: |_, _| 10,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.regs[arg.1 as usize] = ctx.delay_reg;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xF00A,
+            OpCodeHandler {
+                name: "W_KEY",
+                cycles: |_, _| 10,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    if ctx.quirks.key_held {
+                        let mut key = 20;
+                        for i in 0..16 {
+                            if ctx.media_if.is_key_pressed(i) {
+                                key = i;
+                            }
+                        }
+
+                        if key == 20 {
+                            ctx.pc = ctx.inst_addr();
+                        } else {
+                            ctx.regs[arg.1 as usize] = key;
+                        }
+                        return Ok(());
+                    }
+
+                    // the first key pressed, once it is released
+                    while let Some(event) = ctx.media_if.poll_key_event() {
+                        match event {
+                            KeyEvent::Down(k) if ctx.key_down.is_none() => ctx.key_down = Some(k),
+                            KeyEvent::Up(k) if ctx.key_down == Some(k) => {
+                                ctx.key_down = None;
+                                ctx.regs[arg.1 as usize] = k;
+                                return Ok(());
+                            },
+                            _ => {},
+                        }
+                    }
+                    ctx.pc = ctx.inst_addr();
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xF015,
+            OpCodeHandler {
+                name: "LD_DT_VX",
+                cycles: |_, _| 10,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.delay_reg = ctx.regs[arg.1 as usize];
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xF018,
+            OpCodeHandler {
+                name: "LD_ST_VX",
+                cycles: |_, _| 10,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    ctx.sound_reg = ctx.regs[arg.1 as usize];
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xF01E,
+            OpCodeHandler {
+                name: "ADD_I_VX",
+                cycles: |_, _| 19,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let i = ctx.ireg as usize + ctx.regs[arg.1 as usize] as usize;
+                    ctx.ireg = ctx.address(i)?;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xF029,
+            OpCodeHandler {
+                name: "LD_F_VX",
+                cycles: |_, _| 20,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let digit = ctx.regs[arg.1 as usize];
+                    let fault = Fault::BadAddress { pc: ctx.inst_addr(), addr: digit as usize };
+                    ctx.ireg = ctx.cpu_mem.get_font_sprite_addr(digit).ok_or(fault)?;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xF033,
+            OpCodeHandler {
+                name: "LD_B_VX",
+                cycles: |_, _| 204,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    let mut x = ctx.regs[arg.1 as usize];
+
+                    for i in (0..3usize).rev() {
+                        ctx.store(ctx.ireg as usize + i, x % 10)?;
+                        x /= 10;
+                    }
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xF055,
+            OpCodeHandler {
+                name: "LD_I_VX",
+                cycles: |_, _| 133,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    for i in 0..=arg.1 as usize {
+                        ctx.store(ctx.ireg as usize + i, ctx.regs[i])?;
+                    }
+                    ctx.advance_i(arg.1)?;
+                    Ok(())
+                },
+            });
+
+        cpu.isa.register_opcode(
+            0xF065,
+            OpCodeHandler {
+                name: "LD_VX_I",
+                cycles: |_, _| 133,
+                executor: |ctx: &mut Self, arg: ArgOctets| {
+                    for i in 0..=arg.1 as usize {
+                        ctx.regs[i] = ctx.load(ctx.ireg as usize + i)?;
+                    }
+                    ctx.advance_i(arg.1)?;
+                    Ok(())
+                },
+            });
+
+        cpu
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = rng_from_seed(seed);
+    }
+
+    // Return addresses CALL can push before it stops the CPU, None for
+    // unlimited. Empties the stack.
+    pub fn set_stack_depth(&mut self, depth: Option<usize>) {
+        self.stack = Stack::new(depth);
+    }
+
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    // Prints every executed instruction with the registers it saw.
+    #[cfg(feature = "std")]
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    // Counts executed instructions and times frames from now on.
+    #[cfg(feature = "std")]
+    pub fn set_profiling(&mut self, on: bool) {
+        self.profile = if on { Some(Profile::new()) } else { None };
+    }
+
+    #[cfg(feature = "std")]
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    // Writes the frozen values now and again after every frame.
+    pub fn set_cheats(&mut self, cheats: Cheats) {
+        cheats.write_to(&mut self.cpu_mem);
+        self.cheats = cheats;
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    // for tools that look at memory between frames, e.g. RAM search
+    pub fn memory(&mut self) -> &mut M {
+        &mut self.cpu_mem
+    }
+
+    pub fn display(&mut self) -> &mut V {
+        &mut self.gfx_mem
+    }
+
+    pub fn frontend(&mut self) -> &mut F {
+        &mut self.media_if
+    }
+
+    // the memory, display and frontend, e.g. to look at the screen
+    pub fn into_parts(self) -> (M, V, F) {
+        (self.cpu_mem, self.gfx_mem, self.media_if)
+    }
+
+    #[cfg(feature = "std")]
+    fn print_trace(&self, name: &str, arg: ArgOctets) {
+        let mut regs = String::with_capacity(3 * NUM_GP_REGS);
+        for r in self.regs.iter() {
+            regs.push_str(&format!("{:02X} ", r));
+        }
+
+        println!("{:04X}  {:04X}  {:<16}  {:<8}  V={}I={:04X} DT={:02X} ST={:02X}",
+                 self.pc - 2, to_id(arg), disassemble(to_id(arg)), name,
+                 regs, self.ireg, self.delay_reg, self.sound_reg);
+    }
+
+    // I after FX55/FX65
+    fn advance_i(&mut self, x: u8) -> Result<(), Fault> {
+        if self.quirks.memory_leave_i_unchanged {
+            return Ok(());
+        }
+
+        let step = if self.quirks.memory_increment_by_x { x as usize } else { x as usize + 1 };
+        self.ireg = self.address(self.ireg as usize + step)?;
+        Ok(())
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn ireg(&self) -> u16 {
+        self.ireg
+    }
+
+    // address of the executing instruction, fetch has moved PC past it
+    fn inst_addr(&self) -> u16 {
+        self.pc.wrapping_sub(2)
+    }
+
+    fn skip(&mut self) {
+        self.pc = self.pc.wrapping_add(2);
+    }
+
+    // Every access goes through here: addresses past the end of memory
+    // wrap around with the memory_wrap quirk, else they stop the CPU with
+    // the faulting address. `pc` is reported with the fault.
+    fn checked(&self, addr: usize, pc: u16) -> Result<u16, Fault> {
+        let size = self.cpu_mem.size();
+        if addr < size {
+            Ok(addr as u16)
+        } else if self.quirks.memory_wrap {
+            Ok((addr % size) as u16)
+        } else {
+            Err(Fault::BadAddress { pc, addr })
+        }
+    }
+
+    fn address(&self, addr: usize) -> Result<u16, Fault> {
+        self.checked(addr, self.inst_addr())
+    }
+
+    fn load(&mut self, addr: usize) -> Result<u8, Fault> {
+        let a = self.address(addr)?;
+        Ok(self.cpu_mem.get_u8(a).unwrap_or(0))
+    }
+
+    fn store(&mut self, addr: usize, val: u8) -> Result<(), Fault> {
+        let a = self.address(addr)?;
+        self.cpu_mem.set_u8(a, val);
+        Ok(())
+    }
+}
+
+impl<M: CpuMemory, V: VideoMemory, F: MediaIf> PipeLine for CPU<M, V, F>
+{
+    fn poll_input(&mut self) {
+        self.media_if.poll_input()
+    }
+
+    fn poll_control(&mut self) -> Option<Control> {
+        self.media_if.poll_control()
+    }
+
+    fn fetch(&mut self) -> Result<u16, Fault> {
+        let pc = self.pc;
+        let hi = self.checked(pc as usize, pc)?;
+        let lo = self.checked(pc as usize + 1, pc)?;
+        let cur_inst = (self.cpu_mem.get_u8(hi).unwrap_or(0) as u16) << 8
+            | self.cpu_mem.get_u8(lo).unwrap_or(0) as u16;
+        self.pc = hi.wrapping_add(2);
+
+        //println!("fetched instruction: {:04X}", cur_inst);
+        Ok(cur_inst)
+    }
+
+    fn decode(&self, instruction: u16) -> Option<(Id, ArgOctets)> {
+        let octs = to_octets(instruction);
+
+        let id = match octs {
+            (0x0, _, _, _) => to_id((0x0, 0x0, octs.2, octs.3)),
+            (0x1, _, _, _) |
+            (0x2, _, _, _) | 
+            (0x3, _, _, _) |
+            (0x4, _, _, _) | 
+            (0x6, _, _, _) |
+            (0x7, _, _, _) | 
+            (0xA, _, _, _) | 
+            (0xB, _, _, _) |
+            (0xC, _, _, _) | 
+            (0xD, _, _, _) => to_id((octs.0, 0x0, 0x0, 0x0)),
+            (0x5, _, _, _) |
+            (0x8, _, _, _) |
+            (0x9, _, _, _) => to_id((octs.0, 0x0, 0x0, octs.3)),
+            (0xE, _, _, _) |
+            (0xF, _, _, _) => to_id((octs.0, 0x0, octs.2, octs.3)),
+
+            (_, _, _, _) => 0,
+        };
+
+        //println!("decoded instruction: {:04X} => opcode {:04x}, arg {:?}",
+        //         instruction, id, octs);
+        Some((id, octs)) 
+    }
+
+    fn execute(&mut self, id: Id, arg: ArgOctets) -> Result<(), Fault> {
+        // the name is for traces and the profiler
+        #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+        let (name, cycles, executor) = match self.isa.hmap.get(&id) {
+            Some(handler) => (handler.name, handler.cycles, handler.executor),
+            None => return Err(Fault::InvalidInstruction { pc: self.inst_addr(), instruction: to_id(arg) }),
+        };
+
+        #[cfg(feature = "std")]
+        {
+            if self.trace {
+                self.print_trace(name, arg);
+            }
+            if let Some(ref mut profile) = self.profile {
+                profile.count(self.pc.wrapping_sub(2), to_id(arg), name);
+            }
+        }
+        self.cycles += cycles(self, arg) as u64;
+        executor(self, arg)
+    }
+
+    fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    fn update_timers(&mut self) {
+        self.delay_reg = self.delay_reg.saturating_sub(1);
+        self.sound_reg = self.sound_reg.saturating_sub(1);
+        self.media_if.set_sound(self.sound_reg > 0);
+    }
+
+    fn state_hash(&mut self) -> u64 {
+        let mut h = Fnv64::default();
+
+        h.write(&self.regs);
+        h.write(&[(self.ireg >> 8) as u8, self.ireg as u8,
+                  (self.pc >> 8) as u8, self.pc as u8,
+                  self.delay_reg, self.sound_reg]);
+        for addr in self.stack.return_addresses() {
+            h.write(&[(addr >> 8) as u8, *addr as u8]);
+        }
+        for addr in 0..self.cpu_mem.size() {
+            h.write(&[self.cpu_mem.get_u8(addr as u16).unwrap()]);
+        }
+        for row in self.gfx_mem.get_video_buf().unwrap().rows() {
+            h.write(&row.to_be_bytes());
+        }
+
+        h.finish()
+    }
+
+    // The screen is presented here once per frame, and only if it changed.
+    fn end_frame(&mut self) {
+        let dirty = self.gfx_mem.take_dirty();
+        let screen = self.gfx_mem.get_video_buf().unwrap();
+        if dirty {
+            self.media_if.clear_display();
+            self.media_if.draw_display(screen);
+            self.media_if.present_display();
+        }
+        self.media_if.end_frame(screen);
+        self.vblank = true;
+        self.cheats.write_to(&mut self.cpu_mem);
+        #[cfg(feature = "std")]
+        {
+            if let Some(ref mut profile) = self.profile {
+                profile.end_frame();
+            }
+        }
+    }
+
+    // Memory is left alone, the program starts over as it is now.
+    fn reset(&mut self) {
+        self.ireg = 0;
+        self.pc = PC_START_ADDR;
+        self.regs = [0; NUM_GP_REGS];
+        self.delay_reg = 0;
+        self.sound_reg = 0;
+        self.stack = Stack::new(self.stack.depth());
+        self.vblank = false;
+        self.key_down = None;
+        self.gfx_mem.clear();
+        self.media_if.set_sound(false);
+    }
+}
+
+#[cfg(test)]
+mod tests;
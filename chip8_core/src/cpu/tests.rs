@@ -0,0 +1,827 @@
+use super::*;
+use testing::*;
+
+// One test or more per handler registered in CPU::new, named after it.
+
+#[derive(Clone, Copy, Debug)]
+struct State {
+    v: [u8; NUM_GP_REGS],
+    i: u16,
+    pc: u16,
+    dt: u8,
+    st: u8,
+    cycles: u64,
+}
+
+type MockCpu<'a> = CPU<&'a mut MockMemory, &'a mut MockVideo, &'a mut MockMedia>;
+
+struct Rig {
+    mem: MockMemory,
+    video: MockVideo,
+    media: MockMedia,
+    quirks: Quirks,
+    stack_depth: Option<usize>,
+    // return addresses after the last run
+    stack: Vec<u16>,
+}
+
+impl Rig {
+    fn new() -> Rig {
+        Rig {
+            mem: MockMemory::new(),
+            video: MockVideo::new(),
+            media: MockMedia::new(),
+            quirks: Quirks::default(),
+            stack_depth: Some(DEFAULT_STACK_DEPTH),
+            stack: Vec::new(),
+        }
+    }
+
+    // Loads `program` at 0x200, lets `init` set up the registers and
+    // executes as many instructions as the program has.
+    fn run<F: FnOnce(&mut MockCpu)>(&mut self, program: &[u16], init: F) -> State {
+        self.try_run(program, init).unwrap()
+    }
+
+    fn try_run<F: FnOnce(&mut MockCpu)>(&mut self, program: &[u16], init: F) -> Result<State, Fault> {
+        self.execute(program, init, program.len(), 0)
+    }
+
+    // Executes `ipf` instructions and ends the frame, `frames` times.
+    fn run_frames(&mut self, program: &[u16], ipf: usize, frames: usize) -> State {
+        self.execute(program, |_| {}, ipf, frames).unwrap()
+    }
+
+    // Without frames only the instructions are executed.
+    fn execute<F: FnOnce(&mut MockCpu)>(&mut self, program: &[u16], init: F,
+                                    ipf: usize, frames: usize) -> Result<State, Fault> {
+        self.mem.load_program(PC_START_ADDR, program);
+
+        let mut cpu = CPU::new(&mut self.mem, &mut self.video, &mut self.media);
+        cpu.set_quirks(self.quirks);
+        cpu.set_seed(1);
+        cpu.set_stack_depth(self.stack_depth);
+        init(&mut cpu);
+
+        let result = (0..frames.max(1)).try_for_each(|_| {
+            for _ in 0..ipf {
+                let instruction = cpu.fetch()?;
+                let (id, arg) = cpu.decode(instruction).unwrap();
+                cpu.execute(id, arg)?;
+            }
+            if frames > 0 {
+                cpu.update_timers();
+                cpu.end_frame();
+            }
+            Ok(())
+        });
+        self.stack = cpu.stack().return_addresses().to_vec();
+        result?;
+
+        Ok(State {
+            v: cpu.regs,
+            i: cpu.ireg,
+            pc: cpu.pc,
+            dt: cpu.delay_reg,
+            st: cpu.sound_reg,
+            cycles: cpu.cycles,
+        })
+    }
+}
+
+fn run<F: FnOnce(&mut MockCpu)>(program: &[u16], init: F) -> State {
+    Rig::new().run(program, init)
+}
+
+fn fault<F: FnOnce(&mut MockCpu)>(program: &[u16], init: F) -> Fault {
+    Rig::new().try_run(program, init).unwrap_err()
+}
+
+fn regs(v: &[(usize, u8)]) -> impl FnOnce(&mut MockCpu) + '_ {
+    move |cpu: &mut MockCpu| {
+        for &(r, val) in v {
+            cpu.regs[r] = val;
+        }
+    }
+}
+
+// 8XY_ with VX = x and VY = y, returns (VX, VF)
+fn alu(op: u16, x: u8, y: u8) -> (u8, u8) {
+    let s = run(&[0x8120 | op], regs(&[(1, x), (2, y)]));
+    (s.v[1], s.v[VF])
+}
+
+// The original interpreter, every quirk off
+fn cosmac() -> Quirks {
+    Quirks {
+        shift: false,
+        memory_increment_by_x: false,
+        memory_leave_i_unchanged: false,
+        jump: false,
+        logic: false,
+        memory_wrap: false,
+        wrap: false,
+        vblank: false,
+        key_held: false,
+    }
+}
+
+fn wrapping() -> Rig {
+    Rig { quirks: Quirks { memory_wrap: true, ..Quirks::default() }, ..Rig::new() }
+}
+
+fn alu_with(quirks: Quirks, op: u16, x: u8, y: u8) -> (u8, u8) {
+    let mut rig = Rig::new();
+    rig.quirks = quirks;
+    let s = rig.run(&[0x8120 | op], regs(&[(1, x), (2, y)]));
+    (s.v[1], s.v[VF])
+}
+
+#[test]
+fn inv_is_skipped() {
+    let s = run(&[0x0000], |_| {});
+    assert_eq!(s.pc, 0x202);
+}
+
+#[test]
+fn cls() {
+    let mut rig = Rig::new();
+    rig.run(&[0x00E0], |_| {});
+
+    assert_eq!(rig.video.calls(), &[VideoCall::Clear]);
+    assert!(rig.video.dirty);
+    assert!(rig.media.calls().is_empty());
+}
+
+#[test]
+fn call_and_ret() {
+    let mut rig = Rig::new();
+    rig.mem.load_program(0x300, &[0x00EE]);
+    let s = rig.run(&[0x2300, 0x00EE], |_| {});
+
+    assert_eq!(s.pc, 0x202);
+    assert!(rig.stack.is_empty());
+}
+
+#[test]
+fn call_pushes_return_address() {
+    let mut rig = Rig::new();
+    let s = rig.run(&[0x2ABC], |_| {});
+
+    assert_eq!(s.pc, 0xABC);
+    assert_eq!(rig.stack, vec![0x202]);
+}
+
+#[test]
+fn jp() {
+    assert_eq!(run(&[0x1FFE], |_| {}).pc, 0xFFE);
+}
+
+#[test]
+fn se_byte() {
+    assert_eq!(run(&[0x3142], regs(&[(1, 0x42)])).pc, 0x204);
+    assert_eq!(run(&[0x3142], regs(&[(1, 0x43)])).pc, 0x202);
+}
+
+#[test]
+fn sne_byte() {
+    assert_eq!(run(&[0x4142], regs(&[(1, 0x42)])).pc, 0x202);
+    assert_eq!(run(&[0x4142], regs(&[(1, 0x43)])).pc, 0x204);
+}
+
+#[test]
+fn se_reg() {
+    assert_eq!(run(&[0x5120], regs(&[(1, 7), (2, 7)])).pc, 0x204);
+    assert_eq!(run(&[0x5120], regs(&[(1, 7), (2, 8)])).pc, 0x202);
+}
+
+#[test]
+fn sne_reg() {
+    assert_eq!(run(&[0x9120], regs(&[(1, 7), (2, 7)])).pc, 0x202);
+    assert_eq!(run(&[0x9120], regs(&[(1, 7), (2, 8)])).pc, 0x204);
+}
+
+#[test]
+fn ld_byte() {
+    assert_eq!(run(&[0x6AFF], |_| {}).v[0xA], 0xFF);
+}
+
+#[test]
+fn add_byte_wraps_without_flag() {
+    let s = run(&[0x7102], regs(&[(1, 0xFF), (VF, 5)]));
+    assert_eq!(s.v[1], 0x01);
+    assert_eq!(s.v[VF], 5);
+}
+
+#[test]
+fn ld() {
+    assert_eq!(alu(0x0, 1, 0x99), (0x99, 0));
+}
+
+#[test]
+fn or() {
+    assert_eq!(alu(0x1, 0xF0, 0x0F), (0xFF, 0));
+    let logic = Quirks { logic: true, ..cosmac() };
+    let s = Rig { quirks: logic, ..Rig::new() }.run(&[0x8121], regs(&[(1, 1), (VF, 1)]));
+    assert_eq!(s.v[VF], 0);
+}
+
+#[test]
+fn and() {
+    assert_eq!(alu(0x2, 0xF3, 0x3F), (0x33, 0));
+    let logic = Quirks { logic: true, ..cosmac() };
+    let s = Rig { quirks: logic, ..Rig::new() }.run(&[0x8122], regs(&[(1, 1), (VF, 1)]));
+    assert_eq!(s.v[VF], 0);
+}
+
+#[test]
+fn xor() {
+    assert_eq!(alu(0x3, 0xFF, 0x0F), (0xF0, 0));
+    let logic = Quirks { logic: true, ..cosmac() };
+    let s = Rig { quirks: logic, ..Rig::new() }.run(&[0x8123], regs(&[(1, 1), (VF, 1)]));
+    assert_eq!(s.v[VF], 0);
+}
+
+#[test]
+fn logic_leaves_vf_without_quirk() {
+    let s = run(&[0x8121], regs(&[(1, 1), (VF, 1)]));
+    assert_eq!(s.v[VF], 1);
+}
+
+#[test]
+fn add_sets_carry() {
+    assert_eq!(alu(0x4, 0xFF, 0x01), (0x00, 1));
+    assert_eq!(alu(0x4, 0xFE, 0x01), (0xFF, 0));
+}
+
+#[test]
+fn add_into_vf_keeps_flag() {
+    let s = run(&[0x8F14], regs(&[(VF, 0xFF), (1, 2)]));
+    assert_eq!(s.v[VF], 1);
+}
+
+#[test]
+fn sub_sets_not_borrow() {
+    assert_eq!(alu(0x5, 5, 3), (2, 1));
+    assert_eq!(alu(0x5, 3, 5), (0xFE, 0));
+    assert_eq!(alu(0x5, 3, 3), (0, 1));
+}
+
+#[test]
+fn subn_sets_not_borrow() {
+    assert_eq!(alu(0x7, 3, 5), (2, 1));
+    assert_eq!(alu(0x7, 5, 3), (0xFE, 0));
+    assert_eq!(alu(0x7, 3, 3), (0, 1));
+}
+
+#[test]
+fn shr_shifts_vy() {
+    assert_eq!(alu_with(cosmac(), 0x6, 0, 0x03), (0x01, 1));
+    assert_eq!(alu_with(cosmac(), 0x6, 0, 0x02), (0x01, 0));
+}
+
+#[test]
+fn shr_shifts_vx_with_quirk() {
+    let shift = Quirks { shift: true, ..cosmac() };
+    assert_eq!(alu_with(shift, 0x6, 0x81, 0), (0x40, 1));
+}
+
+#[test]
+fn shl_shifts_vy() {
+    assert_eq!(alu_with(cosmac(), 0xE, 0, 0x81), (0x02, 1));
+    assert_eq!(alu_with(cosmac(), 0xE, 0, 0x41), (0x82, 0));
+}
+
+#[test]
+fn shl_shifts_vx_with_quirk() {
+    let shift = Quirks { shift: true, ..cosmac() };
+    assert_eq!(alu_with(shift, 0xE, 0x81, 0), (0x02, 1));
+}
+
+#[test]
+fn ld_i() {
+    assert_eq!(run(&[0xA123], |_| {}).i, 0x123);
+}
+
+#[test]
+fn ld_v0() {
+    let s = Rig { quirks: cosmac(), ..Rig::new() }.run(&[0xB300], regs(&[(0, 0x10), (3, 0x20)]));
+    assert_eq!(s.pc, 0x310);
+
+    let jump = Quirks { jump: true, ..cosmac() };
+    let s = Rig { quirks: jump, ..Rig::new() }.run(&[0xB300], regs(&[(0, 0x10), (3, 0x20)]));
+    assert_eq!(s.pc, 0x320);
+}
+
+#[test]
+fn rnd_is_masked() {
+    for mask in &[0x00u16, 0x0F, 0xF0, 0x81] {
+        let s = run(&[0xC100 | mask], regs(&[(1, 0xFF)]));
+        assert_eq!(s.v[1] & !(*mask as u8), 0);
+    }
+}
+
+#[test]
+fn rnd_repeats_with_seed() {
+    let a = run(&[0xC1FF, 0xC2FF], |_| {});
+    let b = run(&[0xC1FF, 0xC2FF], |_| {});
+    assert_eq!(a.v, b.v);
+}
+
+#[test]
+fn drw() {
+    let mut rig = Rig::new();
+    rig.mem.load(0x300, &[0xAA, 0x55, 0xFF]);
+    rig.video.collision = 1;
+    let s = rig.run(&[0xD123], |cpu: &mut MockCpu| {
+        cpu.ireg = 0x300;
+        cpu.regs[1] = 62;
+        cpu.regs[2] = 31;
+    });
+
+    assert_eq!(s.v[VF], 1);
+    for addr in 0x300..0x303 {
+        assert!(rig.mem.calls().contains(&MemoryCall::GetU8(addr)));
+    }
+    assert_eq!(rig.video.calls()[0], VideoCall::ApplySprites(62, 31, vec![0xAA, 0x55, 0xFF], false));
+    assert!(rig.video.dirty);
+    assert!(rig.media.calls().is_empty());
+}
+
+#[test]
+fn drw_wraps_with_quirk() {
+    let mut rig = Rig { quirks: Quirks { wrap: true, ..cosmac() }, ..Rig::new() };
+    rig.run(&[0xD121], |_| {});
+    assert_eq!(rig.video.calls()[0], VideoCall::ApplySprites(0, 0, vec![0], true));
+}
+
+#[test]
+fn screen_is_presented_once_per_frame() {
+    let mut rig = Rig::new();
+    rig.run_frames(&[0xD121, 0xD121, 0x1204], 3, 2);
+
+    let calls: Vec<_> = rig.media.calls().iter()
+        .filter(|c| **c != MediaCall::SetSound(false))
+        .collect();
+    assert_eq!(calls, vec![&MediaCall::ClearDisplay, &MediaCall::DrawDisplay, &MediaCall::PresentDisplay,
+                           &MediaCall::EndFrame, &MediaCall::EndFrame]);
+}
+
+#[test]
+fn drw_waits_for_vblank() {
+    let sprites = |rig: &Rig| rig.video.calls().iter()
+        .filter(|c| matches!(c, VideoCall::ApplySprites(..)))
+        .count();
+
+    let mut rig = Rig { quirks: Quirks { vblank: true, ..cosmac() }, ..Rig::new() };
+    let s = rig.run_frames(&[0xD121, 0xD121], 10, 1);
+    assert_eq!(s.pc, 0x200);
+    assert_eq!(sprites(&rig), 0);
+
+    // drawn at the start of the second frame, the next DXYN waits again
+    let mut rig = Rig { quirks: Quirks { vblank: true, ..cosmac() }, ..Rig::new() };
+    let s = rig.run_frames(&[0xD121, 0xD121], 10, 2);
+    assert_eq!(s.pc, 0x202);
+    assert_eq!(sprites(&rig), 1);
+}
+
+#[test]
+fn drw_clears_vf_without_collision() {
+    let s = run(&[0xD121], regs(&[(VF, 1)]));
+    assert_eq!(s.v[VF], 0);
+}
+
+#[test]
+fn skp_vx() {
+    let mut rig = Rig::new();
+    rig.media.set_keys(1 << 0xA);
+    assert_eq!(rig.run(&[0xE19E], regs(&[(1, 0xA)])).pc, 0x204);
+    assert_eq!(rig.run(&[0xE19E], regs(&[(1, 0xB)])).pc, 0x202);
+}
+
+#[test]
+fn sknp_vx() {
+    let mut rig = Rig::new();
+    rig.media.set_keys(1 << 0xA);
+    assert_eq!(rig.run(&[0xE1A1], regs(&[(1, 0xA)])).pc, 0x202);
+    assert_eq!(rig.run(&[0xE1A1], regs(&[(1, 0xB)])).pc, 0x204);
+}
+
+#[test]
+fn ld_vx_dt() {
+    let s = run(&[0xF107], |cpu: &mut MockCpu| cpu.delay_reg = 0x3C);
+    assert_eq!(s.v[1], 0x3C);
+}
+
+#[test]
+fn w_key_blocks_without_key() {
+    let mut rig = Rig::new();
+    let s = rig.run(&[0xF30A, 0xF30A, 0xF30A], regs(&[(3, 0x77)]));
+
+    assert_eq!(s.pc, PC_START_ADDR);
+    assert_eq!(s.v[3], 0x77);
+}
+
+// Polls the keys of every frame and from frame `from` on executes F30A
+// once a frame, until it stores a key. Returns PC and V3.
+fn w_key_frames(frames: &[u16], from: usize) -> (u16, u8) {
+    let cpu_mem = &mut MockMemory::new();
+    cpu_mem.load_program(PC_START_ADDR, &[0xF30A]);
+    let video = &mut MockVideo::new();
+    let media = &mut MockMedia::with_keys(frames.to_vec());
+    let mut cpu = CPU::new(cpu_mem, video, media);
+
+    for frame in 0..frames.len() {
+        cpu.poll_input();
+        if frame >= from {
+            let instruction = cpu.fetch().unwrap();
+            let (id, arg) = cpu.decode(instruction).unwrap();
+            cpu.execute(id, arg).unwrap();
+            if cpu.pc != PC_START_ADDR {
+                break;
+            }
+        }
+        cpu.end_frame();
+    }
+    (cpu.pc, cpu.regs[3])
+}
+
+#[test]
+fn w_key_waits_for_release() {
+    assert_eq!(w_key_frames(&[1 << 0xC, 1 << 0xC, 1 << 0xC], 0), (PC_START_ADDR, 0));
+    assert_eq!(w_key_frames(&[0, 1 << 0xC, 1 << 0xC, 0], 0), (0x202, 0xC));
+}
+
+#[test]
+fn w_key_takes_the_first_key_pressed() {
+    assert_eq!(w_key_frames(&[1 << 2, 1 << 2 | 1 << 9, 1 << 9, 0], 0), (0x202, 2));
+}
+
+#[test]
+fn w_key_needs_a_press_during_the_wait() {
+    // 5 is down before the wait and released during it
+    assert_eq!(w_key_frames(&[1 << 5, 1 << 5, 0, 0], 1), (PC_START_ADDR, 0));
+}
+
+#[test]
+fn w_key_held_quirk_takes_a_key_down() {
+    let mut rig = Rig { quirks: Quirks { key_held: true, ..Quirks::default() }, ..Rig::new() };
+    rig.media.set_keys(1 << 0xC | 1 << 3);
+    let s = rig.run(&[0xF30A], |_| {});
+
+    // the highest key down
+    assert_eq!(s.pc, 0x202);
+    assert_eq!(s.v[3], 0xC);
+}
+
+#[test]
+fn ld_dt_vx() {
+    assert_eq!(run(&[0xF115], regs(&[(1, 60)])).dt, 60);
+}
+
+#[test]
+fn ld_st_vx() {
+    assert_eq!(run(&[0xF118], regs(&[(1, 2)])).st, 2);
+}
+
+#[test]
+fn timers_count_down_and_sound() {
+    let mut rig = Rig::new();
+    rig.mem.load_program(PC_START_ADDR, &[0xF118]);
+    {
+        let mut cpu = CPU::new(&mut rig.mem, &mut rig.video, &mut rig.media);
+        cpu.regs[1] = 2;
+        cpu.delay_reg = 1;
+        let (id, arg) = cpu.decode(0xF118).unwrap();
+        cpu.execute(id, arg).unwrap();
+
+        for _ in 0..3 {
+            cpu.update_timers();
+        }
+        assert_eq!((cpu.delay_reg, cpu.sound_reg), (0, 0));
+    }
+
+    let sound: Vec<&MediaCall> = rig.media.calls().iter()
+        .filter(|c| matches!(c, MediaCall::SetSound(_)))
+        .collect();
+    assert_eq!(sound, vec![&MediaCall::SetSound(true),
+                           &MediaCall::SetSound(false),
+                           &MediaCall::SetSound(false)]);
+}
+
+#[test]
+fn add_i_vx() {
+    let s = run(&[0xF11E], |cpu: &mut MockCpu| {
+        cpu.ireg = 0x0FF0;
+        cpu.regs[1] = 0x0F;
+    });
+    assert_eq!(s.i, 0x0FFF);
+}
+
+#[test]
+fn ld_f_vx() {
+    let mut rig = Rig::new();
+    let s = rig.run(&[0xF129], regs(&[(1, 0xA)]));
+
+    assert_eq!(s.i, 50);
+    assert!(rig.mem.calls().contains(&MemoryCall::GetFontSpriteAddr(0xA)));
+}
+
+#[test]
+fn ld_b_vx() {
+    for &(v, digits) in &[(255u8, [2u8, 5, 5]), (0, [0, 0, 0]), (9, [0, 0, 9]), (100, [1, 0, 0])] {
+        let mut rig = Rig::new();
+        let s = rig.run(&[0xF133], |cpu: &mut MockCpu| {
+            cpu.ireg = 0x300;
+            cpu.regs[1] = v;
+        });
+
+        assert_eq!(&rig.mem.bytes[0x300..0x303], &digits, "BCD of {}", v);
+        assert_eq!(s.i, 0x300);
+    }
+}
+
+#[test]
+fn ld_i_vx() {
+    let mut rig = Rig { quirks: cosmac(), ..Rig::new() };
+    let s = rig.run(&[0xF255], |cpu: &mut MockCpu| {
+        cpu.ireg = 0x300;
+        cpu.regs[..4].copy_from_slice(&[1, 2, 3, 4]);
+    });
+
+    assert_eq!(&rig.mem.bytes[0x300..0x304], &[1, 2, 3, 0]);
+    assert_eq!(s.i, 0x303);
+}
+
+#[test]
+fn ld_vx_i() {
+    let mut rig = Rig { quirks: cosmac(), ..Rig::new() };
+    rig.mem.load(0x300, &[9, 8, 7, 6]);
+    let s = rig.run(&[0xF265], |cpu: &mut MockCpu| cpu.ireg = 0x300);
+
+    assert_eq!(&s.v[..4], &[9, 8, 7, 0]);
+    assert_eq!(s.i, 0x303);
+}
+
+#[test]
+fn load_store_i_quirks() {
+    let by_x = Quirks { memory_increment_by_x: true, ..cosmac() };
+    let s = Rig { quirks: by_x, ..Rig::new() }.run(&[0xF255], |cpu: &mut MockCpu| cpu.ireg = 0x300);
+    assert_eq!(s.i, 0x302);
+
+    let leave = Quirks { memory_leave_i_unchanged: true, ..cosmac() };
+    let s = Rig { quirks: leave, ..Rig::new() }.run(&[0xF265], |cpu: &mut MockCpu| cpu.ireg = 0x300);
+    assert_eq!(s.i, 0x300);
+}
+
+#[test]
+fn unregistered_instructions_fault() {
+    for &inst in &[0x8128u16, 0x0123, 0x5121, 0xE1FF, 0xF1FF] {
+        assert_eq!(fault(&[inst], |_| {}),
+                   Fault::InvalidInstruction { pc: 0x200, instruction: inst });
+    }
+}
+
+#[test]
+fn ret_with_empty_stack_faults() {
+    assert_eq!(fault(&[0x6000, 0x00EE], |_| {}), Fault::StackUnderflow { pc: 0x202 });
+}
+
+#[test]
+fn call_with_full_stack_faults() {
+    let mut rig = Rig { stack_depth: Some(1), ..Rig::new() };
+    let f = rig.try_run(&[0x2202, 0x2204], |_| {});
+    assert_eq!(f.unwrap_err(), Fault::StackOverflow { pc: 0x202 });
+    assert_eq!(rig.stack, vec![0x202]);
+}
+
+// Every instruction calls the next one
+fn call_chain(n: usize) -> Vec<u16> {
+    (0..n as u16).map(|i| 0x2202 + 2 * i).collect()
+}
+
+#[test]
+fn vip_stack_holds_12_calls() {
+    let mut rig = Rig { stack_depth: Some(VIP_STACK_DEPTH), ..Rig::new() };
+    rig.run(&call_chain(12), |_| {});
+    assert_eq!(rig.stack.len(), 12);
+
+    let f = rig.try_run(&call_chain(13), |_| {});
+    assert_eq!(f.unwrap_err(), Fault::StackOverflow { pc: 0x218 });
+}
+
+#[test]
+fn unlimited_stack() {
+    let mut rig = Rig { stack_depth: None, ..Rig::new() };
+    rig.run(&call_chain(100), |_| {});
+    assert_eq!(rig.stack.len(), 100);
+}
+
+#[test]
+fn unlimited_stack_has_a_cap() {
+    let mut rig = Rig { mem: MockMemory::with_size(MAX_MEM_SIZE), stack_depth: None, ..Rig::new() };
+    // calls itself forever
+    let f = rig.try_run(&vec![0x2200; MAX_STACK_DEPTH + 1], |_| {});
+    assert_eq!(f.unwrap_err(), Fault::StackOverflow { pc: 0x200 });
+    assert_eq!(rig.stack.len(), MAX_STACK_DEPTH);
+}
+
+#[test]
+fn stack_lists_return_addresses() {
+    let mut rig = Rig::new();
+    rig.run(&call_chain(3), |_| {});
+    assert_eq!(rig.stack, vec![0x202, 0x204, 0x206]);
+}
+
+#[test]
+fn stack_is_apart_from_memory() {
+    let mut rig = Rig::new();
+    rig.run(&call_chain(16), |_| {});
+    assert!(rig.mem.calls().iter().all(|c| matches!(c, MemoryCall::GetU8(_))));
+}
+
+#[test]
+fn drw_past_end_of_memory_faults() {
+    let f = fault(&[0xD125], |cpu: &mut MockCpu| cpu.ireg = (MEM_SIZE - 2) as u16);
+    assert_eq!(f, Fault::BadAddress { pc: 0x200, addr: MEM_SIZE });
+}
+
+#[test]
+fn fetch_past_end_of_memory_faults() {
+    let mut rig = Rig::new();
+    rig.mem.load_program(PC_START_ADDR, &[0x1000 | (MEM_SIZE as u16 - 1)]);
+    let f = rig.try_run(&[0x1000 | (MEM_SIZE as u16 - 1), 0x0000], |_| {});
+    assert_eq!(f.unwrap_err(), Fault::BadAddress { pc: MEM_SIZE as u16 - 1, addr: MEM_SIZE });
+}
+
+#[test]
+fn i_past_end_of_memory_faults() {
+    let f = fault(&[0xF11E], |cpu: &mut MockCpu| {
+        cpu.ireg = (MEM_SIZE - 1) as u16;
+        cpu.regs[1] = 1;
+    });
+    assert_eq!(f, Fault::BadAddress { pc: 0x200, addr: MEM_SIZE });
+
+    let mut rig = Rig { quirks: cosmac(), ..Rig::new() };
+    let f = rig.try_run(&[0xFF55], |cpu: &mut MockCpu| cpu.ireg = (MEM_SIZE - 4) as u16);
+    assert_eq!(f.unwrap_err(), Fault::BadAddress { pc: 0x200, addr: MEM_SIZE });
+}
+
+#[test]
+fn jump_past_end_of_memory_faults() {
+    let f = fault(&[0xBFFF], regs(&[(0, 0xFF)]));
+    assert_eq!(f, Fault::BadAddress { pc: 0x200, addr: 0xFFF + 0xFF });
+}
+
+#[test]
+fn fetch_wraps_at_end_of_memory() {
+    let mut rig = wrapping();
+    rig.mem.load(0xFFF, &[0x12]);
+    rig.mem.load(0x000, &[0x34]);
+    let s = rig.run(&[0x1FFF, 0x0000], |_| {});
+    assert_eq!(s.pc, 0x234);
+}
+
+#[test]
+fn drw_wraps_at_end_of_memory() {
+    let mut rig = wrapping();
+    rig.mem.load(0xFFF, &[0xAA]);
+    rig.mem.load(0x000, &[0x55]);
+    rig.run(&[0xD122], |cpu: &mut MockCpu| cpu.ireg = 0xFFF);
+    assert_eq!(rig.video.calls()[0], VideoCall::ApplySprites(0, 0, vec![0xAA, 0x55], false));
+}
+
+#[test]
+fn i_wraps_at_end_of_memory() {
+    let s = wrapping().run(&[0xF11E], |cpu: &mut MockCpu| {
+        cpu.ireg = 0xFFF;
+        cpu.regs[1] = 3;
+    });
+    assert_eq!(s.i, 0x002);
+
+    let mut rig = wrapping();
+    rig.quirks.memory_leave_i_unchanged = false;
+    let s = rig.run(&[0xF155], |cpu: &mut MockCpu| {
+        cpu.ireg = 0xFFF;
+        cpu.regs[0] = 7;
+        cpu.regs[1] = 8;
+    });
+    assert_eq!((rig.mem.bytes[0xFFF], rig.mem.bytes[0]), (7, 8));
+    assert_eq!(s.i, 0x001);
+}
+
+#[test]
+fn larger_memory_is_addressable() {
+    let mut rig = Rig { mem: MockMemory::with_size(MAX_MEM_SIZE), ..Rig::new() };
+    let s = rig.run(&[0xF11E, 0xF155], |cpu: &mut MockCpu| {
+        cpu.ireg = 0xFFF;
+        cpu.regs[1] = 0x10;
+    });
+    assert_eq!(s.i, 0x100F);
+    assert_eq!(rig.mem.bytes[0x1010], 0x10);
+}
+
+#[test]
+fn cycles_are_charged_per_instruction() {
+    // LD_BYTE, ADD_BYTE, LD_I, LD_B_VX
+    let s = run(&[0x6005, 0x7001, 0xA300, 0xF033], |_| {});
+    assert_eq!(s.cycles, 6 + 10 + 12 + 204);
+}
+
+#[test]
+fn every_handler_costs_cycles() {
+    let cpu_mem = &mut MockMemory::new();
+    let video = &mut MockVideo::new();
+    let media = &mut MockMedia::new();
+    let cpu = CPU::new(cpu_mem, video, media);
+    for (id, handler) in cpu.isa.hmap.iter() {
+        assert!((handler.cycles)(&cpu, to_octets(*id)) > 0, "{}", handler.name);
+    }
+}
+
+#[test]
+fn drw_cycles_grow_with_height() {
+    let one = run(&[0xD011], |_| {}).cycles;
+    let five = run(&[0xD015], |_| {}).cycles;
+    assert_eq!(one, 68 + 46);
+    assert_eq!(five, 68 + 5 * 46);
+}
+
+#[test]
+fn drw_cycles_grow_with_misalignment() {
+    let aligned = run(&[0xD015], regs(&[(0, 8)])).cycles;
+    let shifted = run(&[0xD015], regs(&[(0, 11)])).cycles;
+    assert_eq!(shifted - aligned, 5 * 3 * 20);
+}
+
+#[test]
+fn drw_waiting_for_vblank_idles() {
+    let mut rig = Rig { quirks: Quirks { vblank: true, ..cosmac() }, ..Rig::new() };
+    let s = rig.run(&[0xD015], |cpu| cpu.vblank = false);
+    assert_eq!(s.cycles, 6);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn profiling_counts_handlers_and_frames() {
+    let cpu_mem = &mut MockMemory::new();
+    cpu_mem.load_program(PC_START_ADDR, &[0x6001, 0x7001, 0x1202]);
+    let video = &mut MockVideo::new();
+    let media = &mut MockMedia::new();
+    let mut cpu = CPU::new(cpu_mem, video, media);
+    cpu.set_profiling(true);
+    for _ in 0..7 {
+        let instruction = cpu.fetch().unwrap();
+        let (id, arg) = cpu.decode(instruction).unwrap();
+        cpu.execute(id, arg).unwrap();
+    }
+    cpu.end_frame();
+
+    let profile = cpu.take_profile().unwrap();
+    assert_eq!((profile.instructions(), profile.frames()), (7, 1));
+    assert!(profile.to_text().contains("  0202-0204"));
+    assert!(cpu.take_profile().is_none());
+}
+
+#[test]
+fn cheats_are_written_again_after_every_frame() {
+    let cpu_mem = &mut MockMemory::new();
+    // LD I, 0x300; LD V0, 9; LD [I], V0
+    cpu_mem.load_program(PC_START_ADDR, &[0xA300, 0x6009, 0xF055]);
+    let video = &mut MockVideo::new();
+    let media = &mut MockMedia::new();
+    let mut cpu = CPU::new(cpu_mem, video, media);
+    cpu.set_cheats("300=02".parse().unwrap());
+    assert_eq!(cpu.memory().get_u8(0x300), Some(2));
+
+    for _ in 0..3 {
+        let instruction = cpu.fetch().unwrap();
+        let (id, arg) = cpu.decode(instruction).unwrap();
+        cpu.execute(id, arg).unwrap();
+    }
+    assert_eq!(cpu.memory().get_u8(0x300), Some(9));
+    cpu.end_frame();
+    assert_eq!(cpu.memory().get_u8(0x300), Some(2));
+}
+
+#[test]
+fn reset_starts_over_and_keeps_memory() {
+    let cpu_mem = &mut MockMemory::new();
+    // LD I, 0x300; LD V0, 9; LD [I], V0; CALL 0x200
+    cpu_mem.load_program(PC_START_ADDR, &[0xA300, 0x6009, 0xF055, 0x2200]);
+    let video = &mut MockVideo::new();
+    let media = &mut MockMedia::new();
+    let mut cpu = CPU::new(cpu_mem, video, media);
+    for _ in 0..4 {
+        let instruction = cpu.fetch().unwrap();
+        let (id, arg) = cpu.decode(instruction).unwrap();
+        cpu.execute(id, arg).unwrap();
+    }
+
+    cpu.reset();
+    assert_eq!((cpu.pc, cpu.ireg, cpu.regs), (PC_START_ADDR, 0, [0; NUM_GP_REGS]));
+    assert!(cpu.stack().is_empty());
+    assert_eq!(cpu.memory().get_u8(0x300), Some(9));
+    assert_eq!(cpu.display().calls().last(), Some(&VideoCall::Clear));
+    assert_eq!(cpu.frontend().calls().last(), Some(&MediaCall::SetSound(false)));
+}
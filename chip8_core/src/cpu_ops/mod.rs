@@ -0,0 +1,50 @@
+use media_if::*;
+
+use core::fmt;
+
+pub type ArgOctets = (u8, u8, u8, u8);
+pub type Id = u16;
+
+// Why the CPU stopped. `pc` is the address of the faulting instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    // a word that is no instruction
+    InvalidInstruction { pc: u16, instruction: u16 },
+    // RET without CALL
+    StackUnderflow { pc: u16 },
+    // CALL with a full stack
+    StackOverflow { pc: u16 },
+    // access to or jump to an address outside of memory
+    BadAddress { pc: u16, addr: usize },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Fault::InvalidInstruction { pc, instruction } => {
+                write!(f, "invalid instruction {:04X} at {:04X}", instruction, pc)
+            },
+            Fault::StackUnderflow { pc } => write!(f, "return with an empty stack at {:04X}", pc),
+            Fault::StackOverflow { pc } => write!(f, "call with a full stack at {:04X}", pc),
+            Fault::BadAddress { pc, addr } => {
+                write!(f, "address {:04X} outside of memory at {:04X}", addr, pc)
+            },
+        }
+    }
+}
+
+pub trait PipeLine {
+    // the frontend reads its input before every frame
+    fn poll_input(&mut self);
+    fn poll_control(&mut self) -> Option<Control>;
+    fn fetch(&mut self) -> Result<u16, Fault>;
+    fn decode(&self, instruction: u16) -> Option<(Id, ArgOctets)>;
+    fn execute(&mut self, id: Id, arg: ArgOctets) -> Result<(), Fault>;
+    // COSMAC VIP machine cycles of everything executed so far
+    fn cycles(&self) -> u64;
+    fn update_timers(&mut self);
+    fn end_frame(&mut self);
+    fn reset(&mut self);
+    // hash over registers, memory and VRAM, used to detect movie desyncs
+    fn state_hash(&mut self) -> u64;
+}
@@ -0,0 +1,73 @@
+use utils::*;
+
+use alloc::string::{String, ToString};
+use core::fmt::Write;
+
+// Mnemonic of one instruction in the usual Cowgod notation, words that are
+// no instruction come out as "DW 0x1234".
+pub fn disassemble(instruction: u16) -> String {
+    let (o, x, y, n) = to_octets(instruction);
+    let nnn = instruction & 0xFFF;
+    let kk = to_u8((y, n));
+
+    match (o, x, y, n) {
+        (0x0, 0x0, 0xE, 0x0) => "CLS".to_string(),
+        (0x0, 0x0, 0xE, 0xE) => "RET".to_string(),
+        (0x0, _, _, _) => format!("SYS 0x{:03X}", nnn),
+        (0x1, _, _, _) => format!("JP 0x{:03X}", nnn),
+        (0x2, _, _, _) => format!("CALL 0x{:03X}", nnn),
+        (0x3, _, _, _) => format!("SE V{:X}, 0x{:02X}", x, kk),
+        (0x4, _, _, _) => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        (0x5, _, _, 0x0) => format!("SE V{:X}, V{:X}", x, y),
+        (0x6, _, _, _) => format!("LD V{:X}, 0x{:02X}", x, kk),
+        (0x7, _, _, _) => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        (0x8, _, _, 0x0) => format!("LD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x1) => format!("OR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x2) => format!("AND V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x3) => format!("XOR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x4) => format!("ADD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x5) => format!("SUB V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x6) => format!("SHR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (0x9, _, _, 0x0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, _, _, _) => format!("LD I, 0x{:03X}", nnn),
+        (0xB, _, _, _) => format!("JP V0, 0x{:03X}", nnn),
+        (0xC, _, _, _) => format!("RND V{:X}, 0x{:02X}", x, kk),
+        (0xD, _, _, _) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        (0xE, _, 0x9, 0xE) => format!("SKP V{:X}", x),
+        (0xE, _, 0xA, 0x1) => format!("SKNP V{:X}", x),
+        (0xF, _, 0x0, 0x7) => format!("LD V{:X}, DT", x),
+        (0xF, _, 0x0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF, _, 0x1, 0x5) => format!("LD DT, V{:X}", x),
+        (0xF, _, 0x1, 0x8) => format!("LD ST, V{:X}", x),
+        (0xF, _, 0x1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF, _, 0x2, 0x9) => format!("LD F, V{:X}", x),
+        (0xF, _, 0x3, 0x3) => format!("LD B, V{:X}", x),
+        (0xF, _, 0x5, 0x5) => format!("LD [I], V{:X}", x),
+        (0xF, _, 0x6, 0x5) => format!("LD V{:X}, [I]", x),
+        _ => format!("DW 0x{:04X}", instruction),
+    }
+}
+
+// Linear sweep over a ROM loaded at `start`, one "ADDR  WORD  MNEMONIC"
+// line per instruction. Code and data are not told apart.
+pub fn listing(exe: &[u8], start: u16) -> String {
+    let mut out = String::new();
+
+    for (i, word) in exe.chunks(2).enumerate() {
+        let addr = start as usize + i * 2;
+        if word.len() < 2 {
+            writeln!(out, "{:04X}  {:02X}    DB 0x{:02X}", addr, word[0], word[0]).unwrap();
+            break;
+        }
+
+        let instruction = (word[0] as u16) << 8 | word[1] as u16;
+        writeln!(out, "{:04X}  {:04X}  {}", addr, instruction, disassemble(instruction)).unwrap();
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,36 @@
+use super::*;
+
+#[test]
+fn mnemonics() {
+    assert_eq!(disassemble(0x00E0), "CLS");
+    assert_eq!(disassemble(0x00EE), "RET");
+    assert_eq!(disassemble(0x0123), "SYS 0x123");
+    assert_eq!(disassemble(0x2ABC), "CALL 0xABC");
+    assert_eq!(disassemble(0x3A0F), "SE VA, 0x0F");
+    assert_eq!(disassemble(0x8126), "SHR V1, V2");
+    assert_eq!(disassemble(0xB300), "JP V0, 0x300");
+    assert_eq!(disassemble(0xD125), "DRW V1, V2, 5");
+    assert_eq!(disassemble(0xE5A1), "SKNP V5");
+    assert_eq!(disassemble(0xF70A), "LD V7, K");
+    assert_eq!(disassemble(0xF355), "LD [I], V3");
+}
+
+#[test]
+fn data_words() {
+    assert_eq!(disassemble(0x5121), "DW 0x5121");
+    assert_eq!(disassemble(0x812F), "DW 0x812F");
+    assert_eq!(disassemble(0xFFFF), "DW 0xFFFF");
+}
+
+#[test]
+fn listing_of_bytes() {
+    // the start of MAZE and an odd last byte
+    let exe = [0xA2, 0x1E, 0xC2, 0x01, 0x32, 0x01, 0xA2, 0x1A, 0xD0, 0x14, 0x70];
+    assert_eq!(listing(&exe, 0x200),
+               "0200  A21E  LD I, 0x21E\n\
+                0202  C201  RND V2, 0x01\n\
+                0204  3201  SE V2, 0x01\n\
+                0206  A21A  LD I, 0x21A\n\
+                0208  D014  DRW V0, V1, 4\n\
+                020A  70    DB 0x70\n");
+}
@@ -0,0 +1,28 @@
+// The CPU, memory, display and the frontend interface of the emulator.
+// Built without the std feature it needs no more than alloc, so that it
+// runs on microcontrollers and inside other hosts.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate serde_derive;
+
+pub mod cpu;
+pub mod machine;
+pub mod cpu_ops;
+pub mod memory;
+pub mod stack;
+pub mod timing;
+pub mod sprites;
+pub mod utils;
+pub mod media_if;
+pub mod quirks;
+pub mod disasm;
+#[cfg(feature = "std")]
+pub mod profile;
+pub mod cheat;
+pub mod testing;
@@ -0,0 +1,145 @@
+use cpu::*;
+use cpu_ops::*;
+use media_if::*;
+use memory::*;
+use quirks::*;
+use timing::*;
+
+// A CHIP-8 machine that owns its memory, display and frontend, unlike a CPU
+// borrowing them. It can be kept in a struct, cloned for a snapshot or
+// moved to another thread: it is Send and Clone when the frontend is.
+#[derive(Clone)]
+pub struct Machine<F> {
+    cpu: CPU<Memory, Display, F>,
+    ipf: u32,
+    timing: Timing,
+    // the machine cycles vip timing runs up to
+    budget: u64,
+    paused: bool,
+}
+
+pub fn step<P: PipeLine>(pl: &mut P) -> Result<(), Fault> {
+    let instruction = pl.fetch()?;
+    let (id, arg) = pl.decode(instruction).unwrap();
+    pl.execute(id, arg)
+}
+
+// One 60 Hz frame: `ipf` instructions with ipf timing, with vip timing as
+// many as fit in the cycles the VIP has for a frame, an instruction that
+// overruns taking its cycles from the next one. `budget` carries the
+// cycles from frame to frame.
+pub fn run_frame<P: PipeLine>(pl: &mut P, ipf: u32, timing: Timing, budget: &mut u64) -> Result<(), Fault> {
+    match timing {
+        Timing::Ipf => {
+            for _ in 0..ipf {
+                step(pl)?;
+            }
+        },
+        Timing::Vip => {
+            *budget += vip_frame_cycles();
+            while pl.cycles() < *budget {
+                step(pl)?;
+            }
+        },
+    }
+    // the VIP counts the timers down in its 60 Hz interrupt
+    pl.update_timers();
+    pl.end_frame();
+    Ok(())
+}
+
+// The speed after a Control::Speed of `delta`, within 1..=MAX_SPEED.
+pub fn change_speed(ipf: u32, delta: i32) -> u32 {
+    (ipf as i64 + delta as i64).clamp(1, MAX_SPEED as i64) as u32
+}
+
+// Lets the frontend read its input before a frame and carries out its
+// requests: resets here, pauses by toggling `paused`. Returns the change of
+// speed asked for, None to quit.
+pub fn poll_frontend<P: PipeLine>(pl: &mut P, paused: &mut bool) -> Option<i32> {
+    pl.poll_input();
+    let mut speed = 0;
+    while let Some(control) = pl.poll_control() {
+        match control {
+            Control::Quit => return None,
+            Control::Pause => *paused = !*paused,
+            Control::Reset => pl.reset(),
+            Control::Speed(delta) => speed += delta,
+        }
+    }
+    Some(speed)
+}
+
+impl<F: MediaIf> Machine<F> {
+    // `mem` with the sprites and the program loaded
+    pub fn new(mem: Memory, frontend: F) -> Self {
+        Machine {
+            cpu: CPU::new(mem, Display::new(), frontend),
+            ipf: DEFAULT_TICKRATE,
+            timing: Timing::default(),
+            budget: 0,
+            paused: false,
+        }
+    }
+
+    pub fn set_speed(&mut self, ipf: u32, timing: Timing) {
+        self.ipf = ipf;
+        self.timing = timing;
+    }
+
+    // instructions per frame
+    pub fn speed(&self) -> u32 {
+        self.ipf
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.cpu.set_quirks(quirks);
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.cpu.set_seed(seed);
+    }
+
+    // for everything else the CPU has, e.g. the stack depth or cheats
+    pub fn cpu(&mut self) -> &mut CPU<Memory, Display, F> {
+        &mut self.cpu
+    }
+
+    pub fn memory(&mut self) -> &mut Memory {
+        self.cpu.memory()
+    }
+
+    pub fn display(&mut self) -> &mut Display {
+        self.cpu.display()
+    }
+
+    pub fn frontend(&mut self) -> &mut F {
+        self.cpu.frontend()
+    }
+
+    pub fn run_frame(&mut self) -> Result<(), Fault> {
+        run_frame(&mut self.cpu, self.ipf, self.timing, &mut self.budget)
+    }
+
+    // Runs until the frontend quits, pausing, resetting and changing the
+    // speed as it asks. run_frame leaves all that to the caller.
+    pub fn run(&mut self) -> Result<(), Fault> {
+        while let Some(delta) = poll_frontend(&mut self.cpu, &mut self.paused) {
+            if self.timing == Timing::Ipf {
+                self.ipf = change_speed(self.ipf, delta);
+            }
+            if !self.paused {
+                self.run_frame()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn state_hash(&mut self) -> u64 {
+        self.cpu.state_hash()
+    }
+
+    pub fn into_parts(self) -> (Memory, Display, F) {
+        self.cpu.into_parts()
+    }
+}
@@ -0,0 +1,330 @@
+use memory::*;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+// A frontend is made of four parts: where the screen and the buzzer go,
+// where the keypad comes from and a control channel for the user's
+// requests to the emulator. One backend like SDL can be all of them, or
+// `Frontend` puts separate ones together, e.g. a headless video sink with
+// a scripted input source.
+
+pub trait VideoSink {
+    fn draw_display(&mut self, buf: Screen) -> Option<u8>;
+    fn clear_display(&mut self) -> Option<u8>;
+    fn present_display(&mut self) -> Option<u8>;
+    // called once per emulated 60 Hz frame with the current screen
+    fn end_frame(&mut self, buf: Screen) -> Option<u8>;
+}
+
+pub trait AudioSink {
+    // the buzzer sounds while the sound timer is non-zero
+    fn set_sound(&mut self, on: bool);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyEvent {
+    Down(u8),
+    Up(u8),
+}
+
+pub trait InputSource {
+    // Called once before every frame, keys only change here.
+    fn poll_input(&mut self);
+    fn is_key_pressed(&mut self, key: u8) -> bool;
+    fn get_pressed_key(&self) -> Option<&u8>;
+    // The presses and releases the last poll_input saw, each handed out
+    // once. What is not taken is dropped by the next poll.
+    fn poll_key_event(&mut self) -> Option<KeyEvent>;
+}
+
+// The presses and releases between the keypads of two polls, for sources
+// that know which keys are down.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeyEdges {
+    keys: u16,
+    pressed: u16,
+    released: u16,
+}
+
+impl KeyEdges {
+    // `keys` are down now, bit k for key k
+    pub fn update(&mut self, keys: u16) {
+        self.pressed = keys & !self.keys;
+        self.released = self.keys & !keys;
+        self.keys = keys;
+    }
+
+    // presses before releases, lower keys first
+    pub fn next_event(&mut self) -> Option<KeyEvent> {
+        if self.pressed != 0 {
+            let k = self.pressed.trailing_zeros() as u8;
+            self.pressed &= !(1 << k);
+            return Some(KeyEvent::Down(k));
+        }
+        if self.released != 0 {
+            let k = self.released.trailing_zeros() as u8;
+            self.released &= !(1 << k);
+            return Some(KeyEvent::Up(k));
+        }
+        None
+    }
+}
+
+// What the user asks of the emulator, outside of the game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    Quit,
+    // pauses or resumes
+    Pause,
+    // the reset switch: registers, stack, timers and screen are cleared,
+    // memory is kept
+    Reset,
+    // change of instructions per frame, e.g. through hotkeys
+    Speed(i32),
+}
+
+pub trait ControlSource {
+    // The next request, polled after the input until there is none. Every
+    // request is handed out once, only Quit may be repeated.
+    fn poll_control(&mut self) -> Option<Control>;
+}
+
+// Everything the CPU needs of a frontend.
+pub trait MediaIf: VideoSink + AudioSink + InputSource + ControlSource {}
+
+impl<T: VideoSink + AudioSink + InputSource + ControlSource + ?Sized> MediaIf for T {}
+
+// so that a CPU can borrow its frontend
+impl<T: VideoSink + ?Sized> VideoSink for &mut T {
+    fn draw_display(&mut self, buf: Screen) -> Option<u8> {
+        (**self).draw_display(buf)
+    }
+
+    fn clear_display(&mut self) -> Option<u8> {
+        (**self).clear_display()
+    }
+
+    fn present_display(&mut self) -> Option<u8> {
+        (**self).present_display()
+    }
+
+    fn end_frame(&mut self, buf: Screen) -> Option<u8> {
+        (**self).end_frame(buf)
+    }
+}
+
+impl<T: AudioSink + ?Sized> AudioSink for &mut T {
+    fn set_sound(&mut self, on: bool) {
+        (**self).set_sound(on)
+    }
+}
+
+impl<T: InputSource + ?Sized> InputSource for &mut T {
+    fn poll_input(&mut self) {
+        (**self).poll_input()
+    }
+
+    fn is_key_pressed(&mut self, key: u8) -> bool {
+        (**self).is_key_pressed(key)
+    }
+
+    fn get_pressed_key(&self) -> Option<&u8> {
+        (**self).get_pressed_key()
+    }
+
+    fn poll_key_event(&mut self) -> Option<KeyEvent> {
+        (**self).poll_key_event()
+    }
+}
+
+impl<T: ControlSource + ?Sized> ControlSource for &mut T {
+    fn poll_control(&mut self) -> Option<Control> {
+        (**self).poll_control()
+    }
+}
+
+// `()` is the part that is left out: no screen, silence, no key down and
+// no requests.
+impl VideoSink for () {
+    fn draw_display(&mut self, _buf: Screen) -> Option<u8> {
+        Some(0)
+    }
+
+    fn clear_display(&mut self) -> Option<u8> {
+        Some(0)
+    }
+
+    fn present_display(&mut self) -> Option<u8> {
+        Some(0)
+    }
+
+    fn end_frame(&mut self, _buf: Screen) -> Option<u8> {
+        Some(0)
+    }
+}
+
+impl AudioSink for () {
+    fn set_sound(&mut self, _on: bool) {}
+}
+
+impl InputSource for () {
+    fn poll_input(&mut self) {}
+
+    fn is_key_pressed(&mut self, _key: u8) -> bool {
+        false
+    }
+
+    fn get_pressed_key(&self) -> Option<&u8> {
+        None
+    }
+
+    fn poll_key_event(&mut self) -> Option<KeyEvent> {
+        None
+    }
+}
+
+impl ControlSource for () {
+    fn poll_control(&mut self) -> Option<Control> {
+        None
+    }
+}
+
+// A frontend put together from separate parts.
+#[derive(Clone, Default)]
+pub struct Frontend<V, A, I, C> {
+    pub video: V,
+    pub audio: A,
+    pub input: I,
+    pub control: C,
+}
+
+impl<V, A, I, C> Frontend<V, A, I, C> {
+    pub fn new(video: V, audio: A, input: I, control: C) -> Self {
+        Frontend { video, audio, input, control }
+    }
+}
+
+impl<V: VideoSink, A, I, C> VideoSink for Frontend<V, A, I, C> {
+    fn draw_display(&mut self, buf: Screen) -> Option<u8> {
+        self.video.draw_display(buf)
+    }
+
+    fn clear_display(&mut self) -> Option<u8> {
+        self.video.clear_display()
+    }
+
+    fn present_display(&mut self) -> Option<u8> {
+        self.video.present_display()
+    }
+
+    fn end_frame(&mut self, buf: Screen) -> Option<u8> {
+        self.video.end_frame(buf)
+    }
+}
+
+impl<V, A: AudioSink, I, C> AudioSink for Frontend<V, A, I, C> {
+    fn set_sound(&mut self, on: bool) {
+        self.audio.set_sound(on)
+    }
+}
+
+impl<V, A, I: InputSource, C> InputSource for Frontend<V, A, I, C> {
+    fn poll_input(&mut self) {
+        self.input.poll_input()
+    }
+
+    fn is_key_pressed(&mut self, key: u8) -> bool {
+        self.input.is_key_pressed(key)
+    }
+
+    fn get_pressed_key(&self) -> Option<&u8> {
+        self.input.get_pressed_key()
+    }
+
+    fn poll_key_event(&mut self) -> Option<KeyEvent> {
+        self.input.poll_key_event()
+    }
+}
+
+impl<V, A, I, C: ControlSource> ControlSource for Frontend<V, A, I, C> {
+    fn poll_control(&mut self) -> Option<Control> {
+        self.control.poll_control()
+    }
+}
+
+pub const KEY_IDS: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+// Keys scripted per frame: bit k of keys[n] is key k during frame n,
+// frames past the end of the script have no key down.
+#[derive(Clone, Default)]
+pub struct ScriptedInput {
+    keys: Vec<u16>,
+    // frames polled so far
+    polls: usize,
+    edges: KeyEdges,
+}
+
+impl ScriptedInput {
+    pub fn new(keys: Vec<u16>) -> Self {
+        ScriptedInput { keys, polls: 0, edges: KeyEdges::default() }
+    }
+
+    // the keys of the frame being run
+    pub fn current(&self) -> u16 {
+        self.polls.checked_sub(1).and_then(|f| self.keys.get(f)).cloned().unwrap_or(0)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.polls >= self.keys.len()
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll_input(&mut self) {
+        self.polls += 1;
+        self.edges.update(self.current());
+    }
+
+    fn is_key_pressed(&mut self, key: u8) -> bool {
+        key < 16 && self.current() & (1 << key) != 0
+    }
+
+    fn get_pressed_key(&self) -> Option<&u8> {
+        let keys = self.current();
+        KEY_IDS.iter().find(|&&k| keys & (1 << k) != 0)
+    }
+
+    fn poll_key_event(&mut self) -> Option<KeyEvent> {
+        self.edges.next_event()
+    }
+}
+
+// Requests queued on the thread that runs the machine, e.g. by a
+// backend's hotkeys or by the host between frames, handed out in order.
+// It is a plain queue: requests from another thread have to be passed
+// over, e.g. through a channel, and sent from here.
+#[derive(Clone, Debug, Default)]
+pub struct ControlQueue {
+    pending: VecDeque<Control>,
+}
+
+impl ControlQueue {
+    pub fn new() -> Self {
+        ControlQueue { pending: VecDeque::new() }
+    }
+
+    pub fn send(&mut self, control: Control) {
+        self.pending.push_back(control);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl ControlSource for ControlQueue {
+    fn poll_control(&mut self) -> Option<Control> {
+        self.pending.pop_front()
+    }
+}
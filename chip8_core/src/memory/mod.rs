@@ -0,0 +1,311 @@
+use utils::*;
+
+use alloc::vec::Vec;
+
+const ROM_START_ADDR: usize = 0;
+pub const EXE_START_ADDR: usize = 0x200;
+const SPRITE_SIZE: usize = 0x5;
+// CHIP-8 memory, extended platforms like XO-CHIP have up to MAX_MEM_SIZE
+pub const MEM_SIZE: usize = 0x1000;
+pub const MAX_MEM_SIZE: usize = 0x10000;
+// largest ROM that fits between EXE_START_ADDR and the end of memory
+pub const MAX_EXE_SIZE: usize = MEM_SIZE - EXE_START_ADDR;
+
+// Accesses outside of memory return None, what the CPU makes of it
+// depends on the memory_wrap quirk.
+
+pub trait CpuMemory {
+    fn get_font_sprite(&self, s_n: u8) -> Option<&[u8]>;
+    fn get_font_sprite_addr(&self, s_n: u8) -> Option<u16>;
+    fn get_sprites(&self, addr: u16, n: u8) -> Option<&[u8]>;
+    
+    fn get_instruction(&self, addr: u16) -> Option<u16>;
+    fn set_u8(&mut self, addr: u16, val: u8) -> Option<()>;
+    fn get_u8(&mut self, addr: u16) -> Option<u8>;
+    // in bytes
+    fn size(&self) -> usize;
+}
+
+// so that a CPU can borrow its memory and display
+impl<T: CpuMemory + ?Sized> CpuMemory for &mut T {
+    fn get_font_sprite(&self, s_n: u8) -> Option<&[u8]> {
+        (**self).get_font_sprite(s_n)
+    }
+
+    fn get_font_sprite_addr(&self, s_n: u8) -> Option<u16> {
+        (**self).get_font_sprite_addr(s_n)
+    }
+
+    fn get_sprites(&self, addr: u16, n: u8) -> Option<&[u8]> {
+        (**self).get_sprites(addr, n)
+    }
+
+    fn get_instruction(&self, addr: u16) -> Option<u16> {
+        (**self).get_instruction(addr)
+    }
+
+    fn set_u8(&mut self, addr: u16, val: u8) -> Option<()> {
+        (**self).set_u8(addr, val)
+    }
+
+    fn get_u8(&mut self, addr: u16) -> Option<u8> {
+        (**self).get_u8(addr)
+    }
+
+    fn size(&self) -> usize {
+        (**self).size()
+    }
+}
+
+#[derive(Clone)]
+pub struct Memory {
+    memory: Vec<u8>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::with_size(MEM_SIZE)
+    }
+
+    // `size` is MEM_SIZE up to MAX_MEM_SIZE bytes
+    pub fn with_size(size: usize) -> Self {
+        assert!((MEM_SIZE..=MAX_MEM_SIZE).contains(&size), "memory size {}", size);
+        Memory {
+            memory: vec![0; size],
+        }
+    }
+
+    // largest ROM that fits
+    pub fn max_exe_size(&self) -> usize {
+        self.memory.len() - EXE_START_ADDR
+    }
+
+    pub fn load_exe(&mut self, exe: &[u8]) -> &mut Self {
+        self.memory[EXE_START_ADDR..EXE_START_ADDR + exe.len()].clone_from_slice(exe);
+        self
+    }
+
+    pub fn load_sprites(&mut self, sprites: &[u8]) -> &mut Self {
+        self.memory[ROM_START_ADDR..ROM_START_ADDR + sprites.len()].clone_from_slice(sprites);
+        self
+    }
+
+    pub fn build(&mut self) -> Self {
+        self.clone()
+    }
+}
+
+impl CpuMemory for Memory {
+    fn get_instruction(&self, addr: u16) -> Option<u16> {
+        let high_byte: u16 = *self.memory.get(addr as usize)? as u16;
+        let low_byte: u16 = *self.memory.get(addr as usize + 1)? as u16;
+
+        Some(high_byte << 8 | low_byte)
+    }
+
+    fn get_font_sprite_addr(&self, s_num: u8) -> Option<u16> {
+        Some((ROM_START_ADDR + SPRITE_SIZE * (s_num as usize)) as u16)
+    }
+
+    fn get_font_sprite(&self, s_num: u8) -> Option<&[u8]> {
+        let start = ROM_START_ADDR + SPRITE_SIZE * (s_num as usize);
+        let end = start + SPRITE_SIZE;
+        self.memory.get(start..end)
+    }
+
+    fn get_sprites(&self, addr: u16, n: u8) -> Option<&[u8]> {
+        let start = addr as usize;
+        let end = start + n as usize;
+        self.memory.get(start..end)
+    }
+
+    fn set_u8(&mut self, addr: u16, val: u8) -> Option<()> {
+        *self.memory.get_mut(addr as usize)? = val;
+        Some(())
+    }
+
+    fn get_u8(&mut self, addr: u16) -> Option<u8> {
+        self.memory.get(addr as usize).cloned()
+    }
+
+    fn size(&self) -> usize {
+        self.memory.len()
+    }
+}
+
+pub trait VideoMemory {
+    // Draws one sprite row per byte at (x, y), the start wraps around the
+    // visible screen. Pixels past the right or bottom edge wrap around with
+    // `wrap`, else they are clipped. Returns 1 if a lit pixel was erased.
+    fn apply_sprites(&mut self, x: u8, y: u8, sprites: &[u8], wrap: bool) -> Option<u8>;
+    fn get_video_buf(&mut self) -> Option<Screen<'_>>;
+    fn clear(&mut self);
+    // Whether the screen was drawn to or cleared since the last call.
+    fn take_dirty(&mut self) -> bool;
+}
+
+impl<T: VideoMemory + ?Sized> VideoMemory for &mut T {
+    fn apply_sprites(&mut self, x: u8, y: u8, sprites: &[u8], wrap: bool) -> Option<u8> {
+        (**self).apply_sprites(x, y, sprites, wrap)
+    }
+
+    fn get_video_buf(&mut self) -> Option<Screen<'_>> {
+        (**self).get_video_buf()
+    }
+
+    fn clear(&mut self) {
+        (**self).clear()
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        (**self).take_dirty()
+    }
+}
+
+pub const DISPLAY_VISIBLE_WIDTH: usize = 64;
+pub const DISPLAY_VISIBLE_HEIGHT: usize = 32;
+
+// One bit per pixel of a row, the leftmost pixel in the most significant
+// bit. The SUPER-CHIP hires screen would need u128.
+pub type DisplayRow = u64;
+
+// Borrowed view of the screen handed to the media backends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Screen<'a> {
+    rows: &'a [DisplayRow],
+}
+
+impl<'a> Screen<'a> {
+    pub fn new(rows: &'a [DisplayRow]) -> Self {
+        Screen { rows }
+    }
+
+    pub fn rows(&self) -> &'a [DisplayRow] {
+        self.rows
+    }
+
+    pub fn width(&self) -> usize {
+        DISPLAY_VISIBLE_WIDTH
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & (1 << (DISPLAY_VISIBLE_WIDTH - 1 - x)) != 0
+    }
+
+    // Calls `f(x, y, len)` for every run of lit pixels in a row, left to
+    // right and top to bottom.
+    pub fn for_each_run<F: FnMut(usize, usize, usize)>(&self, mut f: F) {
+        for (y, &row) in self.rows.iter().enumerate() {
+            let mut bits = row;
+            let mut x = 0;
+            while bits != 0 {
+                let skip = bits.leading_zeros();
+                bits <<= skip;
+                let len = (!bits).leading_zeros();
+                f((x + skip) as usize, y, len as usize);
+                x += skip + len;
+                bits = bits.checked_shl(len).unwrap_or(0);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Display {
+    rows: [DisplayRow; DISPLAY_VISIBLE_HEIGHT],
+    dirty: bool,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Self {
+        Display {
+            rows: [0; DISPLAY_VISIBLE_HEIGHT],
+            dirty: false,
+        }
+    }
+
+    // Replaces the whole screen, for machines that scan it out of their
+    // own memory.
+    pub fn load_rows(&mut self, rows: &[DisplayRow; DISPLAY_VISIBLE_HEIGHT]) {
+        self.dirty |= self.rows != *rows;
+        self.rows = *rows;
+    }
+}
+
+// A sprite byte as row bits starting at column `x`
+fn sprite_row(sprite: u8, x: usize, wrap: bool) -> DisplayRow {
+    let bits = (sprite as DisplayRow) << (DISPLAY_VISIBLE_WIDTH - 8);
+    if wrap { bits.rotate_right(x as u32) } else { bits >> x }
+}
+
+impl VideoMemory for Display {
+    fn apply_sprites(&mut self, x: u8, y: u8, sprites: &[u8], wrap: bool) -> Option<u8> {
+        let mut collision = 0u8;
+        let x = x as usize % DISPLAY_VISIBLE_WIDTH;
+        let y = y as usize % DISPLAY_VISIBLE_HEIGHT;
+
+        for (s, sprite) in sprites.iter().enumerate() {
+            let mut r = y + s;
+            if r >= DISPLAY_VISIBLE_HEIGHT {
+                if !wrap {
+                    break;
+                }
+                r %= DISPLAY_VISIBLE_HEIGHT;
+            }
+
+            let bits = sprite_row(*sprite, x, wrap);
+            collision |= (self.rows[r] & bits != 0) as u8;
+            self.rows[r] ^= bits;
+            self.dirty |= bits != 0;
+        }
+        Some(collision)
+    }
+
+    fn get_video_buf(&mut self) -> Option<Screen<'_>> {
+        Some(Screen::new(&self.rows))
+    }
+
+    fn clear(&mut self) {
+        self.rows = [0; DISPLAY_VISIBLE_HEIGHT];
+        self.dirty = true;
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        let dirty = self.dirty;
+        self.dirty = false;
+        dirty
+    }
+}
+
+// Hash of the screen, used to compare screens in tests. It is taken over
+// the layout of the earlier byte display, 10 bytes per row and 36 rows with
+// the padding blank, so hashes stored before stay valid.
+pub fn screen_hash(screen: Screen) -> u64 {
+    const ROWS: usize = DISPLAY_VISIBLE_HEIGHT + 4;
+    let mut h = Fnv64::default();
+    for y in 0..ROWS {
+        let row = screen.rows().get(y).cloned().unwrap_or(0);
+        h.write(&row.to_be_bytes());
+        h.write(&[0, 0]);
+    }
+    h.finish()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,190 @@
+use super::*;
+
+// Display::apply_sprites against a plain pixel grid, for every start
+// coordinate a byte can hold.
+
+const W: usize = DISPLAY_VISIBLE_WIDTH;
+const H: usize = DISPLAY_VISIBLE_HEIGHT;
+
+struct Grid {
+    pixels: [[bool; W]; H],
+}
+
+impl Grid {
+    fn new() -> Grid {
+        Grid { pixels: [[false; W]; H] }
+    }
+
+    fn draw(&mut self, x: u8, y: u8, sprites: &[u8], wrap: bool) -> u8 {
+        let mut collision = 0;
+        for (r, sprite) in sprites.iter().enumerate() {
+            for bit in 0..8 {
+                if sprite & (0x80 >> bit) == 0 {
+                    continue;
+                }
+                let px = x as usize % W + bit;
+                let py = y as usize % H + r;
+                if !wrap && (px >= W || py >= H) {
+                    continue;
+                }
+                let p = &mut self.pixels[py % H][px % W];
+                if *p {
+                    collision = 1;
+                }
+                *p = !*p;
+            }
+        }
+        collision
+    }
+}
+
+fn pixel(display: &mut Display, x: usize, y: usize) -> bool {
+    display.get_video_buf().unwrap().pixel(x, y)
+}
+
+// The rows as they should be
+fn expected(grid: &Grid) -> [DisplayRow; H] {
+    let mut rows = [0; H];
+    for (y, row) in grid.pixels.iter().enumerate() {
+        for (x, &on) in row.iter().enumerate() {
+            if on {
+                rows[y] |= 1 << (W - 1 - x);
+            }
+        }
+    }
+    rows
+}
+
+fn same(display: &mut Display, grid: &Grid) -> bool {
+    display.get_video_buf().unwrap().rows() == &expected(grid)[..]
+}
+
+// A 15 row sprite with a different pattern in every row
+const TALL: [u8; 15] = [0xFF, 0x81, 0xC3, 0xA5, 0x99, 0x18, 0x3C, 0x7E,
+                        0x01, 0x80, 0x55, 0xAA, 0x0F, 0xF0, 0x66];
+
+fn check_everywhere(sprites: &[u8], wrap: bool) {
+    for x in 0..=255u8 {
+        for y in 0..=255u8 {
+            let mut display = Display::new();
+            let mut grid = Grid::new();
+
+            // drawn twice, the second time erases the sprite and collides
+            for _ in 0..2 {
+                let c = display.apply_sprites(x, y, sprites, wrap).unwrap();
+                assert_eq!(c, grid.draw(x, y, sprites, wrap), "collision at ({}, {})", x, y);
+                assert!(same(&mut display, &grid), "video memory after drawing at ({}, {})", x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn every_position_clipped() {
+    check_everywhere(&TALL, false);
+}
+
+#[test]
+fn every_position_wrapped() {
+    check_everywhere(&TALL, true);
+}
+
+#[test]
+fn x_64_wraps_to_left_edge() {
+    let mut display = Display::new();
+    display.apply_sprites(64, 0, &[0x80], false);
+    assert!(pixel(&mut display, 0, 0));
+}
+
+#[test]
+fn y_32_wraps_to_top_edge() {
+    let mut display = Display::new();
+    display.apply_sprites(0, 32, &[0x80], false);
+    assert!(pixel(&mut display, 0, 0));
+}
+
+#[test]
+fn clipped_at_right_edge() {
+    let mut display = Display::new();
+    display.apply_sprites(60, 0, &[0xFF], false);
+    for x in 60..64 {
+        assert!(pixel(&mut display, x, 0));
+    }
+    for x in 0..4 {
+        assert!(!pixel(&mut display, x, 0));
+    }
+}
+
+#[test]
+fn wrapped_at_right_edge() {
+    let mut display = Display::new();
+    display.apply_sprites(60, 0, &[0xFF], true);
+    for x in (60..64).chain(0..4) {
+        assert!(pixel(&mut display, x, 0));
+    }
+}
+
+#[test]
+fn clipped_at_bottom_edge() {
+    let mut display = Display::new();
+    display.apply_sprites(0, 30, &[0x80; 4], false);
+    assert!(pixel(&mut display, 0, 30));
+    assert!(pixel(&mut display, 0, 31));
+    assert!(!pixel(&mut display, 0, 0));
+    assert!(!pixel(&mut display, 0, 1));
+}
+
+#[test]
+fn wrapped_at_bottom_right_corner() {
+    let mut display = Display::new();
+    display.apply_sprites(63, 31, &[0xC0, 0xC0], true);
+    for &(x, y) in &[(63, 31), (0, 31), (63, 0), (0, 0)] {
+        assert!(pixel(&mut display, x, y), "pixel ({}, {})", x, y);
+    }
+}
+
+#[test]
+fn collision_across_byte_boundary() {
+    let mut display = Display::new();
+    assert_eq!(display.apply_sprites(9, 0, &[0x80], false), Some(0));
+    // covers x 4 to 11, pixel 9 is in the second byte
+    assert_eq!(display.apply_sprites(4, 0, &[0x04], false), Some(1));
+    assert!(!pixel(&mut display, 9, 0));
+}
+
+#[test]
+fn no_collision_next_to_lit_pixel() {
+    let mut display = Display::new();
+    display.apply_sprites(8, 0, &[0x80], false);
+    assert_eq!(display.apply_sprites(0, 0, &[0xFF], false), Some(0));
+}
+
+#[test]
+fn clipped_pixels_do_not_collide() {
+    let mut display = Display::new();
+    display.apply_sprites(0, 0, &[0xFF], false);
+    assert_eq!(display.apply_sprites(60, 0, &[0xFF], false), Some(0));
+}
+
+#[test]
+fn wrapped_pixels_collide() {
+    let mut display = Display::new();
+    display.apply_sprites(0, 0, &[0xFF], false);
+    assert_eq!(display.apply_sprites(60, 0, &[0xFF], true), Some(1));
+}
+
+#[test]
+fn clear_blanks_the_screen() {
+    let mut display = Display::new();
+    display.apply_sprites(10, 10, &TALL, false);
+    display.clear();
+    assert!(same(&mut display, &Grid::new()));
+}
+
+#[test]
+fn runs_of_lit_pixels() {
+    let rows = [0xF000_0000_0000_0001, 0, !0, 0x0F0F_0000_0000_0000];
+    let mut runs = Vec::new();
+    Screen::new(&rows).for_each_run(|x, y, len| runs.push((x, y, len)));
+    assert_eq!(runs, vec![(0, 0, 4), (63, 0, 1), (0, 2, 64), (4, 3, 4), (12, 3, 4)]);
+}
@@ -0,0 +1,255 @@
+extern crate serde_json;
+
+use disasm::*;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+// Counts of executed instructions by handler name and by address, the
+// time every frame took and the instructions run under every call stack.
+
+// entries of the report tables
+const TOP: usize = 20;
+
+#[derive(Clone)]
+struct PcCount {
+    count: u64,
+    instruction: u16,
+    name: &'static str,
+}
+
+#[derive(Clone)]
+pub struct Profile {
+    instructions: u64,
+    by_name: HashMap<&'static str, u64>,
+    by_pc: HashMap<u16, PcCount>,
+    // backward jumps by (jump address, target)
+    back_jumps: HashMap<(u16, u16), u64>,
+    // called addresses, outermost first, as CALL and RET have left them
+    calls: Vec<u16>,
+    folded: HashMap<Vec<u16>, u64>,
+    frame_start: Option<Instant>,
+    frame_times: Vec<Duration>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize)]
+struct OpcodeEntry {
+    name: &'static str,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct AddressEntry {
+    pc: String,
+    instruction: String,
+    name: &'static str,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct LoopEntry {
+    start: String,
+    end: String,
+    iterations: u64,
+    instructions: u64,
+}
+
+#[derive(Serialize)]
+struct FrameTimes {
+    frames: usize,
+    mean_us: u64,
+    median_us: u64,
+    p99_us: u64,
+    max_us: u64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    instructions: u64,
+    frame_times: FrameTimes,
+    opcodes: Vec<OpcodeEntry>,
+    addresses: Vec<AddressEntry>,
+    loops: Vec<LoopEntry>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile {
+            instructions: 0,
+            by_name: HashMap::new(),
+            by_pc: HashMap::new(),
+            back_jumps: HashMap::new(),
+            calls: Vec::new(),
+            folded: HashMap::new(),
+            frame_start: None,
+            frame_times: Vec::new(),
+        }
+    }
+
+    // Called before the instruction at `pc` executes, `name` being its
+    // handler's.
+    pub fn count(&mut self, pc: u16, instruction: u16, name: &'static str) {
+        if self.frame_start.is_none() {
+            self.frame_start = Some(Instant::now());
+        }
+
+        self.instructions += 1;
+        *self.by_name.entry(name).or_insert(0) += 1;
+        self.by_pc.entry(pc)
+            .or_insert(PcCount { count: 0, instruction, name })
+            .count += 1;
+
+        match self.folded.get_mut(&self.calls[..]) {
+            Some(n) => *n += 1,
+            None => {
+                self.folded.insert(self.calls.clone(), 1);
+            },
+        }
+
+        let nnn = instruction & 0x0FFF;
+        match name {
+            "JP" if nnn <= pc => *self.back_jumps.entry((pc, nnn)).or_insert(0) += 1,
+            "CALL" => self.calls.push(nnn),
+            "RET" => {
+                self.calls.pop();
+            },
+            _ => {},
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        if let Some(start) = self.frame_start.take() {
+            self.frame_times.push(start.elapsed());
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frame_times.len()
+    }
+
+    fn report(&self) -> Report {
+        let mut opcodes: Vec<_> = self.by_name.iter()
+            .map(|(&name, &count)| OpcodeEntry { name, count })
+            .collect();
+        opcodes.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(b.name)));
+
+        let mut pcs: Vec<_> = self.by_pc.iter().collect();
+        pcs.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        let addresses = pcs.iter().take(TOP)
+            .map(|&(pc, c)| AddressEntry {
+                pc: format!("{:04X}", pc),
+                instruction: disassemble(c.instruction),
+                name: c.name,
+                count: c.count,
+            })
+            .collect();
+
+        // a loop is the code from the target of a backward jump to the
+        // jump, hot by the instructions executed in it
+        let mut loops: Vec<_> = self.back_jumps.iter()
+            .map(|(&(end, start), &iterations)| {
+                let instructions: u64 = self.by_pc.iter()
+                    .filter(|&(&pc, _)| pc >= start && pc <= end)
+                    .map(|(_, c)| c.count)
+                    .sum();
+                (start, end, iterations, instructions)
+            })
+            .collect();
+        loops.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+        let loops = loops.into_iter().take(TOP)
+            .map(|(start, end, iterations, instructions)| LoopEntry {
+                start: format!("{:04X}", start),
+                end: format!("{:04X}", end),
+                iterations,
+                instructions,
+            })
+            .collect();
+
+        Report {
+            instructions: self.instructions,
+            frame_times: self.frame_stats(),
+            opcodes,
+            addresses,
+            loops,
+        }
+    }
+
+    fn frame_stats(&self) -> FrameTimes {
+        let mut us: Vec<u64> = self.frame_times.iter().map(|d| d.as_micros() as u64).collect();
+        us.sort_unstable();
+        let at = |q: f64| us.get(((us.len() as f64 - 1.0) * q).round() as usize).cloned().unwrap_or(0);
+        FrameTimes {
+            frames: us.len(),
+            mean_us: if us.is_empty() { 0 } else { us.iter().sum::<u64>() / us.len() as u64 },
+            median_us: at(0.5),
+            p99_us: at(0.99),
+            max_us: us.last().cloned().unwrap_or(0),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let r = self.report();
+        let total = r.instructions.max(1) as f64;
+        let mut s = String::new();
+
+        let f = &r.frame_times;
+        writeln!(s, "{} instructions in {} frames", r.instructions, f.frames).unwrap();
+        writeln!(s, "frame time: mean {} us, median {} us, 99% {} us, max {} us",
+                 f.mean_us, f.median_us, f.p99_us, f.max_us).unwrap();
+
+        writeln!(s, "\nmost used instructions:").unwrap();
+        for o in &r.opcodes {
+            writeln!(s, "  {:<10} {:>12} {:>6.2}%", o.name, o.count, o.count as f64 * 100.0 / total).unwrap();
+        }
+
+        writeln!(s, "\nhot loops:").unwrap();
+        if r.loops.is_empty() {
+            writeln!(s, "  none").unwrap();
+        }
+        for l in &r.loops {
+            writeln!(s, "  {}-{} {:>12} instructions {:>10} iterations {:>6.2}%",
+                     l.start, l.end, l.instructions, l.iterations, l.instructions as f64 * 100.0 / total).unwrap();
+        }
+
+        writeln!(s, "\nhot addresses:").unwrap();
+        for a in &r.addresses {
+            writeln!(s, "  {}  {:<16} {:<10} {:>12} {:>6.2}%",
+                     a.pc, a.instruction, a.name, a.count, a.count as f64 * 100.0 / total).unwrap();
+        }
+        s
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.report()).unwrap()
+    }
+
+    // One line per call stack with the instructions executed in it, for
+    // flame graph tools: "main;0234;0250 17"
+    pub fn to_folded(&self) -> String {
+        let mut lines: Vec<String> = self.folded.iter()
+            .map(|(calls, count)| {
+                let mut line = String::from("main");
+                for addr in calls {
+                    write!(line, ";{:04X}", addr).unwrap();
+                }
+                format!("{} {}", line, count)
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|l| format!("{}\n", l)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,97 @@
+use super::*;
+
+// A loop at 0200-0206 calling 0300 every round, which returns at once:
+//
+//   0200 6000  LD V0, 0      0300 00EE  RET
+//   0202 2300  CALL 0x300
+//   0204 7001  ADD V0, 1
+//   0206 1202  JP 0x202
+fn looping(rounds: usize) -> Profile {
+    let mut p = Profile::new();
+    p.count(0x200, 0x6000, "LD_BYTE");
+    for _ in 0..rounds {
+        p.count(0x202, 0x2300, "CALL");
+        p.count(0x300, 0x00EE, "RET");
+        p.count(0x204, 0x7001, "ADD_BYTE");
+        p.count(0x206, 0x1202, "JP");
+    }
+    p
+}
+
+#[test]
+fn counts_by_name_and_address() {
+    let p = looping(10);
+    assert_eq!(p.instructions(), 41);
+    assert_eq!(p.by_name["CALL"], 10);
+    assert_eq!(p.by_name["LD_BYTE"], 1);
+    assert_eq!(p.by_pc[&0x300].count, 10);
+    assert_eq!(p.by_pc[&0x300].name, "RET");
+}
+
+#[test]
+fn backward_jump_is_a_loop() {
+    let r = looping(10).report();
+    assert_eq!(r.loops.len(), 1);
+    let l = &r.loops[0];
+    assert_eq!((l.start.as_str(), l.end.as_str()), ("0202", "0206"));
+    assert_eq!(l.iterations, 10);
+    // the called code at 0300 lies outside of the range
+    assert_eq!(l.instructions, 30);
+}
+
+#[test]
+fn forward_jump_is_no_loop() {
+    let mut p = Profile::new();
+    p.count(0x200, 0x1300, "JP");
+    assert!(p.report().loops.is_empty());
+}
+
+#[test]
+fn opcodes_are_ranked() {
+    let mut p = looping(3);
+    p.count(0x208, 0x6000, "LD_BYTE");
+    let names: Vec<_> = p.report().opcodes.iter().map(|o| o.name).collect();
+    assert_eq!(names, vec!["ADD_BYTE", "CALL", "JP", "RET", "LD_BYTE"]);
+}
+
+#[test]
+fn folded_stacks_follow_call_and_ret() {
+    assert_eq!(looping(10).to_folded(), "main 31\nmain;0300 10\n");
+}
+
+#[test]
+fn ret_without_call_keeps_the_root() {
+    let mut p = Profile::new();
+    p.count(0x200, 0x00EE, "RET");
+    p.count(0x202, 0x6000, "LD_BYTE");
+    assert_eq!(p.to_folded(), "main 2\n");
+}
+
+#[test]
+fn frames_are_timed() {
+    let mut p = looping(1);
+    p.end_frame();
+    // no instructions, no frame
+    p.end_frame();
+    p.count(0x200, 0x6000, "LD_BYTE");
+    p.end_frame();
+    assert_eq!(p.frames(), 2);
+}
+
+#[test]
+fn json_report() {
+    let json: serde_json::Value = serde_json::from_str(&looping(2).to_json()).unwrap();
+    assert_eq!(json["instructions"], 9);
+    assert_eq!(json["opcodes"][0]["count"], 2);
+    assert_eq!(json["addresses"][0]["pc"], "0202");
+    assert_eq!(json["addresses"][0]["instruction"], "CALL 0x300");
+    assert_eq!(json["loops"][0]["start"], "0202");
+}
+
+#[test]
+fn text_report() {
+    let text = looping(2).to_text();
+    assert!(text.starts_with("9 instructions in 0 frames\n"));
+    assert!(text.contains("  0202-0206"));
+    assert!(text.contains("  CALL "));
+}
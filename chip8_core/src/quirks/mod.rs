@@ -0,0 +1,177 @@
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+
+// Behaviour differences between CHIP-8 interpreters. Names follow the
+// quirk ids of the community chip-8-database. The default is the behaviour
+// this emulator always had, except for FX0A, see key_held.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VX in place instead of shifting VY into VX
+    pub shift: bool,
+    // FX55/FX65 advance I by X instead of X + 1
+    pub memory_increment_by_x: bool,
+    // FX55/FX65 leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    // BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump: bool,
+    // 8XY1/8XY2/8XY3 reset VF to zero
+    pub logic: bool,
+    // addresses past the end of memory wrap around to 0 as on the VIP
+    // instead of stopping with an error
+    pub memory_wrap: bool,
+    // DXYN wraps sprites around the edges of the screen instead of
+    // clipping them
+    pub wrap: bool,
+    // DXYN waits for the next 60 Hz vertical blank, at most one sprite is
+    // drawn per frame as on the VIP
+    pub vblank: bool,
+    // FX0A takes the highest key held down at once instead of waiting for
+    // a key to be pressed and released, as this emulator used to. Games
+    // then see one key press twice. Not in the database.
+    pub key_held: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            jump: false,
+            logic: false,
+            memory_wrap: false,
+            wrap: false,
+            vblank: false,
+            key_held: false,
+        }
+    }
+}
+
+impl Quirks {
+    fn fields(&self) -> [(&'static str, bool); 9] {
+        [("shift", self.shift),
+         ("memory_increment_by_x", self.memory_increment_by_x),
+         ("memory_leave_i_unchanged", self.memory_leave_i_unchanged),
+         ("jump", self.jump),
+         ("logic", self.logic),
+         ("memory_wrap", self.memory_wrap),
+         ("wrap", self.wrap),
+         ("vblank", self.vblank),
+         ("key_held", self.key_held)]
+    }
+
+    fn set(&mut self, name: &str, val: bool) -> Result<(), String> {
+        match name {
+            "shift" => self.shift = val,
+            "memory_increment_by_x" => self.memory_increment_by_x = val,
+            "memory_leave_i_unchanged" => self.memory_leave_i_unchanged = val,
+            "jump" => self.jump = val,
+            "logic" => self.logic = val,
+            "memory_wrap" => self.memory_wrap = val,
+            "wrap" => self.wrap = val,
+            "vblank" => self.vblank = val,
+            "key_held" => self.key_held = val,
+            _ => return Err(format!("unknown quirk '{}'", name)),
+        }
+        Ok(())
+    }
+}
+
+// "shift=1,memory_increment_by_x=0,..."
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fields = self.fields();
+        for (i, &(name, val)) in fields.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}={}", name, val as u8)?;
+        }
+        Ok(())
+    }
+}
+
+pub const PRESETS: [&str; 4] = ["legacy", "vip", "schip", "xochip"];
+
+impl Quirks {
+    // "legacy" is the default, the others follow the original COSMAC VIP
+    // interpreter, SUPER-CHIP 1.1 and XO-CHIP as described by the
+    // chip-8-database.
+    pub fn preset(name: &str) -> Option<Quirks> {
+        let q = match name {
+            "legacy" => Quirks::default(),
+            "vip" | "chip8" => Quirks {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                jump: false,
+                logic: true,
+                memory_wrap: true,
+                wrap: false,
+                vblank: true,
+                key_held: false,
+            },
+            "schip" => Quirks {
+                shift: true,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: true,
+                jump: true,
+                logic: false,
+                memory_wrap: false,
+                wrap: false,
+                vblank: false,
+                key_held: false,
+            },
+            "xochip" => Quirks {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                jump: false,
+                logic: false,
+                memory_wrap: true,
+                wrap: true,
+                vblank: false,
+                key_held: false,
+            },
+            _ => return None,
+        };
+        Some(q)
+    }
+
+    // Applies a list in the Display format on top of the current settings.
+    // An item without '=' names a preset that replaces all settings, e.g.
+    // "vip,jump=1".
+    pub fn apply(&mut self, s: &str) -> Result<(), String> {
+        for item in s.split(',').filter(|i| !i.is_empty()) {
+            if !item.contains('=') {
+                *self = Quirks::preset(item)
+                    .ok_or_else(|| format!("unknown quirks preset '{}', expected one of {}",
+                                           item, PRESETS.join(", ")))?;
+                continue;
+            }
+
+            let mut it = item.splitn(2, '=');
+            let name = it.next().unwrap_or("");
+            let val = match it.next() {
+                Some("1") | Some("true") => true,
+                Some("0") | Some("false") => false,
+                _ => return Err(format!("invalid quirk setting '{}'", item)),
+            };
+            self.set(name, val)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Parses the Display format. Quirks that are not listed keep their default.
+impl FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut quirks = Quirks::default();
+        quirks.apply(s)?;
+        Ok(quirks)
+    }
+}
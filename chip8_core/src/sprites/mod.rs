@@ -1,4 +1,4 @@
-pub const SPRITES: &'static [u8; 80] = &[ 0xF0, 0x90, 0x90, 0x90, 0xF0, // "0"
+pub const SPRITES: &[u8; 80] = &[ 0xF0, 0x90, 0x90, 0x90, 0xF0, // "0"
                                          0x20, 0x60, 0x20, 0x20, 0x70, // "1"
                                          0xF0, 0x10, 0xF0, 0x80, 0xF0,
                                          0xF0, 0x10, 0xF0, 0x10, 0xF0,
@@ -0,0 +1,72 @@
+use alloc::vec::Vec;
+
+// Return addresses of CALL. The stack is kept apart from memory, so a
+// deep call chain cannot overwrite the program.
+
+// the VIP interpreter has room for 12 return addresses
+pub const VIP_STACK_DEPTH: usize = 12;
+// SUPER-CHIP, also used by XO-CHIP
+pub const SCHIP_STACK_DEPTH: usize = 16;
+pub const DEFAULT_STACK_DEPTH: usize = SCHIP_STACK_DEPTH;
+// "Unlimited" still ends here, so that runaway recursion faults instead of
+// eating the host's memory. No interpreter ever had room for this many.
+pub const MAX_STACK_DEPTH: usize = 0x1000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stack {
+    entries: Vec<u16>,
+    // None for unlimited, that is MAX_STACK_DEPTH
+    depth: Option<usize>,
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new(Some(DEFAULT_STACK_DEPTH))
+    }
+}
+
+impl Stack {
+    pub fn new(depth: Option<usize>) -> Self {
+        Stack {
+            entries: Vec::new(),
+            depth,
+        }
+    }
+
+    pub fn depth(&self) -> Option<usize> {
+        self.depth
+    }
+
+    // None when the stack is full
+    pub fn push(&mut self, addr: u16) -> Option<()> {
+        if self.entries.len() >= self.depth.unwrap_or(MAX_STACK_DEPTH) {
+            return None;
+        }
+        self.entries.push(addr);
+        Some(())
+    }
+
+    // None when the stack is empty
+    pub fn pop(&mut self) -> Option<u16> {
+        self.entries.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Outermost call first, the address the next RET returns to last.
+    pub fn return_addresses(&self) -> &[u16] {
+        &self.entries
+    }
+}
+
+// Depth given as number, 0 for unlimited as in the configuration and on
+// the command line.
+pub fn depth_from(n: usize) -> Option<usize> {
+    if n == 0 { None } else { Some(n) }
+}
@@ -0,0 +1,313 @@
+use media_if::*;
+use memory::*;
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+// Mock implementations of the CPU's traits for tests. Every call is
+// recorded in order so tests can check what an instruction touched.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MemoryCall {
+    GetFontSprite(u8),
+    GetFontSpriteAddr(u8),
+    GetSprites(u16, u8),
+    GetInstruction(u16),
+    SetU8(u16, u8),
+    GetU8(u16),
+}
+
+// Flat memory, fonts are assumed at 0 with 5 bytes per digit like in
+// Memory.
+pub struct MockMemory {
+    pub bytes: Vec<u8>,
+    calls: RefCell<Vec<MemoryCall>>,
+}
+
+impl Default for MockMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockMemory {
+    pub fn new() -> Self {
+        Self::with_size(MEM_SIZE)
+    }
+
+    pub fn with_size(size: usize) -> Self {
+        MockMemory {
+            bytes: vec![0; size],
+            calls: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn load(&mut self, addr: u16, data: &[u8]) -> &mut Self {
+        let start = addr as usize;
+        self.bytes[start..start + data.len()].copy_from_slice(data);
+        self
+    }
+
+    // Instructions stored big endian from `addr` on
+    pub fn load_program(&mut self, addr: u16, program: &[u16]) -> &mut Self {
+        for (i, inst) in program.iter().enumerate() {
+            let a = addr as usize + 2 * i;
+            self.bytes[a] = (inst >> 8) as u8;
+            self.bytes[a + 1] = *inst as u8;
+        }
+        self
+    }
+
+    pub fn calls(&self) -> Vec<MemoryCall> {
+        self.calls.borrow().clone()
+    }
+
+    pub fn clear_calls(&mut self) {
+        self.calls.borrow_mut().clear();
+    }
+
+    fn record(&self, call: MemoryCall) {
+        self.calls.borrow_mut().push(call);
+    }
+}
+
+impl CpuMemory for MockMemory {
+    fn get_font_sprite(&self, s_n: u8) -> Option<&[u8]> {
+        self.record(MemoryCall::GetFontSprite(s_n));
+        let start = 5 * s_n as usize;
+        self.bytes.get(start..start + 5)
+    }
+
+    fn get_font_sprite_addr(&self, s_n: u8) -> Option<u16> {
+        self.record(MemoryCall::GetFontSpriteAddr(s_n));
+        Some(5 * s_n as u16)
+    }
+
+    fn get_sprites(&self, addr: u16, n: u8) -> Option<&[u8]> {
+        self.record(MemoryCall::GetSprites(addr, n));
+        self.bytes.get(addr as usize..addr as usize + n as usize)
+    }
+
+    fn get_instruction(&self, addr: u16) -> Option<u16> {
+        self.record(MemoryCall::GetInstruction(addr));
+        let hb = *self.bytes.get(addr as usize)? as u16;
+        let lb = *self.bytes.get(addr as usize + 1)? as u16;
+        Some(hb << 8 | lb)
+    }
+
+    fn set_u8(&mut self, addr: u16, val: u8) -> Option<()> {
+        self.record(MemoryCall::SetU8(addr, val));
+        *self.bytes.get_mut(addr as usize)? = val;
+        Some(())
+    }
+
+    fn get_u8(&mut self, addr: u16) -> Option<u8> {
+        self.record(MemoryCall::GetU8(addr));
+        self.bytes.get(addr as usize).cloned()
+    }
+
+    fn size(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum VideoCall {
+    // x, y, sprite rows, wrap
+    ApplySprites(u8, u8, Vec<u8>, bool),
+    GetVideoBuf,
+    Clear,
+    TakeDirty,
+}
+
+// Does not draw, apply_sprites returns `collision`. Drawing and clearing
+// set `dirty`.
+pub struct MockVideo {
+    pub collision: u8,
+    pub dirty: bool,
+    pub rows: [DisplayRow; DISPLAY_VISIBLE_HEIGHT],
+    calls: Vec<VideoCall>,
+}
+
+impl Default for MockVideo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockVideo {
+    pub fn new() -> Self {
+        MockVideo {
+            collision: 0,
+            dirty: false,
+            rows: [0; DISPLAY_VISIBLE_HEIGHT],
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn calls(&self) -> &[VideoCall] {
+        &self.calls
+    }
+
+    pub fn clear_calls(&mut self) {
+        self.calls.clear();
+    }
+}
+
+impl VideoMemory for MockVideo {
+    fn apply_sprites(&mut self, x: u8, y: u8, sprites: &[u8], wrap: bool) -> Option<u8> {
+        self.calls.push(VideoCall::ApplySprites(x, y, sprites.to_vec(), wrap));
+        self.dirty = true;
+        Some(self.collision)
+    }
+
+    fn get_video_buf(&mut self) -> Option<Screen<'_>> {
+        self.calls.push(VideoCall::GetVideoBuf);
+        Some(Screen::new(&self.rows))
+    }
+
+    fn clear(&mut self) {
+        self.calls.push(VideoCall::Clear);
+        self.dirty = true;
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.calls.push(VideoCall::TakeDirty);
+        let dirty = self.dirty;
+        self.dirty = false;
+        dirty
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MediaCall {
+    DrawDisplay,
+    ClearDisplay,
+    PresentDisplay,
+    EndFrame,
+    SetSound(bool),
+    PollInput,
+    IsKeyPressed(u8),
+    PollControl,
+}
+
+// Keypad scripted per frame: bit k of script[n] is key k during frame n,
+// frames past the end of the script have no key down. Runs as long as
+// `running` is set, requests sent to `controls` are handed out in order.
+#[derive(Clone)]
+pub struct MockMedia {
+    pub script: Vec<u16>,
+    pub running: bool,
+    pub controls: ControlQueue,
+    frame: usize,
+    edges: KeyEdges,
+    calls: Vec<MediaCall>,
+}
+
+impl Default for MockMedia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockMedia {
+    pub fn new() -> Self {
+        MockMedia {
+            script: Vec::new(),
+            running: true,
+            controls: ControlQueue::new(),
+            frame: 0,
+            edges: KeyEdges::default(),
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn with_keys(script: Vec<u16>) -> Self {
+        MockMedia { script, ..Self::new() }
+    }
+
+    // Sets the keys down in the current frame.
+    pub fn set_keys(&mut self, keys: u16) {
+        if self.script.len() <= self.frame {
+            self.script.resize(self.frame + 1, 0);
+        }
+        self.script[self.frame] = keys;
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn keys(&self) -> u16 {
+        self.script.get(self.frame).cloned().unwrap_or(0)
+    }
+
+    pub fn calls(&self) -> &[MediaCall] {
+        &self.calls
+    }
+
+    pub fn clear_calls(&mut self) {
+        self.calls.clear();
+    }
+}
+
+impl VideoSink for MockMedia {
+    fn draw_display(&mut self, _buf: Screen) -> Option<u8> {
+        self.calls.push(MediaCall::DrawDisplay);
+        Some(0)
+    }
+
+    fn clear_display(&mut self) -> Option<u8> {
+        self.calls.push(MediaCall::ClearDisplay);
+        Some(0)
+    }
+
+    fn present_display(&mut self) -> Option<u8> {
+        self.calls.push(MediaCall::PresentDisplay);
+        Some(0)
+    }
+
+    fn end_frame(&mut self, _buf: Screen) -> Option<u8> {
+        self.calls.push(MediaCall::EndFrame);
+        self.frame += 1;
+        Some(0)
+    }
+}
+
+impl AudioSink for MockMedia {
+    fn set_sound(&mut self, on: bool) {
+        self.calls.push(MediaCall::SetSound(on));
+    }
+}
+
+impl InputSource for MockMedia {
+    fn poll_input(&mut self) {
+        self.calls.push(MediaCall::PollInput);
+        self.edges.update(self.keys());
+    }
+
+    fn is_key_pressed(&mut self, key: u8) -> bool {
+        self.calls.push(MediaCall::IsKeyPressed(key));
+        key < 16 && self.keys() & (1 << key) != 0
+    }
+
+    fn get_pressed_key(&self) -> Option<&u8> {
+        let keys = self.keys();
+        KEY_IDS.iter().find(|&&k| keys & (1 << k) != 0)
+    }
+
+
+    fn poll_key_event(&mut self) -> Option<KeyEvent> {
+        self.edges.next_event()
+    }
+}
+
+impl ControlSource for MockMedia {
+    fn poll_control(&mut self) -> Option<Control> {
+        self.calls.push(MediaCall::PollControl);
+        if !self.running {
+            return Some(Control::Quit);
+        }
+        self.controls.poll_control()
+    }
+}
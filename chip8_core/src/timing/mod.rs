@@ -0,0 +1,55 @@
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+
+// How many instructions run in a 60 Hz frame: a fixed count, or as many
+// as the COSMAC VIP's 1.76 MHz clock allows with every instruction
+// charged its VIP machine cycles.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Timing {
+    #[default]
+    Ipf,
+    Vip,
+}
+
+// instructions per frame when neither ROM nor platform has a tickrate
+pub const DEFAULT_TICKRATE: u32 = 10;
+// instructions per frame at most, however often the speed is raised
+pub const MAX_SPEED: u32 = 1000;
+
+pub const VIP_CLOCK_HZ: u64 = 1_760_000;
+// an 1802 machine cycle takes 8 clock cycles
+pub const CLOCKS_PER_MACHINE_CYCLE: u64 = 8;
+pub const FRAME_HZ: u64 = 60;
+
+// Cycles of every frame the interpreter does not get: the 1861 reads 8
+// bytes by DMA for each of its 128 scanlines, and the interrupt routine
+// counts down the timers.
+pub const VIP_DMA_CYCLES: u64 = 128 * 8;
+pub const VIP_INTERRUPT_CYCLES: u64 = 29;
+
+// Machine cycles left to the interpreter in one frame
+pub fn vip_frame_cycles() -> u64 {
+    VIP_CLOCK_HZ / CLOCKS_PER_MACHINE_CYCLE / FRAME_HZ - VIP_DMA_CYCLES - VIP_INTERRUPT_CYCLES
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Timing::Ipf => write!(f, "ipf"),
+            Timing::Vip => write!(f, "vip"),
+        }
+    }
+}
+
+impl FromStr for Timing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ipf" => Ok(Timing::Ipf),
+            "vip" => Ok(Timing::Vip),
+            _ => Err(format!("unknown timing '{}', expected ipf or vip", s)),
+        }
+    }
+}
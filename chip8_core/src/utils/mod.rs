@@ -0,0 +1,163 @@
+use alloc::string::String;
+
+pub fn get_octet(x: u16, num: u16) -> u8 {
+    ((x & (0xF << num)) >> num) as u8
+}
+
+pub fn to_octets(x: u16) -> (u8, u8, u8, u8) {
+    let oct0 = get_octet(x, 0);
+    let oct1 = get_octet(x, 4);
+    let oct2 = get_octet(x, 8);
+    let oct3 = get_octet(x, 12);
+
+    (oct3, oct2, oct1, oct0)
+}
+
+pub fn to_addr((oct0, oct1, oct2) : (u8, u8, u8)) -> u16 {
+    (oct0 as u16) << 8 | (oct1 as u16) << 4 | (oct2 as u16)
+}
+
+pub fn to_id((oct0, oct1, oct2, oct3) : (u8, u8, u8, u8)) -> u16 {
+    (oct0 as u16) << 12 | (oct1 as u16) << 8 | (oct2 as u16) << 4 | (oct3 as u16)
+}
+
+pub fn to_u8((oct0, oct1): (u8, u8)) -> u8 {
+    (oct0 << 4) | oct1
+}
+
+
+// 64-bit FNV-1a, stable across platforms and releases
+pub struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Fnv64(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv64 {
+    pub fn write(&mut self, data: &[u8]) {
+        for b in data {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+pub fn fnv1a64(data: &[u8]) -> u64 {
+    let mut h = Fnv64::default();
+    h.write(data);
+    h.finish()
+}
+
+// SHA-1 as used by the chip-8-database to identify ROM images
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let t = a.rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut out = [0u8; 20];
+    for (i, v) in h.iter().enumerate() {
+        out[i * 4..i * 4 + 4].copy_from_slice(&v.to_be_bytes());
+    }
+    out
+}
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    sha1(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// CRC-32 (IEEE) as used by BPS patches and zip
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// Marsaglia's xorshift128, the generator RND has always used: the same
+// sequence as rand 0.5's XorShiftRng for the same seed, so recorded
+// movies and seeds keep replaying.
+#[derive(Clone)]
+pub struct XorShift128 {
+    x: u32,
+    y: u32,
+    z: u32,
+    w: u32,
+}
+
+impl XorShift128 {
+    // the state as four little-endian words, all zeros is replaced
+    pub fn from_seed(seed: [u8; 16]) -> XorShift128 {
+        let word = |i: usize| u32::from_le_bytes([seed[i], seed[i + 1], seed[i + 2], seed[i + 3]]);
+        if seed.iter().all(|&b| b == 0) {
+            return XorShift128 { x: 0x0BAD_5EED, y: 0x0BAD_5EED, z: 0x0BAD_5EED, w: 0x0BAD_5EED };
+        }
+        XorShift128 { x: word(0), y: word(4), z: word(8), w: word(12) }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let t = self.x ^ (self.x << 11);
+        self.x = self.y;
+        self.y = self.z;
+        self.z = self.w;
+        self.w = self.w ^ (self.w >> 19) ^ (t ^ (t >> 8));
+        self.w
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.next_u32() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests;
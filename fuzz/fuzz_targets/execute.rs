@@ -0,0 +1,21 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate chip8_opcode;
+
+use chip8_opcode::quirks::*;
+use chip8_opcode::testing::*;
+
+const FRAMES: usize = 120;
+const SPEED: u32 = 20;
+
+// The input is the ROM. Key (frame / 4) % 16 is held in each frame so
+// that every key gets pressed and released; the ROM runs with the legacy
+// and the VIP quirks.
+fuzz_target!(|rom: &[u8]| {
+    let keys: Vec<u16> = (0..FRAMES).map(|f| 1 << ((f / 4) % 16)).collect();
+
+    for preset in &["legacy", "vip"] {
+        fuzz_case(rom, keys.clone(), Quirks::preset(preset).unwrap(), SPEED);
+    }
+});
@@ -0,0 +1,11 @@
+#!/bin/sh
+# Seeds the corpus of the execute target with the ROMs in res/.
+set -e
+cd "$(dirname "$0")"
+mkdir -p corpus/execute
+for rom in ../res/* ../res/tests/*.ch8; do
+    case "$rom" in
+        *.zip|*.md) ;;
+        *) if [ -f "$rom" ]; then cp "$rom" corpus/execute/; fi ;;
+    esac
+done
@@ -0,0 +1,249 @@
+extern crate gif;
+extern crate png;
+
+use memory::*;
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub type Rgb = (u8, u8, u8);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub fg: Rgb,
+    pub bg: Rgb,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            fg: (255, 255, 255),
+            bg: (0, 0, 0),
+        }
+    }
+}
+
+fn parse_rgb(s: &str) -> Option<Rgb> {
+    let s = s.trim_start_matches('#');
+    if s.len() != 6 {
+        return None;
+    }
+
+    let v = u32::from_str_radix(s, 16).ok()?;
+    Some(((v >> 16) as u8, (v >> 8) as u8, v as u8))
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    // "RRGGBB,RRGGBB", foreground first
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s.splitn(2, ',');
+        let fg = it.next().and_then(parse_rgb);
+        let bg = it.next().and_then(parse_rgb);
+
+        match (fg, bg) {
+            (Some(fg), Some(bg)) => Ok(Palette { fg, bg }),
+            _ => Err(format!("invalid palette '{}', expected RRGGBB,RRGGBB", s)),
+        }
+    }
+}
+
+// Calls `f` with the scaled frame, one output row at a time.
+fn for_each_scaled_row<F>(buf: Screen, scale: usize, mut f: F)
+    where F: FnMut(&[bool])
+{
+    let mut row = vec![false; DISPLAY_VISIBLE_WIDTH * scale];
+
+    for y in 0..DISPLAY_VISIBLE_HEIGHT {
+        for (x, px) in row.iter_mut().enumerate() {
+            *px = buf.pixel(x / scale, y);
+        }
+        for _ in 0..scale {
+            f(&row);
+        }
+    }
+}
+
+// Renders the visible part of VRAM as packed 24-bit RGB.
+pub fn render_rgb(buf: Screen, scale: usize, palette: &Palette) -> Vec<u8> {
+    let mut out = Vec::with_capacity(DISPLAY_VISIBLE_WIDTH * DISPLAY_VISIBLE_HEIGHT * scale * scale * 3);
+
+    for_each_scaled_row(buf, scale, |row| {
+        for &on in row {
+            let (r, g, b) = if on { palette.fg } else { palette.bg };
+            out.extend_from_slice(&[r, g, b]);
+        }
+    });
+    out
+}
+
+// Renders the visible part of VRAM as indices into [bg, fg].
+fn render_indexed(buf: Screen, scale: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(DISPLAY_VISIBLE_WIDTH * DISPLAY_VISIBLE_HEIGHT * scale * scale);
+
+    for_each_scaled_row(buf, scale, |row| {
+        out.extend(row.iter().map(|&on| on as u8));
+    });
+    out
+}
+
+fn encoding_err<E: ::std::fmt::Display>(e: E) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+pub fn save_png(path: &Path, buf: Screen,
+                scale: usize, palette: &Palette) -> io::Result<()> {
+    let w = BufWriter::new(File::create(path)?);
+    let mut enc = png::Encoder::new(w,
+                                    (DISPLAY_VISIBLE_WIDTH * scale) as u32,
+                                    (DISPLAY_VISIBLE_HEIGHT * scale) as u32);
+    enc.set_color(png::ColorType::Rgb);
+    enc.set_depth(png::BitDepth::Eight);
+
+    let mut writer = enc.write_header().map_err(encoding_err)?;
+    writer.write_image_data(&render_rgb(buf, scale, palette)).map_err(encoding_err)
+}
+
+// GIF delays are in 1/100 s, so 60 Hz frames are spread as 1,2,2,1,2,2,...
+// and every 3 frames take exactly 5/100 s.
+fn gif_delay(frame: u64) -> u16 {
+    ((frame + 1) * 100 / 60 - frame * 100 / 60) as u16
+}
+
+enum Sink {
+    Gif(gif::Encoder<BufWriter<File>>),
+    // headerless rgb24 frames, e.g.
+    // ffmpeg -f rawvideo -pix_fmt rgb24 -s 640x320 -r 60 -i rec.raw rec.mp4
+    Raw(BufWriter<File>),
+}
+
+pub struct Recorder {
+    sink: Sink,
+    scale: usize,
+    palette: Palette,
+    frames: u64,
+}
+
+impl Recorder {
+    // The format is picked from the file extension: ".gif" gives an
+    // animated GIF, anything else a raw rgb24 frame sequence.
+    pub fn new(path: &Path, scale: usize, palette: Palette) -> io::Result<Self> {
+        let w = BufWriter::new(File::create(path)?);
+        let is_gif = path.extension().map(|e| e.eq_ignore_ascii_case("gif")).unwrap_or(false);
+
+        let sink = if is_gif {
+            let gpal = [palette.bg.0, palette.bg.1, palette.bg.2,
+                        palette.fg.0, palette.fg.1, palette.fg.2];
+            let mut enc = gif::Encoder::new(w,
+                                            (DISPLAY_VISIBLE_WIDTH * scale) as u16,
+                                            (DISPLAY_VISIBLE_HEIGHT * scale) as u16,
+                                            &gpal).map_err(encoding_err)?;
+            enc.set_repeat(gif::Repeat::Infinite).map_err(encoding_err)?;
+            Sink::Gif(enc)
+        } else {
+            Sink::Raw(w)
+        };
+
+        Ok(Recorder {
+            sink,
+            scale,
+            palette,
+            frames: 0,
+        })
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn capture(&mut self, buf: Screen) -> io::Result<()> {
+        match self.sink {
+            Sink::Gif(ref mut enc) => {
+                let mut frame = gif::Frame::from_indexed_pixels(
+                    (DISPLAY_VISIBLE_WIDTH * self.scale) as u16,
+                    (DISPLAY_VISIBLE_HEIGHT * self.scale) as u16,
+                    render_indexed(buf, self.scale),
+                    None);
+                frame.delay = gif_delay(self.frames);
+                enc.write_frame(&frame).map_err(encoding_err)?;
+            },
+            Sink::Raw(ref mut w) => {
+                w.write_all(&render_rgb(buf, self.scale, &self.palette))?;
+            },
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+// Screenshot and recording state shared by the media backends.
+pub struct Capture {
+    pub scale: usize,
+    pub palette: Palette,
+    recorder: Option<Recorder>,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Capture::new(10, Palette::default())
+    }
+}
+
+impl Capture {
+    pub fn new(scale: usize, palette: Palette) -> Self {
+        Capture {
+            scale,
+            palette,
+            recorder: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    pub fn start_recording(&mut self, path: &Path) -> io::Result<()> {
+        self.recorder = Some(Recorder::new(path, self.scale, self.palette)?);
+        Ok(())
+    }
+
+    // Returns the number of frames written.
+    pub fn stop_recording(&mut self) -> u64 {
+        self.recorder.take().map(|r| r.frames()).unwrap_or(0)
+    }
+
+    pub fn screenshot(&self, path: &Path, buf: Screen) -> io::Result<()> {
+        save_png(path, buf, self.scale, &self.palette)
+    }
+
+    // Called once per emulated frame.
+    pub fn frame(&mut self, buf: Screen) {
+        let res = match self.recorder {
+            Some(ref mut r) => r.capture(buf),
+            None => Ok(()),
+        };
+
+        if let Err(e) = res {
+            println!("recording stopped: {}", e);
+            self.recorder = None;
+        }
+    }
+}
+
+// File name for hotkey captures, e.g. "chip8-1538316000123.png".
+pub fn timestamped_path(ext: &str) -> PathBuf {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    PathBuf::from(format!("chip8-{}.{}", millis, ext))
+}
+
+#[cfg(test)]
+mod tests;
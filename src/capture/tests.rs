@@ -0,0 +1,101 @@
+use super::*;
+
+use std::env;
+use std::fs;
+
+// the top left pixel and the one at (63, 31) lit
+fn screen() -> [DisplayRow; DISPLAY_VISIBLE_HEIGHT] {
+    let mut rows = [0; DISPLAY_VISIBLE_HEIGHT];
+    rows[0] = 1 << 63;
+    rows[31] = 1;
+    rows
+}
+
+fn temp(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("chip8-capture-test-{}-{}", ::std::process::id(), name))
+}
+
+#[test]
+fn parse_palette() {
+    assert_eq!("ffcc00,202020".parse(), Ok(Palette { fg: (255, 204, 0), bg: (32, 32, 32) }));
+    assert_eq!("#FFFFFF,#000000".parse(), Ok(Palette::default()));
+    assert!("ffcc00".parse::<Palette>().is_err());
+    assert!("ffcc0,202020".parse::<Palette>().is_err());
+    assert!("ffcc00,20202g".parse::<Palette>().is_err());
+}
+
+#[test]
+fn render_scaled() {
+    let rows = screen();
+    let palette = Palette { fg: (1, 2, 3), bg: (9, 9, 9) };
+    let rgb = render_rgb(Screen::new(&rows), 2, &palette);
+    let width = DISPLAY_VISIBLE_WIDTH * 2;
+    assert_eq!(rgb.len(), width * DISPLAY_VISIBLE_HEIGHT * 2 * 3);
+
+    let px = |x: usize, y: usize| &rgb[(y * width + x) * 3..(y * width + x) * 3 + 3];
+    // one CHIP-8 pixel is a 2x2 block
+    for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1), (126, 62), (127, 63)] {
+        assert_eq!(px(x, y), &[1, 2, 3], "({}, {})", x, y);
+    }
+    for &(x, y) in &[(2, 0), (0, 2), (125, 63), (127, 61)] {
+        assert_eq!(px(x, y), &[9, 9, 9], "({}, {})", x, y);
+    }
+
+    let indexed = render_indexed(Screen::new(&rows), 1);
+    assert_eq!(indexed.iter().filter(|&&i| i == 1).count(), 2);
+    assert_eq!(indexed[0], 1);
+}
+
+#[test]
+fn gif_delays_add_up_to_60_hz() {
+    let delays: Vec<u16> = (0..6).map(gif_delay).collect();
+    assert_eq!(delays, vec![1, 2, 2, 1, 2, 2]);
+
+    // never more than 1/100 s off the 60 Hz clock
+    let mut total = 0u64;
+    for frame in 0..6000 {
+        total += gif_delay(frame) as u64;
+        let exact = (frame + 1) * 100 / 60;
+        assert_eq!(total, exact, "frame {}", frame);
+    }
+    assert_eq!(total, 10000);
+}
+
+#[test]
+fn gif_recording() {
+    let path = temp("rec.gif");
+    let rows = screen();
+    let mut recorder = Recorder::new(&path, 1, Palette::default()).unwrap();
+    for _ in 0..4 {
+        recorder.capture(Screen::new(&rows)).unwrap();
+    }
+    assert_eq!(recorder.frames(), 4);
+    drop(recorder);
+
+    let mut decoder = gif::DecodeOptions::new().read_info(File::open(&path).unwrap()).unwrap();
+    let mut delays = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        assert_eq!((frame.width as usize, frame.height as usize),
+                   (DISPLAY_VISIBLE_WIDTH, DISPLAY_VISIBLE_HEIGHT));
+        delays.push(frame.delay);
+    }
+    fs::remove_file(&path).unwrap();
+    assert_eq!(delays, vec![1, 2, 2, 1]);
+}
+
+#[test]
+fn raw_recording() {
+    let path = temp("rec.raw");
+    let rows = screen();
+    let mut capture = Capture::new(2, Palette::default());
+    capture.start_recording(&path).unwrap();
+    assert!(capture.is_recording());
+    for _ in 0..3 {
+        capture.frame(Screen::new(&rows));
+    }
+    assert_eq!(capture.stop_recording(), 3);
+
+    let size = fs::metadata(&path).unwrap().len();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(size as usize, 3 * DISPLAY_VISIBLE_WIDTH * DISPLAY_VISIBLE_HEIGHT * 4 * 3);
+}
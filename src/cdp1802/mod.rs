@@ -0,0 +1,385 @@
+use cpu_ops::Fault;
+
+// RCA CDP1802, the CPU of the COSMAC VIP. Times are in machine cycles of
+// 8 clock cycles; most instructions take 2, long branches and skips 3.
+
+// Memory and the I/O lines of the chip, as wired up by the machine.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, val: u8);
+    // OUT 1 to 7 with the byte at R(X)
+    fn output(&mut self, port: u8, val: u8);
+    // INP 1 to 7, the byte read goes to M(R(X)) and D
+    fn input(&mut self, port: u8) -> u8;
+    // EF1 to EF4, true when the flag is asserted
+    fn flag(&mut self, n: u8) -> bool;
+}
+
+pub const INTERRUPT_CYCLES: u32 = 1;
+pub const DMA_CYCLES: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cdp1802 {
+    r: [u16; 16],
+    p: u8,
+    x: u8,
+    d: u8,
+    df: bool,
+    t: u8,
+    ie: bool,
+    q: bool,
+    // after IDL, until an interrupt or DMA
+    idle: bool,
+}
+
+impl Default for Cdp1802 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cdp1802 {
+    pub fn new() -> Self {
+        let mut cpu = Cdp1802 {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        };
+        cpu.reset();
+        cpu
+    }
+
+    // What the RESET line clears, the other registers keep their values.
+    pub fn reset(&mut self) {
+        self.r[0] = 0;
+        self.p = 0;
+        self.x = 0;
+        self.ie = true;
+        self.q = false;
+        self.idle = false;
+    }
+
+    pub fn r(&self, n: usize) -> u16 {
+        self.r[n]
+    }
+
+    pub fn set_r(&mut self, n: usize, val: u16) {
+        self.r[n] = val;
+    }
+
+    pub fn p(&self) -> u8 {
+        self.p
+    }
+
+    pub fn x(&self) -> u8 {
+        self.x
+    }
+
+    pub fn d(&self) -> u8 {
+        self.d
+    }
+
+    pub fn df(&self) -> bool {
+        self.df
+    }
+
+    pub fn ie(&self) -> bool {
+        self.ie
+    }
+
+    pub fn q(&self) -> bool {
+        self.q
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.r[self.p as usize]
+    }
+
+    // Registers in a fixed order, for state hashes
+    pub fn state(&self) -> Vec<u8> {
+        let mut s = Vec::with_capacity(40);
+        for r in self.r.iter() {
+            s.extend_from_slice(&[(r >> 8) as u8, *r as u8]);
+        }
+        s.extend_from_slice(&[self.p, self.x, self.d, self.df as u8, self.t,
+                              self.ie as u8, self.q as u8, self.idle as u8]);
+        s
+    }
+
+    // Taken between instructions when the interrupt line is asserted,
+    // returns false while interrupts are disabled.
+    pub fn interrupt(&mut self) -> bool {
+        if !self.ie {
+            return false;
+        }
+        self.t = self.x << 4 | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+        true
+    }
+
+    // A DMA out cycle: the byte at R(0) goes to the device.
+    pub fn dma_out<B: Bus + ?Sized>(&mut self, bus: &mut B) -> u8 {
+        let val = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        val
+    }
+
+    fn imm<B: Bus + ?Sized>(&mut self, bus: &mut B) -> u8 {
+        let p = self.p as usize;
+        let val = bus.read(self.r[p]);
+        self.r[p] = self.r[p].wrapping_add(1);
+        val
+    }
+
+    fn mx<B: Bus + ?Sized>(&mut self, bus: &mut B) -> u8 {
+        bus.read(self.r[self.x as usize])
+    }
+
+    fn inc_x(&mut self) {
+        let x = self.x as usize;
+        self.r[x] = self.r[x].wrapping_add(1);
+    }
+
+    fn add(&mut self, a: u8, b: u8, carry: bool) -> u8 {
+        let sum = a as u16 + b as u16 + carry as u16;
+        self.df = sum > 0xFF;
+        sum as u8
+    }
+
+    // a - b, DF is set when nothing was borrowed
+    fn sub(&mut self, a: u8, b: u8, borrow: bool) -> u8 {
+        self.add(a, !b, !borrow)
+    }
+
+    // Condition of the short and long branches by the low 3 bits of the
+    // opcode: always, Q, D zero, DF, EF1 to EF4.
+    fn condition<B: Bus + ?Sized>(&mut self, bus: &mut B, n: u8) -> bool {
+        match n & 7 {
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            3 => self.df,
+            f => bus.flag(f - 3),
+        }
+    }
+
+    fn short_branch<B: Bus + ?Sized>(&mut self, bus: &mut B, taken: bool) {
+        let p = self.p as usize;
+        if taken {
+            let lo = bus.read(self.r[p]);
+            self.r[p] = self.r[p] & 0xFF00 | lo as u16;
+        } else {
+            self.r[p] = self.r[p].wrapping_add(1);
+        }
+    }
+
+    fn long_branch<B: Bus + ?Sized>(&mut self, bus: &mut B, taken: bool) {
+        let p = self.p as usize;
+        if taken {
+            let hi = bus.read(self.r[p]);
+            let lo = bus.read(self.r[p].wrapping_add(1));
+            self.r[p] = (hi as u16) << 8 | lo as u16;
+        } else {
+            self.r[p] = self.r[p].wrapping_add(2);
+        }
+    }
+
+    fn long_skip(&mut self, taken: bool) {
+        if taken {
+            let p = self.p as usize;
+            self.r[p] = self.r[p].wrapping_add(2);
+        }
+    }
+
+    // Executes one instruction and returns the machine cycles it took.
+    // While idle only a cycle passes.
+    pub fn step<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<u32, Fault> {
+        if self.idle {
+            return Ok(1);
+        }
+
+        let pc = self.pc();
+        let op = self.imm(bus);
+        let n = op & 0xF;
+        let rn = n as usize;
+
+        match op >> 4 {
+            0x0 if n == 0 => self.idle = true,
+            0x0 => self.d = bus.read(self.r[rn]),
+            0x1 => self.r[rn] = self.r[rn].wrapping_add(1),
+            0x2 => self.r[rn] = self.r[rn].wrapping_sub(1),
+            0x3 => {
+                let taken = self.condition(bus, n) != (n & 8 != 0);
+                self.short_branch(bus, taken);
+            },
+            0x4 => {
+                self.d = bus.read(self.r[rn]);
+                self.r[rn] = self.r[rn].wrapping_add(1);
+            },
+            0x5 => bus.write(self.r[rn], self.d),
+            0x6 => match n {
+                0x0 => self.inc_x(),
+                0x1..=0x7 => {
+                    let val = self.mx(bus);
+                    bus.output(n, val);
+                    self.inc_x();
+                },
+                0x8 => return Err(Fault::InvalidInstruction { pc, instruction: op as u16 }),
+                _ => {
+                    let val = bus.input(n & 7);
+                    bus.write(self.r[self.x as usize], val);
+                    self.d = val;
+                },
+            },
+            0x7 => match n {
+                // RET, DIS
+                0x0 | 0x1 => {
+                    let t = self.mx(bus);
+                    self.inc_x();
+                    self.x = t >> 4;
+                    self.p = t & 0xF;
+                    self.ie = n == 0;
+                },
+                // LDXA
+                0x2 => {
+                    self.d = self.mx(bus);
+                    self.inc_x();
+                },
+                // STXD
+                0x3 => {
+                    let x = self.x as usize;
+                    bus.write(self.r[x], self.d);
+                    self.r[x] = self.r[x].wrapping_sub(1);
+                },
+                // ADC
+                0x4 => {
+                    let m = self.mx(bus);
+                    self.d = self.add(m, self.d, self.df);
+                },
+                // SDB
+                0x5 => {
+                    let m = self.mx(bus);
+                    self.d = self.sub(m, self.d, !self.df);
+                },
+                // SHRC
+                0x6 => {
+                    let carry = self.df;
+                    self.df = self.d & 1 != 0;
+                    self.d = self.d >> 1 | (carry as u8) << 7;
+                },
+                // SMB
+                0x7 => {
+                    let m = self.mx(bus);
+                    self.d = self.sub(self.d, m, !self.df);
+                },
+                // SAV
+                0x8 => bus.write(self.r[self.x as usize], self.t),
+                // MARK
+                0x9 => {
+                    self.t = self.x << 4 | self.p;
+                    bus.write(self.r[2], self.t);
+                    self.x = self.p;
+                    self.r[2] = self.r[2].wrapping_sub(1);
+                },
+                0xA => self.q = false,
+                0xB => self.q = true,
+                // ADCI
+                0xC => {
+                    let m = self.imm(bus);
+                    self.d = self.add(m, self.d, self.df);
+                },
+                // SDBI
+                0xD => {
+                    let m = self.imm(bus);
+                    self.d = self.sub(m, self.d, !self.df);
+                },
+                // SHLC
+                0xE => {
+                    let carry = self.df;
+                    self.df = self.d & 0x80 != 0;
+                    self.d = self.d << 1 | carry as u8;
+                },
+                // SMBI
+                _ => {
+                    let m = self.imm(bus);
+                    self.d = self.sub(self.d, m, !self.df);
+                },
+            },
+            0x8 => self.d = self.r[rn] as u8,
+            0x9 => self.d = (self.r[rn] >> 8) as u8,
+            0xA => self.r[rn] = self.r[rn] & 0xFF00 | self.d as u16,
+            0xB => self.r[rn] = self.r[rn] & 0x00FF | (self.d as u16) << 8,
+            0xC => {
+                match n {
+                    // LBR, LBQ, LBZ, LBDF
+                    0x0..=0x3 => {
+                        let taken = self.condition(bus, n);
+                        self.long_branch(bus, taken);
+                    },
+                    // NOP
+                    0x4 => {},
+                    // LSNQ, LSNZ, LSNF, LSKP
+                    0x5..=0x8 => {
+                        let taken = n == 0x8 || !self.condition(bus, n - 4);
+                        self.long_skip(taken);
+                    },
+                    // LBNQ, LBNZ, LBNF
+                    0x9..=0xB => {
+                        let taken = !self.condition(bus, n - 8);
+                        self.long_branch(bus, taken);
+                    },
+                    // LSIE
+                    0xC => self.long_skip(self.ie),
+                    // LSQ, LSZ, LSDF
+                    _ => {
+                        let taken = self.condition(bus, n - 0xC);
+                        self.long_skip(taken);
+                    },
+                }
+                return Ok(3);
+            },
+            0xD => self.p = n,
+            0xE => self.x = n,
+            _ => match n {
+                // SHR
+                0x6 => {
+                    self.df = self.d & 1 != 0;
+                    self.d >>= 1;
+                },
+                // SHL
+                0xE => {
+                    self.df = self.d & 0x80 != 0;
+                    self.d <<= 1;
+                },
+                _ => {
+                    // LDX, OR, AND, XOR, ADD, SD, SM with M(R(X)), from
+                    // F8 on the immediate forms with the byte after the opcode
+                    let m = if n & 8 != 0 { self.imm(bus) } else { self.mx(bus) };
+                    self.d = match n & 7 {
+                        0x0 => m,
+                        0x1 => self.d | m,
+                        0x2 => self.d & m,
+                        0x3 => self.d ^ m,
+                        0x4 => self.add(m, self.d, false),
+                        0x5 => self.sub(m, self.d, false),
+                        _ => self.sub(self.d, m, false),
+                    };
+                },
+            },
+        }
+        Ok(2)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,332 @@
+use super::*;
+
+// 64K of RAM, the flags as set by the test and the OUT writes seen.
+struct TestBus {
+    mem: Vec<u8>,
+    flags: [bool; 4],
+    input: u8,
+    outputs: Vec<(u8, u8)>,
+}
+
+impl TestBus {
+    fn new(program: &[u8]) -> TestBus {
+        let mut mem = vec![0; 0x10000];
+        mem[..program.len()].clone_from_slice(program);
+        TestBus { mem, flags: [false; 4], input: 0, outputs: Vec::new() }
+    }
+}
+
+impl Bus for TestBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.mem[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.mem[addr as usize] = val;
+    }
+
+    fn output(&mut self, port: u8, val: u8) {
+        self.outputs.push((port, val));
+    }
+
+    fn input(&mut self, _port: u8) -> u8 {
+        self.input
+    }
+
+    fn flag(&mut self, n: u8) -> bool {
+        self.flags[n as usize - 1]
+    }
+}
+
+// Runs `steps` instructions of `program` at 0000 after `init`, returns
+// the CPU, the bus and the cycles taken.
+fn exec<F: FnOnce(&mut Cdp1802, &mut TestBus)>(program: &[u8], steps: usize, init: F)
+                                              -> (Cdp1802, TestBus, u32) {
+    let mut cpu = Cdp1802::new();
+    let mut bus = TestBus::new(program);
+    init(&mut cpu, &mut bus);
+    let mut cycles = 0;
+    for _ in 0..steps {
+        cycles += cpu.step(&mut bus).unwrap();
+    }
+    (cpu, bus, cycles)
+}
+
+fn run(program: &[u8]) -> Cdp1802 {
+    exec(program, program.len(), |_, _| {}).0
+}
+
+// D after `op` with D = d, M(R(X)) = m and DF = df
+fn alu(op: u8, d: u8, m: u8, df: bool) -> (u8, bool) {
+    let (cpu, _, _) = exec(&[op], 1, |cpu, bus| {
+        cpu.d = d;
+        cpu.df = df;
+        cpu.x = 1;
+        cpu.r[1] = 0x100;
+        bus.mem[0x100] = m;
+    });
+    (cpu.d, cpu.df)
+}
+
+#[test]
+fn reset_state() {
+    let cpu = Cdp1802::new();
+    assert_eq!((cpu.p(), cpu.x(), cpu.r(0)), (0, 0, 0));
+    assert!(cpu.ie());
+    assert!(!cpu.q());
+}
+
+#[test]
+fn ldi_plo_phi_glo_ghi() {
+    // LDI 12, PHI 3, LDI 34, PLO 3, GHI 3, PLO 4, GLO 3
+    let cpu = run(&[0xF8, 0x12, 0xB3, 0xF8, 0x34, 0xA3, 0x93, 0xA4, 0x83]);
+    assert_eq!(cpu.r(3), 0x1234);
+    assert_eq!(cpu.r(4), 0x0012);
+    assert_eq!(cpu.d(), 0x34);
+}
+
+#[test]
+fn inc_dec_wrap() {
+    let (cpu, _, _) = exec(&[0x13, 0x24], 2, |cpu, _| {
+        cpu.r[3] = 0xFFFF;
+        cpu.r[4] = 0;
+    });
+    assert_eq!(cpu.r(3), 0);
+    assert_eq!(cpu.r(4), 0xFFFF);
+}
+
+#[test]
+fn ldn_lda_str() {
+    // LDA 5, STR 6, LDN 5
+    let (cpu, bus, _) = exec(&[0x45, 0x56, 0x05], 3, |cpu, bus| {
+        cpu.r[5] = 0x100;
+        cpu.r[6] = 0x200;
+        bus.mem[0x100] = 0xAB;
+        bus.mem[0x101] = 0xCD;
+    });
+    assert_eq!(bus.mem[0x200], 0xAB);
+    assert_eq!(cpu.r(5), 0x101);
+    assert_eq!(cpu.d(), 0xCD);
+}
+
+#[test]
+fn ldxa_stxd_irx() {
+    // SEX 2, LDXA, STXD, IRX
+    let (cpu, bus, _) = exec(&[0xE2, 0x72, 0x73, 0x60], 4, |cpu, bus| {
+        cpu.r[2] = 0x100;
+        bus.mem[0x100] = 0x42;
+    });
+    assert_eq!(bus.mem[0x101], 0x42);
+    assert_eq!(cpu.r(2), 0x101);
+}
+
+#[test]
+fn add_sets_carry() {
+    assert_eq!(alu(0xF4, 0x80, 0x80, false), (0x00, true));
+    assert_eq!(alu(0xF4, 0x01, 0x02, true), (0x03, false));
+    // ADC adds DF in
+    assert_eq!(alu(0x74, 0x01, 0x02, true), (0x04, false));
+    assert_eq!(alu(0x74, 0xFF, 0x00, true), (0x00, true));
+}
+
+#[test]
+fn subtract_df_means_no_borrow() {
+    // SD: M - D
+    assert_eq!(alu(0xF5, 0x01, 0x03, false), (0x02, true));
+    assert_eq!(alu(0xF5, 0x03, 0x01, false), (0xFE, false));
+    // SM: D - M
+    assert_eq!(alu(0xF7, 0x03, 0x01, false), (0x02, true));
+    assert_eq!(alu(0xF7, 0x01, 0x03, false), (0xFE, false));
+    // SMB borrows when DF is clear
+    assert_eq!(alu(0x77, 0x03, 0x01, false), (0x01, true));
+    assert_eq!(alu(0x77, 0x03, 0x01, true), (0x02, true));
+    // SDB
+    assert_eq!(alu(0x75, 0x01, 0x03, false), (0x01, true));
+}
+
+#[test]
+fn logic() {
+    assert_eq!(alu(0xF1, 0xF0, 0x0F, false).0, 0xFF);
+    assert_eq!(alu(0xF2, 0xF0, 0x3C, false).0, 0x30);
+    assert_eq!(alu(0xF3, 0xFF, 0x0F, false).0, 0xF0);
+    assert_eq!(alu(0xF0, 0x00, 0x5A, false).0, 0x5A);
+}
+
+#[test]
+fn immediate_forms_read_the_next_byte() {
+    // LDI 10, ADI F0, SMI 01, ORI 03, ANI 0E, XRI 01
+    let program = [0xF8, 0x10, 0xFC, 0xF0, 0xFF, 0x01, 0xF9, 0x03, 0xFA, 0x0E, 0xFB, 0x01];
+    let cpu = exec(&program, 6, |_, _| {}).0;
+    assert_eq!(cpu.d(), 0x0F);
+    assert_eq!(cpu.r(0), 12);
+}
+
+#[test]
+fn shifts() {
+    assert_eq!(alu(0xF6, 0x81, 0, false), (0x40, true));
+    assert_eq!(alu(0xFE, 0x81, 0, false), (0x02, true));
+    // through DF
+    assert_eq!(alu(0x76, 0x02, 0, true), (0x81, false));
+    assert_eq!(alu(0x7E, 0x40, 0, true), (0x81, false));
+}
+
+#[test]
+fn shl_takes_no_operand() {
+    let (cpu, _, _) = exec(&[0xFE, 0xFE], 1, |_, _| {});
+    assert_eq!(cpu.r(0), 1);
+}
+
+#[test]
+fn short_branch_within_page() {
+    // BZ 40 taken with D = 0
+    let (cpu, _, cycles) = exec(&[0x32, 0x40], 1, |_, _| {});
+    assert_eq!(cpu.r(0), 0x0040);
+    assert_eq!(cycles, 2);
+
+    // BNZ 40 falls through
+    let (cpu, _, _) = exec(&[0x3A, 0x40], 1, |_, _| {});
+    assert_eq!(cpu.r(0), 0x0002);
+}
+
+#[test]
+fn short_branch_on_flags() {
+    // B3 40
+    let (cpu, _, _) = exec(&[0x36, 0x40], 1, |_, bus| bus.flags[2] = true);
+    assert_eq!(cpu.r(0), 0x40);
+    // BN3 40
+    let (cpu, _, _) = exec(&[0x3E, 0x40], 1, |_, bus| bus.flags[2] = true);
+    assert_eq!(cpu.r(0), 0x02);
+}
+
+#[test]
+fn skp_skips_one_byte() {
+    let cpu = exec(&[0x38, 0xFF], 1, |_, _| {}).0;
+    assert_eq!(cpu.r(0), 2);
+}
+
+#[test]
+fn long_branch_takes_3_cycles() {
+    let (cpu, _, cycles) = exec(&[0xC0, 0x12, 0x34], 1, |_, _| {});
+    assert_eq!(cpu.r(0), 0x1234);
+    assert_eq!(cycles, 3);
+
+    // LBNZ with D = 0 falls through past the address
+    let (cpu, _, _) = exec(&[0xCA, 0x12, 0x34], 1, |_, _| {});
+    assert_eq!(cpu.r(0), 3);
+}
+
+#[test]
+fn long_skips() {
+    // LSZ with D = 0
+    assert_eq!(exec(&[0xCE], 1, |_, _| {}).0.r(0), 3);
+    // LSNZ with D = 0
+    assert_eq!(exec(&[0xC6], 1, |_, _| {}).0.r(0), 1);
+    // LSKP
+    assert_eq!(exec(&[0xC8], 1, |_, _| {}).0.r(0), 3);
+    // LSIE after reset
+    assert_eq!(exec(&[0xCC], 1, |_, _| {}).0.r(0), 3);
+    // NOP
+    let (cpu, _, cycles) = exec(&[0xC4], 1, |_, _| {});
+    assert_eq!((cpu.r(0), cycles), (1, 3));
+}
+
+#[test]
+fn sep_sex() {
+    // LDI 10, PLO 3, SEP 3, SEX 5
+    let (cpu, _, _) = exec(&[0xF8, 0x10, 0xA3, 0xD3], 4, |_, bus| bus.mem[0x10] = 0xE5);
+    assert_eq!(cpu.p(), 3);
+    assert_eq!(cpu.x(), 5);
+    assert_eq!(cpu.pc(), 0x11);
+}
+
+#[test]
+fn mark_and_ret() {
+    // SEP 3 with X = 4, then MARK, SEX 2, INC 2, RET returns to X = 4
+    let (cpu, bus, _) = exec(&[0xD3], 5, |cpu, bus| {
+        cpu.x = 4;
+        cpu.r[2] = 0x100;
+        cpu.r[3] = 0x20;
+        bus.mem[0x20..0x24].clone_from_slice(&[0x79, 0xE2, 0x12, 0x70]);
+    });
+    assert_eq!(bus.mem[0x100], 0x43);
+    assert_eq!((cpu.x(), cpu.p()), (4, 3));
+    assert_eq!(cpu.r(2), 0x101);
+    assert!(cpu.ie());
+}
+
+#[test]
+fn interrupt_saves_x_and_p() {
+    let mut cpu = Cdp1802::new();
+    let mut bus = TestBus::new(&[]);
+    cpu.x = 5;
+    cpu.p = 3;
+    assert!(cpu.interrupt());
+    assert_eq!((cpu.t, cpu.x(), cpu.p()), (0x53, 2, 1));
+    assert!(!cpu.ie());
+    // disabled until RET
+    assert!(!cpu.interrupt());
+
+    // SAV
+    bus.mem[0] = 0x78;
+    cpu.r[1] = 0;
+    cpu.r[2] = 0x100;
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(bus.mem[0x100], 0x53);
+}
+
+#[test]
+fn dis_keeps_interrupts_off() {
+    let (cpu, _, _) = exec(&[0x71], 1, |cpu, bus| {
+        cpu.r[0] = 0;
+        cpu.x = 2;
+        cpu.r[2] = 0x100;
+        bus.mem[0x100] = 0x00;
+    });
+    assert!(!cpu.ie());
+}
+
+#[test]
+fn idle_until_interrupt() {
+    let (mut cpu, mut bus, _) = exec(&[0x00, 0xC4], 2, |_, _| {});
+    assert_eq!(cpu.r(0), 1);
+    assert_eq!(cpu.step(&mut bus), Ok(1));
+    assert!(cpu.interrupt());
+    assert_eq!(cpu.step(&mut bus), Ok(2));
+}
+
+#[test]
+fn dma_out_reads_at_r0() {
+    let mut cpu = Cdp1802::new();
+    let mut bus = TestBus::new(&[0x11, 0x22]);
+    assert_eq!(cpu.dma_out(&mut bus), 0x11);
+    assert_eq!(cpu.dma_out(&mut bus), 0x22);
+    assert_eq!(cpu.r(0), 2);
+}
+
+#[test]
+fn out_and_inp() {
+    // SEX 2, OUT 2, INP 1
+    let (cpu, bus, _) = exec(&[0xE2, 0x62, 0x69], 3, |cpu, bus| {
+        cpu.r[2] = 0x100;
+        bus.mem[0x100] = 0x07;
+        bus.input = 0x5A;
+    });
+    assert_eq!(bus.outputs, vec![(2, 0x07)]);
+    assert_eq!(cpu.r(2), 0x101);
+    assert_eq!(bus.mem[0x101], 0x5A);
+    assert_eq!(cpu.d(), 0x5A);
+}
+
+#[test]
+fn seq_req() {
+    assert!(run(&[0x7B]).q());
+    assert!(!run(&[0x7B, 0x7A]).q());
+}
+
+#[test]
+fn opcode_68_is_invalid() {
+    let mut cpu = Cdp1802::new();
+    let mut bus = TestBus::new(&[0xC4, 0x68]);
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.step(&mut bus), Err(Fault::InvalidInstruction { pc: 1, instruction: 0x68 }));
+}
@@ -0,0 +1,415 @@
+use chip8_opcode::capture::*;
+use chip8_opcode::cheat::*;
+use chip8_opcode::phosphor::*;
+
+use std::path::PathBuf;
+
+pub const EXIT_OK: i32 = 0;
+// the ROM could not be loaded or run, or a test failed
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+pub const USAGE: &str = "\
+usage: chip8_opcode COMMAND ROM [OPTIONS]
+
+commands:
+    run       play a ROM (the default when COMMAND is left out)
+    disasm    print a disassembly listing
+    info      print size, hashes, database entry, platform guess and hex dump
+    trace     print every executed instruction with the registers
+    bench     run without a window as fast as possible and report the speed
+    test      run without a window and compare the final screen with a hash
+    search    find the addresses of a value by how it changes over the frames
+    diff      create an IPS or BPS patch from two ROMs
+
+ROM is a file or an entry of a zip archive, e.g. res/c8games.zip:PONG. Given
+only an archive, its entries are listed. A patch next to the ROM with the
+same name and the extension .bps or .ips is applied when it is loaded.
+
+See 'chip8_opcode COMMAND --help' for the options of a command.
+";
+
+const PATCH_OPTIONS: &str = "    --patch FILE         apply an IPS or BPS patch instead of the one found
+                         next to the ROM, \"none\" applies no patch
+";
+
+const MACHINE_OPTIONS: &str = "    --speed N            instructions per frame, default from the ROM database or 10
+    --quirks LIST        preset legacy, vip, schip or xochip and/or single
+                         settings, e.g. vip,jump=1 or shift=0,logic=1
+    --seed N             seed of the RND random number generator
+    --memory N           bytes of memory, 4096 up to 65536, default from the
+                         ROM database or 4096
+    --stack N            return addresses the stack holds, 0 for unlimited,
+                         default from the ROM database or 16
+    --timing MODE        ipf runs --speed instructions per frame, vip as many
+                         as fit in a frame of a 1.76 MHz COSMAC VIP
+    --cpu NAME           hle interprets CHIP-8 directly, cdp1802 runs the
+                         original interpreter on an emulated COSMAC VIP
+    --vip-rom FILE       the VIP's 512 byte monitor ROM, for --cpu cdp1802
+    --interpreter FILE   the VIP's CHIP-8 interpreter, loaded at 0000
+    --db DIR             load the ROM database from DIR instead of the bundled one
+    --db-match SHA1      use the database entry of SHA1 instead of the ROM's
+                         own hash, \"none\" ignores the database
+    --config FILE        read settings from FILE instead of
+                         $XDG_CONFIG_HOME/chip8_opcode/config.toml
+    --no-config          ignore the configuration file
+";
+
+const PROFILE_OPTIONS: &str = "    --profile FILE       count the instructions by handler and address and time
+                         the frames, report hot loops and addresses to FILE
+                         on exit; .json gives JSON, - prints the text report
+    --folded FILE        instructions per CALL stack as folded stacks for
+                         flame graph tools
+";
+
+const CHEAT_OPTIONS: &str = "    --cheat LIST         freeze memory after every frame, ADDR=VALUE in hex,
+                         e.g. 02F3=05,0300=FF; cheats saved for the ROM apply
+                         as well
+";
+
+const RUN_USAGE: &str = "\
+usage: chip8_opcode run ROM [OPTIONS]
+
+    --headless           run without a window
+    --frames N           stop after N frames
+    --scale N            scale of the window, screenshots and recordings (default 10)
+    --fullscreen         fill the screen
+    --mute               no sound
+    --palette FG,BG      colours of window, screenshots and recordings, e.g. ffffff,000000
+    --keymap LIST        extra key bindings HOST=KEY with SDL key names and
+                         CHIP-8 keys in hex, e.g. W=5,S=8
+    --persistence MODE   phosphor persistence: off, fade:N or max:N (frames)
+    --screenshot FILE    save the final frame as PNG on exit
+    --record FILE        record every frame; .gif gives an animated GIF,
+                         anything else raw rgb24 frames for ffmpeg:
+                         ffmpeg -f rawvideo -pix_fmt rgb24 -s 640x320 -r 60 -i FILE out.mp4
+    --movie-record FILE  record the keypad of every frame to an input movie
+    --movie-play FILE    replay an input movie instead of live input; seed,
+                         speed, quirks, timing, memory size and stack depth
+                         are taken from the movie and state hashes stored
+                         every 60 frames are checked for desyncs
+    --save-cheats        save the --cheat list for the ROM's SHA-1 in the
+                         configuration
+    --console            read RAM search and cheat commands from stdin while
+                         the game runs, see 'help' there
+";
+
+const DISASM_USAGE: &str = "\
+usage: chip8_opcode disasm ROM [OPTIONS]
+
+Prints ADDRESS  WORD  MNEMONIC for every word of the ROM.
+
+";
+
+const INFO_USAGE: &str = "\
+usage: chip8_opcode info ROM [OPTIONS]
+
+    --db DIR             load the ROM database from DIR instead of the bundled one
+    --db-match SHA1      use the database entry of SHA1 instead of the ROM's
+                         own hash, \"none\" ignores the database
+";
+
+const TRACE_USAGE: &str = "\
+usage: chip8_opcode trace ROM [OPTIONS]
+
+Runs without a window and prints every executed instruction.
+
+    --instructions N     stop after N instructions (default 1000)
+";
+
+const BENCH_USAGE: &str = "\
+usage: chip8_opcode bench ROM [OPTIONS]
+
+Runs without a window and without the 60 Hz throttle.
+
+    --frames N           number of frames to run (default 600)
+";
+
+const TEST_USAGE: &str = "\
+usage: chip8_opcode test ROM [OPTIONS]
+
+Runs without a window for a number of frames and prints a hash of the final
+screen. Exits with 1 if it differs from the expected one.
+
+    --frames N           number of frames to run (default 600)
+    --expect HASH        expected screen hash in hex
+    --screenshot FILE    save the final frame as PNG
+";
+
+const SEARCH_USAGE: &str = "\
+usage: chip8_opcode search ROM [OPTIONS]
+
+Runs without a window and narrows down the addresses that hold a value, e.g.
+the lives left, by comparing memory at the given frames with the last
+snapshot. Prints the addresses left with their values.
+
+    --filter FRAME:REL   at FRAME keep the addresses whose value stayed the
+                         same, changed, went up (inc) or down (dec) since the
+                         last filter, or equals a value, e.g. 300:dec or 600:2;
+                         may be repeated, frame 0 is the ROM just loaded
+    --movie-play FILE    press the keys of an input movie
+";
+
+const DIFF_USAGE: &str = "\
+usage: chip8_opcode diff ORIGINAL MODIFIED PATCH
+
+Writes a patch turning ORIGINAL into MODIFIED to PATCH, in the format of its
+extension: .ips or .bps. BPS patches carry checksums of both ROMs.
+";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Run,
+    Disasm,
+    Info,
+    Trace,
+    Bench,
+    Test,
+    Search,
+    Diff,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        let cmd = match name {
+            "run" => Command::Run,
+            "disasm" => Command::Disasm,
+            "info" => Command::Info,
+            "trace" => Command::Trace,
+            "bench" => Command::Bench,
+            "test" => Command::Test,
+            "search" => Command::Search,
+            "diff" => Command::Diff,
+            _ => return None,
+        };
+        Some(cmd)
+    }
+
+    pub fn usage(self) -> String {
+        match self {
+            Command::Run => format!("{}{}{}{}{}", RUN_USAGE, CHEAT_OPTIONS, PROFILE_OPTIONS,
+                                    PATCH_OPTIONS, MACHINE_OPTIONS),
+            Command::Disasm => format!("{}{}", DISASM_USAGE, PATCH_OPTIONS),
+            Command::Info => format!("{}{}", INFO_USAGE, PATCH_OPTIONS),
+            Command::Trace => format!("{}{}{}", TRACE_USAGE, PATCH_OPTIONS, MACHINE_OPTIONS),
+            Command::Bench => format!("{}{}{}{}{}", BENCH_USAGE, CHEAT_OPTIONS, PROFILE_OPTIONS,
+                                      PATCH_OPTIONS, MACHINE_OPTIONS),
+            Command::Test => format!("{}{}{}{}{}", TEST_USAGE, CHEAT_OPTIONS, PROFILE_OPTIONS,
+                                     PATCH_OPTIONS, MACHINE_OPTIONS),
+            Command::Search => format!("{}{}{}{}", SEARCH_USAGE, CHEAT_OPTIONS, PATCH_OPTIONS, MACHINE_OPTIONS),
+            Command::Diff => DIFF_USAGE.to_string(),
+        }
+    }
+
+    // --speed, --quirks, --seed, --memory, --stack, --timing, --cpu
+    fn runs_machine(self) -> bool {
+        self != Command::Disasm && self != Command::Info && self != Command::Diff
+    }
+}
+
+pub struct Options {
+    pub command: Command,
+    pub rom: String,
+    pub speed: Option<u32>,
+    pub quirks: Option<String>,
+    pub seed: Option<u64>,
+    pub memory: Option<usize>,
+    pub stack: Option<usize>,
+    pub timing: Option<String>,
+    pub cpu: Option<String>,
+    pub vip_rom: Option<PathBuf>,
+    pub interpreter: Option<PathBuf>,
+    pub db_dir: Option<PathBuf>,
+    pub db_match: Option<String>,
+    pub headless: bool,
+    pub frames: Option<u64>,
+    pub instructions: u64,
+    pub expect: Option<u64>,
+    pub config: Option<PathBuf>,
+    pub no_config: bool,
+    pub scale: Option<u32>,
+    pub fullscreen: bool,
+    pub mute: bool,
+    pub palette: Option<Palette>,
+    pub keymap: Option<String>,
+    pub persistence: Option<Persistence>,
+    pub screenshot: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub movie_record: Option<PathBuf>,
+    pub movie_play: Option<PathBuf>,
+    pub profile: Option<PathBuf>,
+    pub folded: Option<PathBuf>,
+    pub cheats: Option<String>,
+    pub save_cheats: bool,
+    pub console: bool,
+    // (frame, relation) in the order given
+    pub filters: Vec<(u64, Relation)>,
+    pub patch: Option<String>,
+    // the ROMs after the first, for diff
+    pub more_roms: Vec<String>,
+}
+
+// Ok(None) means help was asked for and printed.
+pub type ParseResult = Result<Option<Options>, String>;
+
+fn parse_value<T: ::std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+// FRAME:REL of --filter
+fn parse_filter(value: &str) -> Result<(u64, Relation), String> {
+    let mut it = value.splitn(2, ':');
+    let frame = it.next().unwrap_or("");
+    let frame = frame.parse()
+        .map_err(|_| format!("invalid frame '{}' for --filter, expected FRAME:REL", frame))?;
+    let rel = it.next().ok_or_else(|| format!("invalid filter '{}', expected FRAME:REL", value))?;
+    Ok((frame, rel.parse()?))
+}
+
+fn parse_hex(flag: &str, value: &str) -> Result<u64, String> {
+    u64::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid value '{}' for {}, expected hex", value, flag))
+}
+
+// `args` excludes the program name.
+pub fn parse_args<I: Iterator<Item = String>>(args: I) -> ParseResult {
+    let mut args = args.peekable();
+
+    let command = match args.peek().map(|a| a.as_str()) {
+        None => return Err("no ROM given".to_string()),
+        Some("-h") | Some("--help") | Some("help") => {
+            args.next();
+            match args.next() {
+                Some(name) => {
+                    let cmd = Command::from_name(&name)
+                        .ok_or_else(|| format!("unknown command '{}'", name))?;
+                    print!("{}", cmd.usage());
+                },
+                None => print!("{}", USAGE),
+            }
+            return Ok(None);
+        },
+        Some(name) => match Command::from_name(name) {
+            Some(cmd) => {
+                args.next();
+                cmd
+            },
+            None => Command::Run,
+        },
+    };
+
+    let mut opts = Options {
+        command,
+        rom: String::new(),
+        speed: None,
+        quirks: None,
+        seed: None,
+        memory: None,
+        stack: None,
+        timing: None,
+        cpu: None,
+        vip_rom: None,
+        interpreter: None,
+        db_dir: None,
+        db_match: None,
+        headless: command != Command::Run,
+        frames: None,
+        instructions: 1000,
+        expect: None,
+        config: None,
+        no_config: false,
+        scale: None,
+        fullscreen: false,
+        mute: false,
+        palette: None,
+        keymap: None,
+        persistence: None,
+        screenshot: None,
+        record: None,
+        movie_record: None,
+        movie_play: None,
+        profile: None,
+        folded: None,
+        cheats: None,
+        save_cheats: false,
+        console: false,
+        filters: Vec::new(),
+        patch: None,
+        more_roms: Vec::new(),
+    };
+    let mut rom = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            print!("{}", command.usage());
+            return Ok(None);
+        }
+
+        if !arg.starts_with("--") {
+            if rom.is_none() {
+                rom = Some(arg);
+            } else if command == Command::Diff && opts.more_roms.len() < 2 {
+                opts.more_roms.push(arg);
+            } else {
+                return Err(format!("unexpected argument '{}'", arg));
+            }
+            continue;
+        }
+
+        let mut value = || args.next().ok_or_else(|| format!("{} expects a value", arg));
+        let run = command == Command::Run;
+        let machine = command.runs_machine();
+        let frames = run || command == Command::Bench || command == Command::Test;
+
+        match arg.as_str() {
+            "--speed" if machine => opts.speed = Some(parse_value(&arg, &value()?)?),
+            "--quirks" if machine => opts.quirks = Some(value()?),
+            "--seed" if machine => opts.seed = Some(parse_value(&arg, &value()?)?),
+            "--memory" if machine => opts.memory = Some(parse_value(&arg, &value()?)?),
+            "--stack" if machine => opts.stack = Some(parse_value(&arg, &value()?)?),
+            "--timing" if machine => opts.timing = Some(value()?),
+            "--cpu" if machine => opts.cpu = Some(value()?),
+            "--vip-rom" if machine => opts.vip_rom = Some(PathBuf::from(value()?)),
+            "--interpreter" if machine => opts.interpreter = Some(PathBuf::from(value()?)),
+            "--patch" if command != Command::Diff => opts.patch = Some(value()?),
+            "--db" if command != Command::Disasm && command != Command::Diff => opts.db_dir = Some(PathBuf::from(value()?)),
+            "--db-match" if command != Command::Disasm && command != Command::Diff => opts.db_match = Some(value()?),
+            "--frames" if frames => opts.frames = Some(parse_value(&arg, &value()?)?),
+            "--instructions" if command == Command::Trace => {
+                opts.instructions = parse_value(&arg, &value()?)?
+            },
+            "--expect" if command == Command::Test => opts.expect = Some(parse_hex(&arg, &value()?)?),
+            "--screenshot" if run || command == Command::Test => {
+                opts.screenshot = Some(PathBuf::from(value()?))
+            },
+            "--config" if machine => opts.config = Some(PathBuf::from(value()?)),
+            "--no-config" if machine => opts.no_config = true,
+            "--headless" if run => opts.headless = true,
+            "--scale" if run => opts.scale = Some(parse_value(&arg, &value()?)?),
+            "--fullscreen" if run => opts.fullscreen = true,
+            "--mute" if run => opts.mute = true,
+            "--palette" if run => opts.palette = Some(value()?.parse()?),
+            "--keymap" if run => opts.keymap = Some(value()?),
+            "--persistence" if run => opts.persistence = Some(value()?.parse()?),
+            "--record" if run => opts.record = Some(PathBuf::from(value()?)),
+            "--movie-record" if run => opts.movie_record = Some(PathBuf::from(value()?)),
+            "--movie-play" if run || command == Command::Search => opts.movie_play = Some(PathBuf::from(value()?)),
+            "--profile" if frames => opts.profile = Some(PathBuf::from(value()?)),
+            "--folded" if frames => opts.folded = Some(PathBuf::from(value()?)),
+            "--cheat" if frames || command == Command::Search => opts.cheats = Some(value()?),
+            "--save-cheats" if run => opts.save_cheats = true,
+            "--console" if run => opts.console = true,
+            "--filter" if command == Command::Search => opts.filters.push(parse_filter(&value()?)?),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+
+    opts.rom = rom.ok_or_else(|| "no ROM given".to_string())?;
+    if command == Command::Diff && opts.more_roms.len() < 2 {
+        return Err("diff expects ORIGINAL, MODIFIED and PATCH".to_string());
+    }
+    Ok(Some(opts))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,106 @@
+use super::*;
+
+fn parse(args: &str) -> ParseResult {
+    parse_args(args.split_whitespace().map(String::from))
+}
+
+fn opts(args: &str) -> Options {
+    match parse(args) {
+        Ok(Some(opts)) => opts,
+        Ok(None) => panic!("'{}' asked for help", args),
+        Err(e) => panic!("'{}' failed: {}", args, e),
+    }
+}
+
+// main exits with EXIT_USAGE on these
+fn err(args: &str) -> String {
+    match parse(args) {
+        Err(e) => e,
+        Ok(_) => panic!("'{}' was accepted", args),
+    }
+}
+
+#[test]
+fn subcommands() {
+    let commands = [
+        ("run", Command::Run),
+        ("disasm", Command::Disasm),
+        ("info", Command::Info),
+        ("trace", Command::Trace),
+        ("bench", Command::Bench),
+        ("test", Command::Test),
+        ("search", Command::Search),
+    ];
+    for &(name, command) in &commands {
+        let o = opts(&format!("{} res/PONG", name));
+        assert_eq!(o.command, command);
+        assert_eq!(o.rom, "res/PONG");
+        // only run opens a window
+        assert_eq!(o.headless, command != Command::Run);
+    }
+
+    let o = opts("diff a.ch8 b.ch8 fix.bps");
+    assert_eq!(o.command, Command::Diff);
+    assert_eq!((o.rom.as_str(), o.more_roms), ("a.ch8", vec!["b.ch8".to_string(), "fix.bps".to_string()]));
+}
+
+#[test]
+fn run_is_the_default() {
+    let o = opts("res/PONG --speed 12 --seed 3 --headless");
+    assert_eq!(o.command, Command::Run);
+    assert_eq!((o.speed, o.seed, o.headless), (Some(12), Some(3), true));
+}
+
+#[test]
+fn options_of_commands() {
+    let o = opts("test res/MAZE --frames 180 --expect 0x8ad153b4627fc875 --quirks vip,jump=1");
+    assert_eq!(o.frames, Some(180));
+    assert_eq!(o.expect, Some(0x8ad1_53b4_627f_c875));
+    assert_eq!(o.quirks.as_deref(), Some("vip,jump=1"));
+
+    let o = opts("trace res/MAZE --instructions 20");
+    assert_eq!(o.instructions, 20);
+
+    let o = opts("search res/BRIX --filter 60:dec --filter 10:same");
+    assert_eq!(o.filters.len(), 2);
+    assert_eq!(o.filters[0].0, 60);
+
+    assert!(opts("run res/BRIX --console").console);
+}
+
+// main exits with EXIT_OK after the help was printed
+#[test]
+fn help() {
+    assert!(matches!(parse("--help"), Ok(None)));
+    assert!(matches!(parse("-h"), Ok(None)));
+    assert!(matches!(parse("help trace"), Ok(None)));
+    assert!(matches!(parse("bench res/PONG --help"), Ok(None)));
+    assert_eq!(err("help fly"), "unknown command 'fly'");
+    assert!(Command::Trace.usage().contains("--instructions"));
+    assert!(!Command::Disasm.usage().contains("--speed"));
+}
+
+#[test]
+fn missing_values() {
+    assert_eq!(err(""), "no ROM given");
+    assert_eq!(err("trace"), "no ROM given");
+    assert_eq!(err("run res/PONG --speed"), "--speed expects a value");
+    assert_eq!(err("diff a.ch8 b.ch8"), "diff expects ORIGINAL, MODIFIED and PATCH");
+}
+
+#[test]
+fn invalid_values() {
+    assert_eq!(err("run res/PONG --speed fast"), "invalid value 'fast' for --speed");
+    assert_eq!(err("test res/PONG --expect xyz"), "invalid value 'xyz' for --expect, expected hex");
+    assert_eq!(err("run res/PONG --persistence bogus"), "invalid persistence frame count in 'bogus'");
+    assert_eq!(err("search res/PONG --filter dec"), "invalid frame 'dec' for --filter, expected FRAME:REL");
+    assert_eq!(err("run res/PONG res/BRIX"), "unexpected argument 'res/BRIX'");
+}
+
+#[test]
+fn options_of_other_commands_are_unknown() {
+    assert_eq!(err("disasm res/PONG --speed 10"), "unknown option --speed");
+    assert_eq!(err("test res/PONG --instructions 5"), "unknown option --instructions");
+    assert_eq!(err("run res/PONG --fly"), "unknown option --fly");
+    assert_eq!(err("search res/PONG --console"), "unknown option --console");
+}
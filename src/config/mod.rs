@@ -0,0 +1,209 @@
+extern crate toml;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Every setting is optional so that sections can be layered: per-ROM
+// sections override [defaults], command line flags override both.
+//
+//   [defaults]
+//   speed = 10
+//   quirks = "vip"
+//   palette = "ffcc00,202020"
+//   keymap = "W=5,S=8"
+//   persistence = "fade:4"
+//   memory = 4096
+//   stack = 16
+//   timing = "ipf"
+//
+//   [defaults.window]
+//   scale = 12
+//   fullscreen = false
+//
+//   [defaults.audio]
+//   enabled = true
+//   volume = 0.25
+//   tone = 440
+//
+//   [rom.PONG]                                          file or entry name
+//   speed = 12
+//
+//   [rom.b232ef880bd6060fb45fa6effed7edf0ae95670e]      SHA-1 of the image
+//   quirks = "jump=1"
+//   cheats = "02F3=05"
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WindowSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fullscreen: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AudioSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tone: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    // instructions per frame
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u32>,
+    // same format as --quirks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quirks: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<String>,
+    // same format as --keymap
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keymap: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistence: Option<String>,
+    // bytes of memory, same as --memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<usize>,
+    // return addresses, 0 for unlimited, same as --stack
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack: Option<usize>,
+    // ipf or vip, same as --timing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<String>,
+    // frozen addresses, same as --cheat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cheats: Option<String>,
+    #[serde(default, skip_serializing_if = "WindowSection::is_empty")]
+    pub window: WindowSection,
+    #[serde(default, skip_serializing_if = "AudioSection::is_empty")]
+    pub audio: AudioSection,
+}
+
+impl WindowSection {
+    fn is_empty(&self) -> bool {
+        *self == WindowSection::default()
+    }
+
+    fn merge(&mut self, over: &WindowSection) {
+        self.scale = over.scale.or(self.scale);
+        self.fullscreen = over.fullscreen.or(self.fullscreen);
+    }
+}
+
+impl AudioSection {
+    fn is_empty(&self) -> bool {
+        *self == AudioSection::default()
+    }
+
+    fn merge(&mut self, over: &AudioSection) {
+        self.enabled = over.enabled.or(self.enabled);
+        self.volume = over.volume.or(self.volume);
+        self.tone = over.tone.or(self.tone);
+    }
+}
+
+// Lists are applied item by item, so joining them keeps later items winning.
+fn join(base: &Option<String>, over: &Option<String>) -> Option<String> {
+    match (base, over) {
+        (Some(b), Some(o)) => Some(format!("{},{}", b, o)),
+        (b, o) => o.clone().or_else(|| b.clone()),
+    }
+}
+
+impl Settings {
+    // Settings in `over` win. quirks, keymap and cheats are lists, `over` is
+    // appended to them.
+    pub fn merge(&mut self, over: &Settings) {
+        self.speed = over.speed.or(self.speed);
+        self.quirks = join(&self.quirks, &over.quirks);
+        self.palette = over.palette.clone().or_else(|| self.palette.clone());
+        self.keymap = join(&self.keymap, &over.keymap);
+        self.persistence = over.persistence.clone().or_else(|| self.persistence.clone());
+        self.memory = over.memory.or(self.memory);
+        self.stack = over.stack.or(self.stack);
+        self.timing = over.timing.clone().or_else(|| self.timing.clone());
+        self.cheats = join(&self.cheats, &over.cheats);
+        self.window.merge(&over.window);
+        self.audio.merge(&over.audio);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub defaults: Settings,
+    // keyed by file name or SHA-1
+    #[serde(default)]
+    pub rom: BTreeMap<String, Settings>,
+}
+
+fn invalid(path: &Path, msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), msg))
+}
+
+impl Config {
+    // $XDG_CONFIG_HOME/chip8_opcode/config.toml, falling back to
+    // ~/.config when XDG_CONFIG_HOME is unset
+    pub fn default_path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+
+        Some(base.join("chip8_opcode").join("config.toml"))
+    }
+
+    // A missing file is an empty configuration.
+    pub fn load(path: &Path) -> io::Result<Config> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e),
+        };
+
+        toml::from_str(&text).map_err(|e| invalid(path, e.to_string()))
+    }
+
+    // Rewrites the whole file, comments are not kept.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(|e| invalid(path, e.to_string()))?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+
+    // The per-ROM settings; a section for the hash wins over one for the
+    // file name.
+    pub fn rom_settings(&self, name: &str, sha1: &str) -> Settings {
+        let mut settings = Settings::default();
+        if let Some(s) = self.rom.get(name) {
+            settings.merge(s);
+        }
+        if let Some(s) = self.rom.get(sha1) {
+            settings.merge(s);
+        }
+        settings
+    }
+
+    // The section that runtime changes are saved to: the existing one,
+    // else a new one named after the file.
+    pub fn rom_entry(&mut self, name: &str, sha1: &str) -> &mut Settings {
+        let key = if self.rom.contains_key(sha1) { sha1 } else { name };
+        self.rom.entry(key.to_string()).or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests;
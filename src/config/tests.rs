@@ -0,0 +1,109 @@
+use super::*;
+
+const SHA1: &str = "b232ef880bd6060fb45fa6effed7edf0ae95670e";
+
+fn sample() -> Config {
+    toml::from_str(r#"
+        [defaults]
+        speed = 10
+        quirks = "vip"
+        keymap = "W=5"
+        memory = 4096
+
+        [defaults.window]
+        scale = 12
+        fullscreen = true
+
+        [rom.PONG]
+        speed = 12
+        quirks = "jump=1"
+        persistence = "fade:4"
+        timing = "vip"
+
+        [rom.PONG.window]
+        scale = 8
+
+        [rom.b232ef880bd6060fb45fa6effed7edf0ae95670e]
+        speed = 14
+        quirks = "shift=0"
+        cheats = "02F3=05"
+
+        [rom.BRIX]
+        palette = "ffcc00,202020"
+    "#).unwrap()
+}
+
+// [defaults], then the section of the name, then the one of the hash
+fn layered(config: &Config, name: &str, sha1: &str) -> Settings {
+    let mut settings = config.defaults.clone();
+    settings.merge(&config.rom_settings(name, sha1));
+    settings
+}
+
+#[test]
+fn hash_wins_over_name_over_defaults() {
+    let config = sample();
+    let s = layered(&config, "PONG", SHA1);
+    assert_eq!(s.speed, Some(14));
+    assert_eq!(s.quirks.as_deref(), Some("vip,jump=1,shift=0"));
+    assert_eq!(s.persistence.as_deref(), Some("fade:4"));
+    assert_eq!(s.timing.as_deref(), Some("vip"));
+    assert_eq!(s.keymap.as_deref(), Some("W=5"));
+    assert_eq!(s.memory, Some(4096));
+    assert_eq!(s.cheats.as_deref(), Some("02F3=05"));
+    // sections are merged setting by setting
+    assert_eq!(s.window, WindowSection { scale: Some(8), fullscreen: Some(true) });
+
+    let s = layered(&config, "PONG", "0000");
+    assert_eq!(s.speed, Some(12));
+    assert_eq!(s.quirks.as_deref(), Some("vip,jump=1"));
+    assert_eq!(s.cheats, None);
+
+    // no section of its own, only [defaults] and what is built in
+    let s = layered(&config, "TETRIS", "0000");
+    assert_eq!(s.speed, Some(10));
+    assert_eq!(s.persistence, None);
+    assert_eq!(s.timing, None);
+    assert_eq!(s.palette, None);
+}
+
+// command line flags come last, as one more layer
+#[test]
+fn command_line_wins() {
+    let mut s = layered(&sample(), "PONG", SHA1);
+    s.merge(&Settings { speed: Some(20), quirks: Some("jump=0".to_string()), ..Settings::default() });
+    assert_eq!(s.speed, Some(20));
+    assert_eq!(s.quirks.as_deref(), Some("vip,jump=1,shift=0,jump=0"));
+    assert_eq!(s.persistence.as_deref(), Some("fade:4"));
+    assert_eq!(s.timing.as_deref(), Some("vip"));
+}
+
+#[test]
+fn unknown_settings_are_errors() {
+    assert!(toml::from_str::<Config>("[defaults]\nsped = 10\n").is_err());
+    assert!(toml::from_str::<Config>("[rom.PONG.window]\nzoom = 2\n").is_err());
+}
+
+// A speed changed at runtime goes to the hash's section if there is one,
+// every other section is written back unchanged.
+#[test]
+fn saving_the_speed_keeps_the_rest() {
+    let path = env::temp_dir().join(format!("chip8-config-test-{}.toml", ::std::process::id()));
+    let mut config = sample();
+    config.rom_entry("PONG", SHA1).speed = Some(9);
+    config.rom_entry("TETRIS", "0000").speed = Some(5);
+    config.save(&path).unwrap();
+    let saved = Config::load(&path);
+    fs::remove_file(&path).unwrap();
+
+    let mut expected = sample();
+    expected.rom.get_mut(SHA1).unwrap().speed = Some(9);
+    expected.rom.insert("TETRIS".to_string(), Settings { speed: Some(5), ..Settings::default() });
+    assert_eq!(saved.unwrap(), expected);
+}
+
+#[test]
+fn missing_file_is_empty() {
+    let config = Config::load(Path::new("/nonexistent/chip8_opcode/config.toml")).unwrap();
+    assert_eq!(config, Config::default());
+}
@@ -0,0 +1,94 @@
+use cheat::*;
+use memory::*;
+
+// The console of `run --console`: commands typed while the game runs, read
+// between frames. RAM search narrows the candidates down with every filter,
+// e.g. `dec` after a life was lost, and the addresses found can be frozen
+// right away.
+
+// addresses listed at most
+pub const LISTED: usize = 64;
+
+pub const HELP: &str = "\
+same, changed, inc, dec  keep the addresses whose value relates so to the last filter
+VALUE                    keep the addresses that hold VALUE, decimal or hex with 0x
+list                     print the addresses left with their values
+reset                    start over with every address
+freeze ADDR=VALUE,...    freeze addresses, in hex
+unfreeze ADDR            let an address go again
+cheats                   print the frozen addresses
+help                     print this
+";
+
+// The candidates left and their values as of the last filter.
+pub fn search_listing(search: &RamSearch) -> String {
+    let candidates = search.candidates();
+    let mut out = String::new();
+    for &addr in candidates.iter().take(LISTED) {
+        out.push_str(&format!("{:04X}  {:02X}\n", addr, search.value(addr)));
+    }
+    if candidates.len() > LISTED {
+        out.push_str(&format!("... and {} more\n", candidates.len() - LISTED));
+    }
+    out
+}
+
+pub struct Console {
+    search: RamSearch,
+}
+
+impl Console {
+    // Every address is a candidate, compared with `mem` as it is now.
+    pub fn new(mem: &mut dyn CpuMemory) -> Self {
+        Console { search: RamSearch::new(mem) }
+    }
+
+    pub fn search(&self) -> &RamSearch {
+        &self.search
+    }
+
+    // Runs a command on the memory and the cheats of the game, returns
+    // what to print.
+    pub fn command(&mut self, line: &str, mem: &mut dyn CpuMemory,
+                   cheats: &mut Cheats) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let (cmd, arg) = match words.next() {
+            Some(cmd) => (cmd, words.next()),
+            None => return Ok(String::new()),
+        };
+        if words.next().is_some() {
+            return Err(format!("too many arguments for '{}'", cmd));
+        }
+
+        let out = match (cmd, arg) {
+            ("help", None) => HELP.to_string(),
+            ("list", None) => search_listing(&self.search),
+            ("reset", None) => {
+                self.search = RamSearch::new(mem);
+                format!("{} addresses\n", self.search.candidates().len())
+            },
+            ("freeze", Some(list)) => {
+                cheats.apply(list)?;
+                format!("cheats {}\n", cheats)
+            },
+            ("unfreeze", Some(addr)) => {
+                let addr = u16::from_str_radix(addr, 16)
+                    .map_err(|_| format!("invalid address '{}', expected hex", addr))?;
+                cheats.unfreeze(addr);
+                format!("cheats {}\n", cheats)
+            },
+            ("cheats", None) if cheats.is_empty() => "no cheats\n".to_string(),
+            // the option that keeps them for the next run
+            ("cheats", None) => format!("cheats {}, keep them with --cheat {} --save-cheats\n", cheats, cheats),
+            (_, None) => match cmd.parse::<Relation>() {
+                Ok(rel) => format!("{} addresses left\n", self.search.filter(mem, rel)),
+                Err(_) => return Err(format!("unknown command '{}', see help", cmd)),
+            },
+            (_, Some(_)) => return Err(format!("unknown command '{}', see help", line.trim())),
+        };
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests;
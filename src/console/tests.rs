@@ -0,0 +1,62 @@
+use super::*;
+
+fn run(console: &mut Console, mem: &mut Memory, cheats: &mut Cheats, line: &str) -> String {
+    console.command(line, mem, cheats).unwrap()
+}
+
+#[test]
+fn filters_narrow_down_between_frames() {
+    let mut mem = Memory::new();
+    let mut cheats = Cheats::new();
+    mem.set_u8(0x300, 3).unwrap();
+    let mut console = Console::new(&mut mem);
+
+    // a life lost while a timer ticks
+    mem.set_u8(0x300, 2).unwrap();
+    mem.set_u8(0x302, 1).unwrap();
+    assert_eq!(run(&mut console, &mut mem, &mut cheats, "changed"), "2 addresses left\n");
+    mem.set_u8(0x302, 5).unwrap();
+    assert_eq!(run(&mut console, &mut mem, &mut cheats, " 2 "), "1 addresses left\n");
+    assert_eq!(run(&mut console, &mut mem, &mut cheats, "list"), "0300  02\n");
+    assert_eq!(console.search().candidates(), &[0x300]);
+
+    assert_eq!(run(&mut console, &mut mem, &mut cheats, "reset"), format!("{} addresses\n", MEM_SIZE));
+    assert_eq!(run(&mut console, &mut mem, &mut cheats, ""), "");
+}
+
+#[test]
+fn long_lists_are_cut() {
+    let mut mem = Memory::new();
+    let console = Console::new(&mut mem);
+    let listing = search_listing(console.search());
+    assert_eq!(listing.lines().count(), LISTED + 1);
+    assert!(listing.ends_with(&format!("... and {} more\n", MEM_SIZE - LISTED)));
+}
+
+#[test]
+fn cheats_are_changed() {
+    let mut mem = Memory::new();
+    let mut cheats = Cheats::new();
+    let mut console = Console::new(&mut mem);
+    assert_eq!(run(&mut console, &mut mem, &mut cheats, "cheats"), "no cheats\n");
+    assert_eq!(run(&mut console, &mut mem, &mut cheats, "freeze 300=09,2F3=1"), "cheats 0300=09,02F3=01\n");
+    assert_eq!(run(&mut console, &mut mem, &mut cheats, "unfreeze 0300"), "cheats 02F3=01\n");
+    assert_eq!(run(&mut console, &mut mem, &mut cheats, "cheats"),
+               "cheats 02F3=01, keep them with --cheat 02F3=01 --save-cheats\n");
+    assert_eq!(cheats.to_string(), "02F3=01");
+}
+
+#[test]
+fn bad_commands() {
+    let mut mem = Memory::new();
+    let mut cheats = Cheats::new();
+    let mut console = Console::new(&mut mem);
+    let mut err = |line: &str| console.command(line, &mut mem, &mut cheats).unwrap_err();
+    assert_eq!(err("less"), "unknown command 'less', see help");
+    assert_eq!(err("300"), "unknown command '300', see help");
+    assert_eq!(err("list all"), "unknown command 'list all', see help");
+    assert_eq!(err("freeze 300"), "invalid cheat '300', expected ADDR=VALUE in hex");
+    assert_eq!(err("unfreeze x"), "invalid address 'x', expected hex");
+    assert_eq!(err("freeze 1=2 3=4"), "too many arguments for 'freeze'");
+    assert!(cheats.is_empty());
+}
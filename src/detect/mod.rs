@@ -0,0 +1,193 @@
+use memory::*;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Guesses the platform a ROM was written for from the opcodes only some of
+// them have. The code is followed from the start like the CPU would: jumps,
+// calls and both ways of every skip, so sprites and other data are only
+// read as instructions if the code runs into them. BNNN jumps cannot be
+// followed.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Platform {
+    Chip8,
+    Schip,
+    XoChip,
+}
+
+impl Platform {
+    // the quirks preset of the platform
+    pub fn preset(self) -> &'static str {
+        match self {
+            Platform::Chip8 => "vip",
+            Platform::Schip => "schip",
+            Platform::XoChip => "xochip",
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Platform::Chip8 => write!(f, "CHIP-8"),
+            Platform::Schip => write!(f, "SUPER-CHIP"),
+            Platform::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+// An opcode of one platform, where it was first seen and how often.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub opcode: &'static str,
+    pub what: &'static str,
+    pub platform: Platform,
+    pub addr: u16,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Detection {
+    // in the order of their first address
+    pub findings: Vec<Finding>,
+    // bytes reached as code
+    pub code_bytes: usize,
+}
+
+impl Detection {
+    // the newest platform with an opcode in the ROM, CHIP-8 without any
+    pub fn platform(&self) -> Platform {
+        self.findings.iter().map(|f| f.platform).max().unwrap_or(Platform::Chip8)
+    }
+
+    // 0NNN calls of 1802 machine code, only the VIP runs them
+    pub fn has_machine_code(&self) -> bool {
+        self.findings.iter().any(|f| f.opcode == "0NNN")
+    }
+
+    // command line options to run the ROM as its platform
+    pub fn suggestion(&self) -> String {
+        let platform = self.platform();
+        let mut s = format!("--quirks {}", platform.preset());
+        match platform {
+            Platform::Chip8 if self.has_machine_code() => s.push_str(" --cpu cdp1802"),
+            Platform::XoChip => s.push_str(&format!(" --memory {}", MAX_MEM_SIZE)),
+            _ => {},
+        }
+        s
+    }
+}
+
+// The opcodes not every platform has, as (pattern, description, platform).
+fn classify(instruction: u16) -> Option<(&'static str, &'static str, Platform)> {
+    let n = instruction & 0xF;
+    let nn = instruction & 0xFF;
+    let found = match instruction >> 12 {
+        0x0 => match instruction {
+            0x0000 | 0x00E0 | 0x00EE => return None,
+            0x00FE => ("00FE", "low resolution", Platform::Schip),
+            0x00FF => ("00FF", "high resolution", Platform::Schip),
+            0x00FB => ("00FB", "scroll right", Platform::Schip),
+            0x00FC => ("00FC", "scroll left", Platform::Schip),
+            0x00FD => ("00FD", "exit", Platform::Schip),
+            0x00C1..=0x00CF => ("00CN", "scroll down", Platform::Schip),
+            0x00D1..=0x00DF => ("00DN", "scroll up", Platform::XoChip),
+            _ => ("0NNN", "machine code call", Platform::Chip8),
+        },
+        0x5 if n == 2 => ("5XY2", "store a range of registers", Platform::XoChip),
+        0x5 if n == 3 => ("5XY3", "load a range of registers", Platform::XoChip),
+        0xD if n == 0 => ("DXY0", "16x16 sprite", Platform::Schip),
+        0xF => match nn {
+            0x00 if instruction == 0xF000 => ("F000", "16 bit I", Platform::XoChip),
+            0x01 => ("FN01", "bit planes", Platform::XoChip),
+            0x02 if instruction == 0xF002 => ("F002", "audio pattern", Platform::XoChip),
+            0x30 => ("FX30", "big font", Platform::Schip),
+            0x3A => ("FX3A", "pitch", Platform::XoChip),
+            0x75 => ("FX75", "store flags", Platform::Schip),
+            0x85 => ("FX85", "load flags", Platform::Schip),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(found)
+}
+
+fn is_skip(instruction: u16) -> bool {
+    match instruction >> 12 {
+        0x3 | 0x4 => true,
+        0x5 | 0x9 => instruction & 0xF == 0,
+        0xE => matches!(instruction & 0xFF, 0x9E | 0xA1),
+        _ => false,
+    }
+}
+
+pub fn detect(exe: &[u8]) -> Detection {
+    let start = EXE_START_ADDR;
+    let end = start + exe.len();
+    let word = |addr: usize| -> Option<u16> {
+        if addr < start || addr + 2 > end {
+            return None;
+        }
+        let i = addr - start;
+        Some((exe[i] as u16) << 8 | exe[i + 1] as u16)
+    };
+    // an instruction's length, F000 NNNN is the only long one
+    let length = |addr: usize| if word(addr) == Some(0xF000) { 4 } else { 2 };
+
+    let mut seen = vec![false; exe.len()];
+    let mut found: BTreeMap<&'static str, Finding> = BTreeMap::new();
+    let mut code_bytes = 0;
+    let mut todo = vec![start];
+
+    while let Some(addr) = todo.pop() {
+        let instruction = match word(addr) {
+            Some(i) if !seen[addr - start] => i,
+            _ => continue,
+        };
+        seen[addr - start] = true;
+        code_bytes += length(addr);
+
+        if let Some((opcode, what, platform)) = classify(instruction) {
+            found.entry(opcode)
+                .and_modify(|f| f.count += 1)
+                .or_insert(Finding { opcode, what, platform, addr: addr as u16, count: 1 });
+        }
+
+        let nnn = (instruction & 0xFFF) as usize;
+        match instruction >> 12 {
+            _ if instruction == 0x00EE || instruction == 0x00FD => {},
+            0x1 => todo.push(nnn),
+            0x2 => {
+                todo.push(nnn);
+                todo.push(addr + 2);
+            },
+            0xB => {},
+            _ if is_skip(instruction) => {
+                todo.push(addr + 2);
+                todo.push(addr + 2 + length(addr + 2));
+            },
+            _ => todo.push(addr + length(addr)),
+        }
+    }
+
+    let mut findings: Vec<Finding> = found.into_values().collect();
+    findings.sort_by_key(|f| f.addr);
+    Detection { findings, code_bytes }
+}
+
+// Hex dump of a ROM loaded at `start`, 16 bytes a line as big-endian
+// words like the CPU fetches them, an odd last byte on its own.
+pub fn hexdump(exe: &[u8], start: u16) -> String {
+    let mut out = String::new();
+    for (i, line) in exe.chunks(16).enumerate() {
+        let words: Vec<String> = line.chunks(2)
+            .map(|w| w.iter().map(|b| format!("{:02X}", b)).collect())
+            .collect();
+        out.push_str(&format!("{:04X}  {}\n", start as usize + i * 16, words.join(" ")));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests;
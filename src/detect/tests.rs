@@ -0,0 +1,74 @@
+use super::*;
+
+fn rom(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_be_bytes()).collect()
+}
+
+fn opcodes(d: &Detection) -> Vec<&'static str> {
+    d.findings.iter().map(|f| f.opcode).collect()
+}
+
+#[test]
+fn plain_chip8() {
+    let d = detect(include_bytes!("../../res/BRIX"));
+    assert_eq!(d.findings, vec![]);
+    assert_eq!(d.platform(), Platform::Chip8);
+    assert_eq!(d.suggestion(), "--quirks vip");
+}
+
+#[test]
+fn schip_opcodes() {
+    // 00FF, a 16x16 sprite, then a loop
+    let d = detect(&rom(&[0x00FF, 0xD120, 0xD120, 0x1206]));
+    assert_eq!(opcodes(&d), vec!["00FF", "DXY0"]);
+    assert_eq!(d.findings[1].addr, 0x202);
+    assert_eq!(d.findings[1].count, 2);
+    assert_eq!(d.platform(), Platform::Schip);
+    assert_eq!(d.suggestion(), "--quirks schip");
+}
+
+#[test]
+fn xochip_wins_over_schip() {
+    // F000 NNNN is 4 bytes long, the NNNN is not an instruction
+    let d = detect(&rom(&[0xF000, 0x00FF, 0x00FE, 0x1206]));
+    assert_eq!(opcodes(&d), vec!["F000", "00FE"]);
+    assert_eq!(d.platform(), Platform::XoChip);
+    assert_eq!(d.suggestion(), "--quirks xochip --memory 65536");
+}
+
+#[test]
+fn machine_code_calls() {
+    let d = detect(&rom(&[0x0280, 0x1202]));
+    assert_eq!(opcodes(&d), vec!["0NNN"]);
+    assert_eq!(d.platform(), Platform::Chip8);
+    assert_eq!(d.suggestion(), "--quirks vip --cpu cdp1802");
+}
+
+#[test]
+fn data_is_not_code() {
+    // the sprite after the loop is never run
+    let d = detect(&rom(&[0xA204, 0x1202, 0x00FF, 0xD120]));
+    assert_eq!(d.findings, vec![]);
+    assert_eq!(d.code_bytes, 4);
+}
+
+#[test]
+fn both_ways_of_a_skip_and_calls() {
+    // the skip runs into a call or over it, the subroutine has the opcode
+    let d = detect(&rom(&[0x3000, 0x220A, 0x1204, 0x0000, 0x0000, 0xF075, 0x00EE]));
+    assert_eq!(opcodes(&d), vec!["FX75"]);
+    assert_eq!(d.findings[0].addr, 0x20A);
+
+    // a skip over F000 NNNN skips all 4 bytes
+    let d = detect(&rom(&[0x3000, 0xF000, 0x00FF, 0x1206]));
+    assert_eq!(opcodes(&d), vec!["F000"]);
+}
+
+#[test]
+fn hexdump_is_big_endian() {
+    let exe = [0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C, 0x61, 0x08, 0x62, 0x1F, 0x63, 0x00, 0x64, 0x02,
+               0x65, 0x05, 0xD3];
+    assert_eq!(hexdump(&exe, 0x200),
+               "0200  00E0 A22A 600C 6108 621F 6300 6402 6505\n\
+                0210  D3\n");
+}
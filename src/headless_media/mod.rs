@@ -0,0 +1,91 @@
+use media_if::*;
+use memory::*;
+use capture::*;
+
+// Media backend without a window: nothing is drawn and no key is ever
+// pressed. Runs until `max_frames` emulated frames have passed, if given.
+pub struct Headless {
+    keypad: [u8; 16],
+    frames: u64,
+    max_frames: Option<u64>,
+    capture: Capture,
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Headless {
+    pub fn new() -> Self {
+        Headless {
+            keypad: [0; 16],
+            frames: 0,
+            max_frames: None,
+            capture: Capture::default(),
+        }
+    }
+
+    pub fn set_max_frames(&mut self, frames: u64) {
+        self.max_frames = Some(frames);
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn capture(&mut self) -> &mut Capture {
+        &mut self.capture
+    }
+}
+
+impl VideoSink for Headless {
+    fn draw_display(&mut self, _buf: Screen) -> Option<u8> {
+        Some(0)
+    }
+
+    fn clear_display(&mut self) -> Option<u8> {
+        Some(0)
+    }
+
+    fn present_display(&mut self) -> Option<u8> {
+        Some(0)
+    }
+
+    fn end_frame(&mut self, buf: Screen) -> Option<u8> {
+        self.capture.frame(buf);
+        self.frames += 1;
+        Some(0)
+    }
+}
+
+impl AudioSink for Headless {
+    fn set_sound(&mut self, _on: bool) {}
+}
+
+impl InputSource for Headless {
+    fn poll_input(&mut self) {}
+
+    fn is_key_pressed(&mut self, key: u8) -> bool {
+        self.keypad[key as usize] == 1
+    }
+
+    fn get_pressed_key(&self) -> Option<&u8> {
+        self.keypad.iter().find(|&&s| s == 1)
+    }
+
+    fn poll_key_event(&mut self) -> Option<KeyEvent> {
+        None
+    }
+}
+
+// quits after `max_frames`
+impl ControlSource for Headless {
+    fn poll_control(&mut self) -> Option<Control> {
+        match self.max_frames {
+            Some(m) if self.frames >= m => Some(Control::Quit),
+            _ => None,
+        }
+    }
+}
@@ -1,7 +1,34 @@
-pub mod cpu;
-pub mod cpu_ops;
-pub mod memory;
-pub mod sprites;
-pub mod utils;
+#[macro_use]
+extern crate serde_derive;
+extern crate chip8_core;
+
+// the core, usable without std
+pub use chip8_core::cpu;
+pub use chip8_core::machine;
+pub use chip8_core::cpu_ops;
+pub use chip8_core::memory;
+pub use chip8_core::stack;
+pub use chip8_core::timing;
+pub use chip8_core::sprites;
+pub use chip8_core::utils;
+pub use chip8_core::media_if;
+pub use chip8_core::quirks;
+pub use chip8_core::disasm;
+pub use chip8_core::profile;
+pub use chip8_core::cheat;
+
+pub mod cdp1802;
+pub mod vip;
+#[cfg(feature = "sdl2")]
 pub mod sdl2_media;
-pub mod media_if;
+pub mod phosphor;
+pub mod capture;
+pub mod headless_media;
+pub mod movie;
+pub mod romdb;
+pub mod romfile;
+pub mod patch;
+pub mod detect;
+pub mod config;
+pub mod console;
+pub mod testing;
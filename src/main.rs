@@ -1,76 +1,963 @@
-mod memory;
-mod sprites;
-mod utils;
-mod media_if;
-mod sdl2_media;
-mod cpu_ops;
-mod cpu;
-
-use cpu_ops::*;
-use cpu::*;
-use memory::*;
-use sprites::*;
-use media_if::*;
-use sdl2_media::*;
+extern crate chip8_opcode;
+#[cfg(feature = "rand")]
+extern crate rand;
 
-use std::io::prelude::*;
+mod cli;
+
+use chip8_opcode::cpu_ops::*;
+use chip8_opcode::cpu::*;
+use chip8_opcode::machine::*;
+use chip8_opcode::vip::*;
+use chip8_opcode::memory::*;
+use chip8_opcode::stack::*;
+use chip8_opcode::timing::*;
+use chip8_opcode::sprites::*;
+use chip8_opcode::media_if::*;
+#[cfg(feature = "sdl2")]
+use chip8_opcode::sdl2_media::*;
+use chip8_opcode::capture::*;
+use chip8_opcode::headless_media::*;
+use chip8_opcode::quirks::*;
+use chip8_opcode::movie::*;
+use chip8_opcode::utils::*;
+use chip8_opcode::romdb::*;
+use chip8_opcode::romfile::*;
+use chip8_opcode::patch::*;
+use chip8_opcode::detect::*;
+use chip8_opcode::disasm::*;
+use chip8_opcode::profile::*;
+use chip8_opcode::cheat::*;
+use chip8_opcode::config::*;
+use chip8_opcode::phosphor::*;
+use chip8_opcode::console::*;
+
+use cli::*;
 
 use std::env;
-use std::fs::File;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Instant;
+
+// what the emulated machine needs to reproduce a run
+struct RunConfig {
+    ipf: u32,
+    seed: u64,
+    quirks: Quirks,
+    mem_size: usize,
+    // None for unlimited
+    stack_depth: Option<usize>,
+    timing: Timing,
+    // run on the CDP1802 core instead of interpreting CHIP-8
+    vip: Option<VipImages>,
+    profile: Option<ProfileOutput>,
+    cheats: Cheats,
+    // read commands from stdin, see run_console
+    console: bool,
+    rom_hash: u64,
+    throttle: bool,
+}
+
+// The monitor ROM and the CHIP-8 interpreter of the COSMAC VIP
+struct VipImages {
+    rom: Vec<u8>,
+    interpreter: Vec<u8>,
+}
+
+// where the profiler writes when the run ends
+struct ProfileOutput {
+    report: Option<PathBuf>,
+    folded: Option<PathBuf>,
+}
+
+fn write_profile(out: &ProfileOutput, profile: &Profile) -> Result<(), String> {
+    if let Some(ref path) = out.report {
+        let report = match path.extension() {
+            Some(ext) if ext == "json" => profile.to_json(),
+            _ => profile.to_text(),
+        };
+        if path.as_os_str() == "-" {
+            print!("{}", report);
+        } else {
+            fs::write(path, report).map_err(|e| format!("cannot save {}: {}", path.display(), e))?;
+        }
+    }
+    if let Some(ref path) = out.folded {
+        fs::write(path, profile.to_folded()).map_err(|e| format!("cannot save {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn setup_capture(capture: &mut Capture, opts: &Options,
+                 scale: u32, palette: Palette) -> Result<(), String> {
+    *capture = Capture::new(scale as usize, palette);
+    if let Some(ref path) = opts.record {
+        capture.start_recording(path)
+            .map_err(|e| format!("cannot record to {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+use std::time::Duration;
+
+// `c` is the number of instructions executed per 60 Hz frame with ipf
+// timing, see run_frame. `throttle` keeps the frames at real time speed.
+// `between` looks at the machine before every frame, also while paused.
+// Returns `c` as changed by the user.
+fn execute_vm<P: PipeLine>(pl: &mut P, mut c: u32, timing: Timing, throttle: bool,
+                           log: &mut Option<StateLog>,
+                           between: &mut dyn FnMut(&mut P)) -> Result<u32, Fault> {
+    let mut frame = 0u64;
+    let mut budget = pl.cycles();
+    let mut paused = false;
+
+    // the speed changes before the frame, as in Machine::run
+    while let Some(delta) = poll_frontend(pl, &mut paused) {
+        if delta != 0 && timing == Timing::Ipf {
+            c = change_speed(c, delta);
+            println!("speed {} instructions per frame", c);
+        }
+        between(pl);
+
+        if !paused {
+            run_frame(pl, c, timing, &mut budget)?;
+            frame += 1;
+
+            if let Some(ref mut log) = *log {
+                if log.wants_hash(frame) {
+                    log.on_frame(frame, pl.state_hash());
+                }
+            }
+        }
+
+        if throttle {
+            ::std::thread::sleep(Duration::new(0, 16666666));
+        }
+    }
 
-fn load_game(path: String) -> Vec<u8> {
-    let mut f = File::open(path).unwrap();
-    let metadata = f.metadata().unwrap();
-    let fsize = metadata.len() as usize;
-    let mut exe = Vec::with_capacity(fsize);
+    Ok(c)
+}
+
+fn execute_vip(vip: &mut Vip, throttle: bool, log: &mut Option<StateLog>) -> Result<(), Fault> {
+    let mut frame = 0u64;
+    let mut paused = false;
+
+    'frames: loop {
+        vip.poll_input();
+        // the VIP runs at its clock, there is no speed to change
+        while let Some(control) = vip.poll_control() {
+            match control {
+                Control::Quit => break 'frames,
+                Control::Pause => paused = !paused,
+                Control::Reset => vip.reset(),
+                Control::Speed(_) => {},
+            }
+        }
+
+        if !paused {
+            vip.run_frame()?;
+            frame += 1;
+
+            if let Some(ref mut log) = *log {
+                if log.wants_hash(frame) {
+                    log.on_frame(frame, vip.state_hash());
+                }
+            }
+        }
+
+        if throttle {
+            ::std::thread::sleep(Duration::new(0, 16666666));
+        }
+    }
+
+    Ok(())
+}
+
+// the CPU on the memory, display and frontend of a run
+type Emulator<'a> = CPU<&'a mut Memory, &'a mut Display, &'a mut dyn MediaIf>;
+
+fn new_cpu<'a>(mem: &'a mut Memory, display: &'a mut Display,
+               media_if: &'a mut dyn MediaIf, cfg: &RunConfig) -> Emulator<'a> {
+    let mut emulator = CPU::new(mem, display, media_if);
+    emulator.set_seed(cfg.seed);
+    emulator.set_quirks(cfg.quirks);
+    emulator.set_stack_depth(cfg.stack_depth);
+    emulator.set_cheats(cfg.cheats.clone());
+    emulator
+}
 
-    f.read_to_end(&mut exe).unwrap();
+// return addresses printed at most
+const STACK_LISTED: usize = 32;
 
-    let u16_bus = unsafe {
-        std::mem::transmute::<&[u8], &[u16]>(exe.as_slice())
+// The fault with the return addresses on the stack, innermost first.
+fn stopped(fault: Fault, cpu: &Emulator) -> String {
+    let mut msg = format!("emulation stopped: {}", fault);
+    let addrs = cpu.stack().return_addresses();
+    if !addrs.is_empty() {
+        msg.push_str("\ncall stack:");
+        for addr in addrs.iter().rev().take(STACK_LISTED) {
+            msg.push_str(&format!(" {:04X}", addr));
+        }
+        if addrs.len() > STACK_LISTED {
+            msg.push_str(&format!(" ... and {} more", addrs.len() - STACK_LISTED));
+        }
+    }
+    msg
+}
+
+// Returns the speed at the end of the run.
+fn run(mem: &mut Memory, display: &mut Display, media_if: &mut dyn MediaIf,
+       cfg: &RunConfig, log: &mut Option<StateLog>) -> Result<u32, String> {
+    if let Some(ref images) = cfg.vip {
+        let ram = (0..mem.size()).map(|a| mem.get_u8(a as u16).unwrap()).collect();
+        let mut vip = Vip::new(ram, display, media_if);
+        vip.load_rom(&images.rom).load_interpreter(&images.interpreter);
+        vip.set_cheats(cfg.cheats.clone());
+        execute_vip(&mut vip, cfg.throttle, log).map_err(|f| format!("emulation stopped: {}", f))?;
+        return Ok(cfg.ipf);
+    }
+
+    let mut emulator = new_cpu(mem, display, media_if, cfg);
+    emulator.set_profiling(cfg.profile.is_some());
+    let mut console = if cfg.console {
+        println!("console ready, type help for the commands");
+        Some((Console::new(emulator.memory()), stdin_lines()))
+    } else {
+        None
     };
-    let mut del = 0u16;
-    for i in 0..fsize/2 {
-        if del % 16 == 0 {
-            println!("");
+    let mut between = |cpu: &mut Emulator| {
+        if let Some((ref mut console, ref lines)) = console {
+            for line in lines.try_iter() {
+                run_console(console, &line, cpu);
+            }
         }
+    };
+    let result = execute_vm(&mut emulator, cfg.ipf, cfg.timing, cfg.throttle, log, &mut between)
+        .map_err(|f| stopped(f, &emulator));
 
-        print!("{:04X}  ", u16_bus[i]);
-        del += 1;    
+    // also after a fault, the profile shows how the program got there
+    if let (Some(ref out), Some(profile)) = (&cfg.profile, emulator.take_profile()) {
+        write_profile(out, &profile)?;
     }
+    result
+}
 
-    exe
+// Lines typed on stdin, read on a thread of their own so that the game
+// runs on while nothing is typed.
+fn stdin_lines() -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
 }
 
-use std::time::Duration;
-fn execute_vm<P: PipeLine>(pl: &mut P, _c: u32) -> Option<()> {
+// A console command on the game's memory; freezing takes effect at once.
+fn run_console(console: &mut Console, line: &str, cpu: &mut Emulator) {
+    let mut cheats = cpu.cheats().clone();
+    match console.command(line, cpu.memory(), &mut cheats) {
+        Ok(out) => print!("{}", out),
+        Err(e) => println!("error: {}", e),
+    }
+    if cheats != *cpu.cheats() {
+        cpu.set_cheats(cheats);
+    }
+}
 
-    while pl.process_events() {
-        let instruction = pl.fetch().unwrap();
-        let (id, arg) = pl.decode(instruction).unwrap();
-        let _ = pl.execute(id, arg).unwrap();
-        pl.update_timers();
-        ::std::thread::sleep(Duration::new(0, 3000000));
+// Runs the game, recording or replaying an input movie if asked to.
+fn run_session(mem: &mut Memory, display: &mut Display, backend: &mut dyn MediaIf,
+               cfg: &RunConfig, opts: &Options, movie: Option<Movie>) -> Result<u32, String> {
+    if let Some(movie) = movie {
+        let mut player = MoviePlayer::new(backend, movie.keys);
+        let mut log = Some(StateLog::verifying(movie.hashes));
+        run(mem, display, &mut player, cfg, &mut log)?;
+
+        let desyncs = log.map(|l| l.desyncs()).unwrap_or(0);
+        println!("movie playback finished, {} desynced state hashes", desyncs);
+        Ok(cfg.ipf)
+    } else if let Some(ref path) = opts.movie_record {
+        let mut recorder = MovieRecorder::new(backend);
+        let mut log = Some(StateLog::recording());
+        // a movie that ends in a fault is kept, replaying it reproduces the fault
+        let result = run(mem, display, &mut recorder, cfg, &mut log);
+
+        let mut movie = Movie::new(cfg.rom_hash, cfg.seed, cfg.ipf, cfg.quirks);
+        movie.timing = cfg.timing;
+        movie.mem_size = cfg.mem_size;
+        movie.stack_depth = cfg.stack_depth;
+        movie.keys = recorder.into_keys();
+        movie.hashes = log.map(|l| l.into_hashes()).unwrap_or_default();
+        movie.save(path).map_err(|e| format!("cannot save {}: {}", path.display(), e))?;
+        println!("movie with {} frames saved to {}", movie.keys.len(), path.display());
+        result
+    } else {
+        run(mem, display, backend, cfg, &mut None)
     }
+}
+
 
-    Some(())
+// The first of the patches next to the ROM that exists, an archive entry
+// looks next to the archive.
+fn find_patch(spec: &RomSpec) -> Option<PathBuf> {
+    let rom = match *spec {
+        RomSpec::File(ref path) => path.clone(),
+        RomSpec::Entry(ref archive, _) => archive.with_file_name(spec.name()),
+        RomSpec::Archive(_) => return None,
+    };
+    patch_candidates(&rom).into_iter().find(|p| p.is_file())
 }
 
-fn main() {
-    let path = env::args().nth(1).unwrap();
-    let c = env::args().nth(2).unwrap().parse::<u32>().unwrap();
-    let exe = load_game(path);
+// The ROM with the patch of --patch or the one found next to it applied,
+// and the image as it was before. The database knows the games by the
+// latter, a fix or translation does not change their settings.
+fn load(opts: &Options) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let spec = RomSpec::parse(&opts.rom);
+    let exe = load_rom(&spec).map_err(|e| format!("cannot load {}: {}", opts.rom, e))?;
 
-    let mut mem = Memory::new()
-        .load_sprites(SPRITES)
-        .load_exe(exe.as_slice())
-        .build();
+    let path = match opts.patch.as_deref() {
+        Some("none") => return Ok((exe.clone(), exe)),
+        Some(p) => PathBuf::from(p),
+        None => match find_patch(&spec) {
+            Some(p) => p,
+            None => return Ok((exe.clone(), exe)),
+        },
+    };
+    let patch = fs::read(&path).map_err(|e| format!("cannot load {}: {}", path.display(), e))?;
+    let patched = apply_patch(&exe, &patch).map_err(|e| format!("cannot apply {}: {}", path.display(), e))?;
+    // not on stdout, where disasm and info print
+    eprintln!("patch {} applied", path.display());
+    Ok((patched, exe))
+}
 
+// Looks the ROM up by SHA-1, or by the hash given with --db-match.
+// "--db-match none" skips the database.
+fn lookup_rom(exe: &[u8], opts: &Options) -> Result<Option<RomInfo>, String> {
+    let db = match opts.db_dir {
+        Some(ref dir) => RomDb::load(dir)
+            .map_err(|e| format!("cannot load the ROM database from {}: {}", dir.display(), e))?,
+        None => RomDb::bundled(),
+    };
+
+    Ok(match opts.db_match.as_deref() {
+        Some("none") => None,
+        Some(sha1) => db.lookup(sha1),
+        None => db.lookup_rom(exe),
+    })
+}
+
+// The configuration file and the sections of it that apply to one ROM.
+struct RomConfig {
+    config: Config,
+    path: Option<PathBuf>,
+    defaults: Settings,
+    rom: Settings,
+    name: String,
+    sha1: String,
+}
+
+impl RomConfig {
+    // [defaults] with the per-ROM sections on top
+    fn merged(&self) -> Settings {
+        let mut s = self.defaults.clone();
+        s.merge(&self.rom);
+        s
+    }
+}
+
+fn load_config(exe: &[u8], opts: &Options) -> Result<RomConfig, String> {
+    let path = if opts.no_config {
+        None
+    } else {
+        opts.config.clone().or_else(Config::default_path)
+    };
+
+    let config = match path {
+        Some(ref p) => Config::load(p).map_err(|e| format!("cannot load the configuration: {}", e))?,
+        None => Config::default(),
+    };
+
+    let name = RomSpec::parse(&opts.rom).name();
+    let sha1 = sha1_hex(exe);
+    Ok(RomConfig {
+        defaults: config.defaults.clone(),
+        rom: config.rom_settings(&name, &sha1),
+        config,
+        path,
+        name,
+        sha1,
+    })
+}
+
+fn config_err<E: ::std::fmt::Display>(e: E) -> String {
+    format!("in the configuration: {}", e)
+}
+
+// Speed, quirks, memory size and stack depth come from the command line,
+// then the per-ROM configuration, then the database, then the configured
+// defaults. The timing is not in the database.
+fn machine_config(exe: &[u8], opts: &Options, info: &Option<RomInfo>,
+                  rc: &RomConfig) -> Result<RunConfig, String> {
+    let mut quirks = Quirks::default();
+    if let Some(ref spec) = rc.defaults.quirks {
+        quirks.apply(spec).map_err(config_err)?;
+    }
+    if let Some(ref i) = *info {
+        quirks = i.quirks;
+    }
+    if let Some(ref spec) = rc.rom.quirks {
+        quirks.apply(spec).map_err(config_err)?;
+    }
+    if let Some(ref spec) = opts.quirks {
+        quirks.apply(spec)?;
+    }
+
+    let ipf = opts.speed
+        .or(rc.rom.speed)
+        .or(info.as_ref().map(|i| i.tickrate))
+        .or(rc.defaults.speed)
+        .unwrap_or(DEFAULT_TICKRATE);
+
+    let mem_size = opts.memory
+        .or(rc.rom.memory)
+        .or(info.as_ref().map(|i| i.memory_size))
+        .or(rc.defaults.memory)
+        .unwrap_or(MEM_SIZE);
+    if !(MEM_SIZE..=MAX_MEM_SIZE).contains(&mem_size) {
+        return Err(format!("memory of {} bytes, expected {} up to {}",
+                           mem_size, MEM_SIZE, MAX_MEM_SIZE));
+    }
+
+    let stack = opts.stack
+        .or(rc.rom.stack)
+        .or(info.as_ref().map(|i| i.stack_depth))
+        .or(rc.defaults.stack)
+        .unwrap_or(DEFAULT_STACK_DEPTH);
+
+    let timing = match opts.timing {
+        Some(ref t) => t.parse()?,
+        None => match rc.rom.timing.as_ref().or(rc.defaults.timing.as_ref()) {
+            Some(t) => t.parse().map_err(config_err)?,
+            None => Timing::default(),
+        },
+    };
+
+    let vip = match opts.cpu.as_deref() {
+        None | Some("hle") => None,
+        Some("cdp1802") => Some(vip_images(exe, opts, mem_size)?),
+        Some(other) => return Err(format!("unknown cpu '{}', expected hle or cdp1802", other)),
+    };
+
+    let profile = if opts.profile.is_some() || opts.folded.is_some() {
+        if vip.is_some() {
+            return Err("the profiler counts CHIP-8 instructions, it cannot profile the cdp1802 cpu".to_string());
+        }
+        Some(ProfileOutput { report: opts.profile.clone(), folded: opts.folded.clone() })
+    } else {
+        None
+    };
+
+    if opts.console && vip.is_some() {
+        return Err("the console searches the CHIP-8 memory, it cannot search the cdp1802 cpu".to_string());
+    }
+
+    let mut cheats = Cheats::new();
+    if let Some(ref spec) = rc.merged().cheats {
+        cheats.apply(spec).map_err(config_err)?;
+    }
+    if let Some(ref spec) = opts.cheats {
+        cheats.apply(spec)?;
+    }
+
+    Ok(RunConfig {
+        ipf: ipf.clamp(1, MAX_SPEED),
+        seed: opts.seed.unwrap_or_else(random_seed),
+        quirks,
+        mem_size,
+        stack_depth: depth_from(stack),
+        timing,
+        vip,
+        profile,
+        cheats,
+        console: opts.console,
+        rom_hash: fnv1a64(exe),
+        throttle: !opts.headless,
+    })
+}
+
+#[cfg(feature = "rand")]
+fn random_seed() -> u64 {
+    rand::random()
+}
+
+// without rand the clock has to do
+#[cfg(not(feature = "rand"))]
+fn random_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    fnv1a64(&now.as_nanos().to_le_bytes())
+}
+
+fn read_image(path: &PathBuf, what: &str, max: usize) -> Result<Vec<u8>, String> {
+    let image = fs::read(path).map_err(|e| format!("cannot load {}: {}", path.display(), e))?;
+    if image.len() > max {
+        return Err(format!("the {} is {} bytes, at most {} fit", what, image.len(), max));
+    }
+    Ok(image)
+}
+
+// Neither image is bundled, both come from the command line.
+fn vip_images(exe: &[u8], opts: &Options, mem_size: usize) -> Result<VipImages, String> {
+    let rom = match opts.vip_rom {
+        Some(ref p) => read_image(p, "VIP ROM", VIP_ROM_SIZE)?,
+        None => return Err("--cpu cdp1802 needs the VIP's monitor ROM, see --vip-rom".to_string()),
+    };
+    let interpreter = match opts.interpreter {
+        Some(ref p) => read_image(p, "interpreter", INTERPRETER_SIZE)?,
+        None => return Err("--cpu cdp1802 needs a CHIP-8 interpreter, see --interpreter".to_string()),
+    };
+
+    if !mem_size.is_power_of_two() || mem_size > MAX_VIP_RAM {
+        return Err(format!("memory of {} bytes, the VIP takes a power of two up to {}",
+                           mem_size, MAX_VIP_RAM));
+    }
+    let room = mem_size - EXE_START_ADDR - INTERPRETER_WORK_AREA;
+    if exe.len() > room {
+        return Err(format!("the ROM is {} bytes, at most {} fit next to the interpreter's work area",
+                           exe.len(), room));
+    }
+    Ok(VipImages { rom, interpreter })
+}
+
+// Keys bound by the configured defaults, the database, the per-ROM
+// configuration and the command line, later ones win.
+#[cfg(feature = "sdl2")]
+fn keymap(opts: &Options, info: &Option<RomInfo>, rc: &RomConfig) -> Result<Keymap, String> {
+    let mut keymap = Keymap::default();
+    if let Some(ref spec) = rc.defaults.keymap {
+        keymap.apply(spec).map_err(config_err)?;
+    }
+    if let Some(ref i) = *info {
+        for (action, &key) in &i.keys {
+            keymap.bind_action(action, key);
+        }
+    }
+    if let Some(ref spec) = rc.rom.keymap {
+        keymap.apply(spec).map_err(config_err)?;
+    }
+    if let Some(ref spec) = opts.keymap {
+        keymap.apply(spec)?;
+    }
+    Ok(keymap)
+}
+
+fn print_match(exe: &[u8], info: &Option<RomInfo>) {
+    match *info {
+        Some(ref i) => println!("{} ({}), platform {}, recommended speed {}",
+                                i.title, i.sha1, i.platform_name, i.tickrate),
+        None => println!("ROM {} not in the database, using defaults", sha1_hex(exe)),
+    }
+}
+
+fn new_memory(exe: &[u8], cfg: &RunConfig) -> Result<Memory, String> {
+    let mut mem = Memory::with_size(cfg.mem_size);
+    if exe.len() > mem.max_exe_size() {
+        return Err(format!("the ROM is {} bytes, at most {} fit in {} bytes of memory",
+                           exe.len(), mem.max_exe_size(), cfg.mem_size));
+    }
+    Ok(mem.load_sprites(SPRITES).load_exe(exe).build())
+}
+
+// The movie of --movie-play, with the settings it was recorded with
+// taken over into `cfg`.
+fn load_movie(opts: &Options, cfg: &mut RunConfig) -> Result<Option<Movie>, String> {
+    let movie = match opts.movie_play {
+        Some(ref p) => Movie::load(p).map_err(|e| format!("cannot load {}: {}", p.display(), e))?,
+        None => return Ok(None),
+    };
+    if movie.rom_hash != cfg.rom_hash {
+        println!("warning: the movie was recorded with a different ROM");
+    }
+    cfg.seed = movie.seed;
+    cfg.ipf = movie.ipf;
+    cfg.quirks = movie.quirks;
+    cfg.timing = movie.timing;
+    cfg.mem_size = movie.mem_size;
+    cfg.stack_depth = movie.stack_depth;
+    Ok(Some(movie))
+}
+
+fn cmd_run(opts: &Options) -> Result<(), String> {
+    let (exe, original) = load(opts)?;
+    let info = lookup_rom(&original, opts)?;
+    print_match(&original, &info);
+
+    let mut rc = load_config(&exe, opts)?;
+    if opts.save_cheats {
+        save_cheats(&mut rc, opts)?;
+    }
+    let settings = rc.merged();
+
+    let mut cfg = machine_config(&exe, opts, &info, &rc)?;
+    let movie = load_movie(opts, &mut cfg)?;
+    let mut mem = new_memory(&exe, &cfg)?;
+    let mut display = Display::new();
+
+    let palette = match (opts.palette, settings.palette) {
+        (Some(p), _) => p,
+        (None, Some(ref p)) => p.parse().map_err(config_err)?,
+        (None, None) => Palette::default(),
+    };
+    let persistence = match (opts.persistence, settings.persistence) {
+        (Some(p), _) => p,
+        (None, Some(ref p)) => p.parse().map_err(config_err)?,
+        (None, None) => Persistence::Off,
+    };
+    let scale = opts.scale.or(settings.window.scale).unwrap_or(10).max(1);
+
+    let speed = if opts.headless {
+        let mut media_if = Headless::new();
+        if let Some(frames) = opts.frames {
+            media_if.set_max_frames(frames);
+        }
+        setup_capture(media_if.capture(), opts, scale, palette)?;
+        run_session(&mut mem, &mut display, &mut media_if, &cfg, opts, movie)?
+    } else {
+        let mut media_if = window(opts, &info, &rc, scale, palette, persistence)?;
+        run_session(&mut mem, &mut display, &mut media_if, &cfg, opts, movie)?
+    };
+
+    if speed != cfg.ipf {
+        save_speed(rc, speed)?;
+    }
+
+    if let Some(ref path) = opts.screenshot {
+        let capture = Capture::new(scale as usize, palette);
+        capture.screenshot(path, display.get_video_buf().unwrap())
+            .map_err(|e| format!("cannot save {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sdl2")]
+fn window(opts: &Options, info: &Option<RomInfo>, rc: &RomConfig,
+          scale: u32, palette: Palette, persistence: Persistence) -> Result<Sdl2Be, String> {
+    let settings = rc.merged();
+    let window = WindowSettings {
+        scale,
+        fullscreen: opts.fullscreen || settings.window.fullscreen.unwrap_or(false),
+        palette,
+    };
+    let defaults = AudioSettings::default();
+    let audio = AudioSettings {
+        enabled: !opts.mute && settings.audio.enabled.unwrap_or(defaults.enabled),
+        volume: settings.audio.volume.unwrap_or(defaults.volume),
+        tone: settings.audio.tone.unwrap_or(defaults.tone),
+    };
+
+    let mut media_if = Sdl2Be::with_settings(&window, &audio);
+    media_if.set_persistence(persistence);
+    media_if.set_keymap(keymap(opts, info, rc)?);
+    if let Some(frames) = opts.frames {
+        media_if.set_max_frames(frames);
+    }
+    setup_capture(media_if.capture(), opts, scale, palette)?;
+    Ok(media_if)
+}
+
+#[cfg(not(feature = "sdl2"))]
+fn window(_opts: &Options, _info: &Option<RomInfo>, _rc: &RomConfig,
+          _scale: u32, _palette: Palette, _persistence: Persistence) -> Result<Headless, String> {
+    Err("built without the sdl2 feature, only --headless runs are possible".to_string())
+}
+
+// Keeps a speed set with the hotkeys for the next run of the ROM.
+fn save_speed(mut rc: RomConfig, speed: u32) -> Result<(), String> {
+    let path = match rc.path {
+        Some(ref p) => p.clone(),
+        None => return Ok(()),
+    };
+
+    rc.config.rom_entry(&rc.name, &rc.sha1).speed = Some(speed);
+    rc.config.save(&path).map_err(|e| format!("cannot save {}: {}", path.display(), e))?;
+    println!("speed {} saved for {} in {}", speed, rc.name, path.display());
+    Ok(())
+}
+
+// Adds the --cheat list to the section of the ROM's SHA-1, so that it is
+// only applied to this image of the game.
+fn save_cheats(rc: &mut RomConfig, opts: &Options) -> Result<(), String> {
+    let path = match rc.path {
+        Some(ref p) => p.clone(),
+        None => return Err("--save-cheats needs a configuration file".to_string()),
+    };
+    let spec = opts.cheats.as_ref().ok_or_else(|| "--save-cheats needs a --cheat list".to_string())?;
+
+    let entry = rc.config.rom.entry(rc.sha1.clone()).or_default();
+    let mut cheats = Cheats::new();
+    if let Some(ref saved) = entry.cheats {
+        cheats.apply(saved).map_err(config_err)?;
+    }
+    cheats.apply(spec)?;
+    entry.cheats = Some(cheats.to_string());
+    rc.rom = rc.config.rom_settings(&rc.name, &rc.sha1);
+
+    rc.config.save(&path).map_err(|e| format!("cannot save {}: {}", path.display(), e))?;
+    println!("cheats {} saved for {} in {}", cheats, rc.name, path.display());
+    Ok(())
+}
+
+fn cmd_disasm(opts: &Options) -> Result<(), String> {
+    let (exe, _) = load(opts)?;
+    print!("{}", listing(&exe, EXE_START_ADDR as u16));
+    Ok(())
+}
+
+fn cmd_info(opts: &Options) -> Result<(), String> {
+    let (exe, original) = load(opts)?;
+    let info = lookup_rom(&original, opts)?;
+
+    println!("file:     {}", opts.rom);
+    println!("size:     {} bytes", exe.len());
+    println!("sha1:     {}", sha1_hex(&exe));
+    if exe != original {
+        println!("original: {}", sha1_hex(&original));
+    }
+    match info {
+        Some(ref i) => {
+            println!("title:    {}", i.title);
+            println!("platform: {} ({})", i.platform_name, i.platform);
+            println!("speed:    {} instructions per frame", i.tickrate);
+            println!("quirks:   {}", i.quirks);
+            println!("memory:   {} bytes", i.memory_size);
+            println!("stack:    {} return addresses", i.stack_depth);
+        },
+        None => println!("title:    not in the database"),
+    }
+
+    let detection = detect(&exe);
+    let platform = detection.platform();
+    if detection.findings.is_empty() {
+        println!("detected: {}, no opcodes of later platforms", platform);
+    } else {
+        println!("detected: {}", platform);
+    }
+    for f in &detection.findings {
+        let times = if f.count == 1 { String::new() } else { format!(", {} times", f.count) };
+        println!("          {} {} at {:04X}{}", f.opcode, f.what, f.addr, times);
+    }
+    println!("suggest:  {}", detection.suggestion());
+
+    println!();
+    print!("{}", hexdump(&exe, EXE_START_ADDR as u16));
+    Ok(())
+}
+
+// Steps instruction by instruction, with the timers ticking every
+// `cfg.ipf` instructions or VIP frame as in a normal run.
+fn cmd_trace(opts: &Options) -> Result<(), String> {
+    let (exe, original) = load(opts)?;
+    let info = lookup_rom(&original, opts)?;
+    let rc = load_config(&exe, opts)?;
+    let cfg = machine_config(&exe, opts, &info, &rc)?;
+
+    let mut mem = new_memory(&exe, &cfg)?;
     let mut display = Display::new();
-    let mut media_if = Sdl2Be::new();
+    let mut media_if = Headless::new();
+    if cfg.vip.is_some() {
+        return Err("trace steps through CHIP-8 instructions, it cannot trace the cdp1802 cpu".to_string());
+    }
+    let mut emulator = new_cpu(&mut mem, &mut display, &mut media_if, &cfg);
+    emulator.set_trace(true);
 
-    let mut emulator = CPU::new(&mut mem as &mut CpuMemory,
-                                &mut display as &mut VideoMemory,
-                                &mut media_if as &mut MediaIf);
-    execute_vm(&mut emulator, c);
+    let mut budget = vip_frame_cycles();
+    for n in 1..=opts.instructions {
+        let step = emulator.fetch().and_then(|instruction| {
+            let (id, arg) = emulator.decode(instruction).unwrap();
+            emulator.execute(id, arg)
+        });
+        step.map_err(|f| stopped(f, &emulator))?;
+
+        let frame_done = match cfg.timing {
+            Timing::Ipf => n.is_multiple_of(cfg.ipf as u64),
+            Timing::Vip => emulator.cycles() >= budget,
+        };
+        if frame_done {
+            budget += vip_frame_cycles();
+            emulator.update_timers();
+            emulator.end_frame();
+        }
+    }
+    Ok(())
+}
+
+fn cmd_bench(opts: &Options) -> Result<(), String> {
+    let (exe, original) = load(opts)?;
+    let info = lookup_rom(&original, opts)?;
+    let rc = load_config(&exe, opts)?;
+    let cfg = machine_config(&exe, opts, &info, &rc)?;
+    let frames = opts.frames.unwrap_or(600);
+
+    let mut mem = new_memory(&exe, &cfg)?;
+    let mut display = Display::new();
+    let mut media_if = Headless::new();
+    media_if.set_max_frames(frames);
+
+    let start = Instant::now();
+    run(&mut mem, &mut display, &mut media_if, &cfg, &mut None)?;
+    let secs = start.elapsed().as_secs_f64();
+
+    if cfg.timing == Timing::Vip || cfg.vip.is_some() {
+        println!("{} frames in {:.3} s", frames, secs);
+        println!("{:.0} frames/s ({:.1}x real time)", frames as f64 / secs, frames as f64 / secs / 60.0);
+        return Ok(());
+    }
+
+    let instructions = frames * cfg.ipf as u64;
+    println!("{} frames, {} instructions in {:.3} s", frames, instructions, secs);
+    println!("{:.0} frames/s ({:.1}x real time), {:.0} instructions/s",
+             frames as f64 / secs, frames as f64 / secs / 60.0, instructions as f64 / secs);
+    Ok(())
+}
+
+fn cmd_test(opts: &Options) -> Result<(), String> {
+    let (exe, original) = load(opts)?;
+    let info = lookup_rom(&original, opts)?;
+    let rc = load_config(&exe, opts)?;
+    let cfg = machine_config(&exe, opts, &info, &rc)?;
+
+    let mut mem = new_memory(&exe, &cfg)?;
+    let mut display = Display::new();
+    let mut media_if = Headless::new();
+    media_if.set_max_frames(opts.frames.unwrap_or(600));
+    run(&mut mem, &mut display, &mut media_if, &cfg, &mut None)?;
+
+    if let Some(ref path) = opts.screenshot {
+        Capture::default().screenshot(path, display.get_video_buf().unwrap())
+            .map_err(|e| format!("cannot save {}: {}", path.display(), e))?;
+    }
+
+    let hash = screen_hash(display.get_video_buf().unwrap());
+    println!("{:016x}", hash);
+    match opts.expect {
+        Some(expect) if expect != hash => Err(format!("screen hash {:016x}, expected {:016x}", hash, expect)),
+        _ => Ok(()),
+    }
+}
+
+fn cmd_diff(opts: &Options) -> Result<(), String> {
+    let read = |rom: &str| {
+        load_rom(&RomSpec::parse(rom)).map_err(|e| format!("cannot load {}: {}", rom, e))
+    };
+    let original = read(&opts.rom)?;
+    let modified = read(&opts.more_roms[0])?;
+
+    let path = PathBuf::from(&opts.more_roms[1]);
+    let format = PatchFormat::from_path(&path)
+        .ok_or_else(|| format!("cannot tell the format of {}, expected .ips or .bps", path.display()))?;
+    let patch = create_patch(format, &original, &modified);
+    fs::write(&path, &patch).map_err(|e| format!("cannot save {}: {}", path.display(), e))?;
+    println!("{} bytes of patch saved to {}", patch.len(), path.display());
+    Ok(())
+}
+
+// Runs to the frame of every filter in turn and narrows the candidates
+// down with it.
+fn cmd_search(opts: &Options) -> Result<(), String> {
+    if opts.filters.is_empty() {
+        return Err("search needs at least one --filter".to_string());
+    }
+    let (exe, original) = load(opts)?;
+    let info = lookup_rom(&original, opts)?;
+    let rc = load_config(&exe, opts)?;
+    let mut cfg = machine_config(&exe, opts, &info, &rc)?;
+    if cfg.vip.is_some() {
+        return Err("search looks at the CHIP-8 memory, it cannot search the cdp1802 cpu".to_string());
+    }
+    let movie = load_movie(opts, &mut cfg)?;
+
+    let mut mem = new_memory(&exe, &cfg)?;
+    let mut display = Display::new();
+    let mut headless = Headless::new();
+    let mut player;
+    let media_if: &mut dyn MediaIf = match movie {
+        Some(m) => {
+            player = MoviePlayer::new(&mut headless, m.keys);
+            &mut player
+        },
+        None => &mut headless,
+    };
+    let mut emulator = new_cpu(&mut mem, &mut display, media_if, &cfg);
+
+    let mut filters = opts.filters.clone();
+    filters.sort_by_key(|f| f.0);
+    let mut search = RamSearch::new(emulator.memory());
+    let mut frame = 0;
+    let mut budget = emulator.cycles();
+    for &(at, rel) in &filters {
+        while frame < at {
+            if poll_frontend(&mut emulator, &mut false).is_none() {
+                return Err(format!("the movie ends at frame {}, before the filter at frame {}", frame, at));
+            }
+            run_frame(&mut emulator, cfg.ipf, cfg.timing, &mut budget).map_err(|f| stopped(f, &emulator))?;
+            frame += 1;
+        }
+        let left = search.filter(emulator.memory(), rel);
+        println!("frame {:>6}  {:<8} {} addresses left", at, rel, left);
+    }
+
+    print!("{}", search_listing(&search));
+    Ok(())
+}
+
+fn main() {
+    let opts = match parse_args(env::args().skip(1)) {
+        Ok(Some(opts)) => opts,
+        Ok(None) => process::exit(EXIT_OK),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("see 'chip8_opcode --help'");
+            process::exit(EXIT_USAGE);
+        },
+    };
+
+    // a bare archive lists its entries whatever the command
+    if let RomSpec::Archive(ref archive) = RomSpec::parse(&opts.rom) {
+        match list_archive(archive) {
+            Ok(entries) => {
+                for e in entries {
+                    println!("{:>6}  {}", e.size, e.name);
+                }
+                process::exit(EXIT_OK);
+            },
+            Err(e) => {
+                eprintln!("error: {}: {}", archive.display(), e);
+                process::exit(EXIT_FAILURE);
+            },
+        }
+    }
+
+    let res = match opts.command {
+        Command::Run => cmd_run(&opts),
+        Command::Disasm => cmd_disasm(&opts),
+        Command::Info => cmd_info(&opts),
+        Command::Trace => cmd_trace(&opts),
+        Command::Bench => cmd_bench(&opts),
+        Command::Test => cmd_test(&opts),
+        Command::Search => cmd_search(&opts),
+        Command::Diff => cmd_diff(&opts),
+    };
+
+    if let Err(e) = res {
+        eprintln!("error: {}", e);
+        process::exit(EXIT_FAILURE);
+    }
 }
@@ -0,0 +1,380 @@
+use media_if::*;
+use memory::*;
+use quirks::*;
+use stack::*;
+use timing::*;
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+// A state hash is stored every HASH_INTERVAL frames.
+pub const HASH_INTERVAL: u64 = 60;
+
+const MAGIC: &str = "chip8-movie 1";
+
+// Per-frame keypad state plus everything else needed to replay a run
+// deterministically. On disk it is a line based text file:
+//
+//   chip8-movie 1
+//   rom 9a2b...        FNV-1a hash of the ROM image
+//   seed 1234
+//   ipf 10
+//   quirks shift=1,... with memory_wrap, faults or wrapping past memory
+//   timing vip         ipf when missing
+//   memory 1000        bytes of memory, 4096 when missing
+//   stack 10           return addresses, 0 for unlimited, 16 when missing
+//   k 0010             keypad of one frame, bit N set = key N pressed
+//   h 60 5c1e...       state hash after the given frame
+#[derive(Clone, Debug)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub ipf: u32,
+    pub quirks: Quirks,
+    pub timing: Timing,
+    pub mem_size: usize,
+    // None for unlimited
+    pub stack_depth: Option<usize>,
+    pub keys: Vec<u16>,
+    pub hashes: Vec<(u64, u64)>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_hex<T>(s: Option<&str>, line: usize, f: fn(&str, u32) -> Result<T, ::std::num::ParseIntError>) -> io::Result<T> {
+    s.and_then(|s| f(s, 16).ok())
+        .ok_or_else(|| invalid(format!("movie line {}: bad number", line)))
+}
+
+impl Movie {
+    pub fn new(rom_hash: u64, seed: u64, ipf: u32, quirks: Quirks) -> Self {
+        Movie {
+            rom_hash,
+            seed,
+            ipf,
+            quirks,
+            timing: Timing::default(),
+            mem_size: MEM_SIZE,
+            stack_depth: Some(DEFAULT_STACK_DEPTH),
+            keys: Vec::new(),
+            hashes: Vec::new(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Movie::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "{}", MAGIC)?;
+        writeln!(w, "rom {:016x}", self.rom_hash)?;
+        writeln!(w, "seed {:x}", self.seed)?;
+        writeln!(w, "ipf {:x}", self.ipf)?;
+        writeln!(w, "quirks {}", self.quirks)?;
+        writeln!(w, "timing {}", self.timing)?;
+        writeln!(w, "memory {:x}", self.mem_size)?;
+        writeln!(w, "stack {:x}", self.stack_depth.unwrap_or(0))?;
+
+        let mut hashes = self.hashes.iter().peekable();
+        for (frame, keys) in self.keys.iter().enumerate() {
+            writeln!(w, "k {:04x}", keys)?;
+            while let Some(&&(f, h)) = hashes.peek() {
+                if f > frame as u64 + 1 {
+                    break;
+                }
+                writeln!(w, "h {:x} {:016x}", f, h)?;
+                hashes.next();
+            }
+        }
+        for &(f, h) in hashes {
+            writeln!(w, "h {:x} {:016x}", f, h)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: BufRead>(r: R) -> io::Result<Self> {
+        let mut movie = Movie::new(0, 0, 0, Quirks::default());
+        let mut lines = r.lines().enumerate();
+
+        match lines.next() {
+            Some((_, Ok(ref l))) if l == MAGIC => {},
+            _ => return Err(invalid("not a chip8 movie".to_string())),
+        }
+
+        for (n, line) in lines {
+            let line = line?;
+            let n = n + 1;
+            let mut it = line.split_whitespace();
+
+            match it.next() {
+                Some("rom") => movie.rom_hash = parse_hex(it.next(), n, u64::from_str_radix)?,
+                Some("seed") => movie.seed = parse_hex(it.next(), n, u64::from_str_radix)?,
+                Some("ipf") => movie.ipf = parse_hex(it.next(), n, u32::from_str_radix)?,
+                Some("quirks") => {
+                    movie.quirks = it.next().unwrap_or("").parse()
+                        .map_err(|e| invalid(format!("movie line {}: {}", n, e)))?;
+                },
+                Some("timing") => {
+                    movie.timing = it.next().unwrap_or("").parse()
+                        .map_err(|e| invalid(format!("movie line {}: {}", n, e)))?;
+                },
+                Some("memory") => {
+                    movie.mem_size = parse_hex(it.next(), n, usize::from_str_radix)?;
+                    if !(MEM_SIZE..=MAX_MEM_SIZE).contains(&movie.mem_size) {
+                        return Err(invalid(format!("movie line {}: memory of {} bytes, expected {} up to {}",
+                                                   n, movie.mem_size, MEM_SIZE, MAX_MEM_SIZE)));
+                    }
+                },
+                Some("stack") => movie.stack_depth = depth_from(parse_hex(it.next(), n, usize::from_str_radix)?),
+                Some("k") => movie.keys.push(parse_hex(it.next(), n, u16::from_str_radix)?),
+                Some("h") => {
+                    let f = parse_hex(it.next(), n, u64::from_str_radix)?;
+                    let h = parse_hex(it.next(), n, u64::from_str_radix)?;
+                    movie.hashes.push((f, h));
+                },
+                None => {},
+                Some(tag) => return Err(invalid(format!("movie line {}: unknown tag '{}'", n, tag))),
+            }
+        }
+
+        Ok(movie)
+    }
+}
+
+// Passes everything through to the real backend and samples its keypad
+// once per frame.
+pub struct MovieRecorder<'a> {
+    inner: &'a mut (dyn MediaIf + 'a),
+    keys: Vec<u16>,
+}
+
+impl<'a> MovieRecorder<'a> {
+    pub fn new(inner: &'a mut (dyn MediaIf + 'a)) -> Self {
+        MovieRecorder {
+            inner,
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn into_keys(self) -> Vec<u16> {
+        self.keys
+    }
+}
+
+impl<'a> VideoSink for MovieRecorder<'a> {
+    fn draw_display(&mut self, buf: Screen) -> Option<u8> {
+        self.inner.draw_display(buf)
+    }
+
+    fn clear_display(&mut self) -> Option<u8> {
+        self.inner.clear_display()
+    }
+
+    fn present_display(&mut self) -> Option<u8> {
+        self.inner.present_display()
+    }
+
+    fn end_frame(&mut self, buf: Screen) -> Option<u8> {
+        // keys only change in poll_input, so this is what the frame saw
+        let mut mask = 0u16;
+        for k in 0..16 {
+            if self.inner.is_key_pressed(k) {
+                mask |= 1 << k;
+            }
+        }
+        self.keys.push(mask);
+
+        self.inner.end_frame(buf)
+    }
+}
+
+impl<'a> AudioSink for MovieRecorder<'a> {
+    fn set_sound(&mut self, on: bool) {
+        self.inner.set_sound(on)
+    }
+}
+
+impl<'a> InputSource for MovieRecorder<'a> {
+    fn poll_input(&mut self) {
+        self.inner.poll_input()
+    }
+
+    fn is_key_pressed(&mut self, key: u8) -> bool {
+        self.inner.is_key_pressed(key)
+    }
+
+    fn get_pressed_key(&self) -> Option<&u8> {
+        self.inner.get_pressed_key()
+    }
+
+    fn poll_key_event(&mut self) -> Option<KeyEvent> {
+        self.inner.poll_key_event()
+    }
+}
+
+impl<'a> ControlSource for MovieRecorder<'a> {
+    fn poll_control(&mut self) -> Option<Control> {
+        movie_control(&mut *self.inner)
+    }
+}
+
+// The movie has a single speed and starts at power-on, speed changes and
+// resets would desync the replay. Pausing does not, no frame runs.
+fn movie_control(inner: &mut dyn MediaIf) -> Option<Control> {
+    loop {
+        match inner.poll_control() {
+            Some(Control::Speed(_)) | Some(Control::Reset) => continue,
+            other => return other,
+        }
+    }
+}
+
+// Drives the key queries from a recorded movie, live input is ignored.
+// Video still goes to the real backend. Stops when the movie ends.
+pub struct MoviePlayer<'a> {
+    inner: &'a mut (dyn MediaIf + 'a),
+    keys: Vec<u16>,
+    frame: usize,
+    edges: KeyEdges,
+}
+
+impl<'a> MoviePlayer<'a> {
+    pub fn new(inner: &'a mut (dyn MediaIf + 'a), keys: Vec<u16>) -> Self {
+        MoviePlayer {
+            inner,
+            keys,
+            frame: 0,
+            edges: KeyEdges::default(),
+        }
+    }
+
+    fn current(&self) -> u16 {
+        self.keys.get(self.frame).cloned().unwrap_or(0)
+    }
+}
+
+impl<'a> VideoSink for MoviePlayer<'a> {
+    fn draw_display(&mut self, buf: Screen) -> Option<u8> {
+        self.inner.draw_display(buf)
+    }
+
+    fn clear_display(&mut self) -> Option<u8> {
+        self.inner.clear_display()
+    }
+
+    fn present_display(&mut self) -> Option<u8> {
+        self.inner.present_display()
+    }
+
+    fn end_frame(&mut self, buf: Screen) -> Option<u8> {
+        self.frame += 1;
+        self.inner.end_frame(buf)
+    }
+}
+
+impl<'a> AudioSink for MoviePlayer<'a> {
+    fn set_sound(&mut self, on: bool) {
+        self.inner.set_sound(on)
+    }
+}
+
+// the live input is still polled, e.g. for the backend to see a quit
+impl<'a> InputSource for MoviePlayer<'a> {
+    fn poll_input(&mut self) {
+        self.inner.poll_input();
+        self.edges.update(self.current());
+    }
+
+    fn is_key_pressed(&mut self, key: u8) -> bool {
+        self.current() & (1 << key) != 0
+    }
+
+    fn get_pressed_key(&self) -> Option<&u8> {
+        let keys = self.current();
+        KEY_IDS.iter().find(|&&k| keys & (1 << k) != 0)
+    }
+
+    fn poll_key_event(&mut self) -> Option<KeyEvent> {
+        self.edges.next_event()
+    }
+}
+
+impl<'a> ControlSource for MoviePlayer<'a> {
+    fn poll_control(&mut self) -> Option<Control> {
+        if self.frame >= self.keys.len() {
+            return Some(Control::Quit);
+        }
+        movie_control(&mut *self.inner)
+    }
+}
+
+// Collects state hashes while recording, or compares them while playing.
+pub struct StateLog {
+    expected: Option<Vec<(u64, u64)>>,
+    recorded: Vec<(u64, u64)>,
+    desyncs: u64,
+}
+
+impl StateLog {
+    pub fn recording() -> Self {
+        StateLog {
+            expected: None,
+            recorded: Vec::new(),
+            desyncs: 0,
+        }
+    }
+
+    pub fn verifying(expected: Vec<(u64, u64)>) -> Self {
+        StateLog {
+            expected: Some(expected),
+            recorded: Vec::new(),
+            desyncs: 0,
+        }
+    }
+
+    pub fn wants_hash(&self, frame: u64) -> bool {
+        frame.is_multiple_of(HASH_INTERVAL)
+    }
+
+    // `frame` is the number of frames completed so far.
+    pub fn on_frame(&mut self, frame: u64, hash: u64) {
+        self.recorded.push((frame, hash));
+
+        let expected = match self.expected {
+            Some(ref e) => e.iter().find(|&&(f, _)| f == frame).map(|&(_, h)| h),
+            None => None,
+        };
+
+        if let Some(h) = expected {
+            if h != hash {
+                if self.desyncs == 0 {
+                    println!("movie desync at frame {}: expected state {:016x}, got {:016x}",
+                             frame, h, hash);
+                }
+                self.desyncs += 1;
+            }
+        }
+    }
+
+    pub fn desyncs(&self) -> u64 {
+        self.desyncs
+    }
+
+    pub fn into_hashes(self) -> Vec<(u64, u64)> {
+        self.recorded
+    }
+}
+
+#[cfg(test)]
+mod tests;
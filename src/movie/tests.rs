@@ -0,0 +1,201 @@
+use super::*;
+use cpu::*;
+use cpu_ops::*;
+use headless_media::*;
+use machine::*;
+use sprites::*;
+use utils::*;
+
+use std::env;
+use std::fs;
+use std::process;
+
+const BRIX: &[u8] = include_bytes!("../../res/BRIX");
+const FRAMES: usize = 300;
+
+// BRIX with the paddle moved left and then right
+fn keys() -> Vec<u16> {
+    (0..FRAMES).map(|f| match f / 60 {
+        1 => 1 << 4,
+        3 => 1 << 6,
+        _ => 0,
+    }).collect()
+}
+
+// Runs `movie`'s settings for at most `frames` frames, logging the state
+// hashes like a run of the emulator does.
+fn play(movie: &Movie, media_if: &mut dyn MediaIf, frames: usize, log: &mut StateLog) {
+    let mut mem = Memory::new().load_sprites(SPRITES).load_exe(BRIX).build();
+    let mut display = Display::new();
+    let mut cpu = CPU::new(&mut mem, &mut display, media_if);
+    cpu.set_quirks(movie.quirks);
+    cpu.set_seed(movie.seed);
+
+    let mut budget = cpu.cycles();
+    let mut frame = 0;
+    while frame < frames && poll_frontend(&mut cpu, &mut false).is_some() {
+        run_frame(&mut cpu, movie.ipf, movie.timing, &mut budget).unwrap();
+        frame += 1;
+        if log.wants_hash(frame as u64) {
+            log.on_frame(frame as u64, cpu.state_hash());
+        }
+    }
+}
+
+fn record(timing: Timing) -> Movie {
+    let mut movie = Movie::new(fnv1a64(BRIX), 3, 15, Quirks::preset("vip").unwrap());
+    movie.timing = timing;
+
+    let mut input = Frontend::new(Headless::new(), (), ScriptedInput::new(keys()), ());
+    let mut recorder = MovieRecorder::new(&mut input);
+    let mut log = StateLog::recording();
+    play(&movie, &mut recorder, FRAMES, &mut log);
+    movie.keys = recorder.into_keys();
+    movie.hashes = log.into_hashes();
+    movie
+}
+
+fn round_trip(movie: &Movie) -> Movie {
+    let mut text = Vec::new();
+    movie.write(&mut text).unwrap();
+    Movie::read(&text[..]).unwrap()
+}
+
+// Replays with the settings of `movie`, returns the desynced hashes.
+fn replay(movie: &Movie) -> u64 {
+    let mut headless = Headless::new();
+    let mut player = MoviePlayer::new(&mut headless, movie.keys.clone());
+    let mut log = StateLog::verifying(movie.hashes.clone());
+    play(movie, &mut player, usize::MAX, &mut log);
+    log.desyncs()
+}
+
+#[test]
+fn vip_timing_round_trip() {
+    let movie = round_trip(&record(Timing::Vip));
+    assert_eq!(movie.timing, Timing::Vip);
+    assert_eq!(movie.keys, keys());
+    assert_eq!(movie.hashes.len(), FRAMES / HASH_INTERVAL as usize);
+    assert_eq!(replay(&movie), 0);
+
+    // what playback did before the timing was stored
+    let mut other = movie;
+    other.timing = Timing::Ipf;
+    assert!(replay(&other) > 0);
+}
+
+#[test]
+fn stack_depth_round_trip() {
+    let mut movie = Movie::new(1, 2, 3, Quirks::default());
+    assert_eq!(round_trip(&movie).stack_depth, Some(DEFAULT_STACK_DEPTH));
+    movie.stack_depth = Some(VIP_STACK_DEPTH);
+    assert_eq!(round_trip(&movie).stack_depth, Some(VIP_STACK_DEPTH));
+    movie.stack_depth = None;
+    assert_eq!(round_trip(&movie).stack_depth, None);
+}
+
+#[test]
+fn memory_round_trip() {
+    let mut movie = Movie::new(1, 2, 3, Quirks::default());
+    assert_eq!(round_trip(&movie).mem_size, MEM_SIZE);
+    movie.mem_size = MAX_MEM_SIZE;
+    assert_eq!(round_trip(&movie).mem_size, MAX_MEM_SIZE);
+
+    let text = format!("{}\nmemory {:x}\n", MAGIC, MAX_MEM_SIZE + 1);
+    assert!(Movie::read(text.as_bytes()).is_err());
+}
+
+#[test]
+fn save_and_load() {
+    let mut movie = Movie::new(0x0123_4567_89ab_cdef, 42, 12, Quirks::preset("schip").unwrap());
+    movie.keys = vec![0, 0x10, 0x8001];
+    movie.hashes = vec![(1, 0xAA), (3, 0xBB)];
+    let path = env::temp_dir().join(format!("chip8-movie-test-{}.mov", process::id()));
+    movie.save(&path).unwrap();
+    let loaded = Movie::load(&path);
+    fs::remove_file(&path).unwrap();
+
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.rom_hash, movie.rom_hash);
+    assert_eq!(loaded.seed, 42);
+    assert_eq!(loaded.ipf, 12);
+    assert_eq!(loaded.quirks, movie.quirks);
+    assert_eq!(loaded.keys, movie.keys);
+    assert_eq!(loaded.hashes, movie.hashes);
+}
+
+#[test]
+fn hashes_follow_their_frame() {
+    let mut movie = Movie::new(1, 2, 3, Quirks::default());
+    movie.keys = vec![1, 2];
+    movie.hashes = vec![(1, 0xAA), (5, 0xBB)];
+    let mut text = Vec::new();
+    movie.write(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.ends_with("k 0001\nh 1 00000000000000aa\nk 0002\nh 5 00000000000000bb\n"));
+}
+
+#[test]
+fn bad_movies_are_rejected() {
+    let read = |text: &str| Movie::read(text.as_bytes()).map_err(|e| e.to_string());
+    assert_eq!(read("chip8-movie 2\nseed 1\n").unwrap_err(), "not a chip8 movie");
+    assert_eq!(read("").unwrap_err(), "not a chip8 movie");
+    assert_eq!(read("chip8-movie 1\nseed xyz\n").unwrap_err(), "movie line 2: bad number");
+    assert_eq!(read("chip8-movie 1\nk 1\nh 60\n").unwrap_err(), "movie line 3: bad number");
+    assert!(read("chip8-movie 1\nquirks jump=2\n").unwrap_err().starts_with("movie line 2:"));
+    assert!(read("chip8-movie 1\ntiming fast\n").unwrap_err().starts_with("movie line 2:"));
+    assert_eq!(read("chip8-movie 1\nspeed 3\n").unwrap_err(), "movie line 2: unknown tag 'speed'");
+    assert!(read("chip8-movie 1\n\nk 0\n").is_ok());
+}
+
+#[test]
+fn state_log_counts_desyncs() {
+    let mut log = StateLog::verifying(vec![(60, 1), (120, 2), (180, 3)]);
+    assert!(log.wants_hash(60) && !log.wants_hash(61));
+    log.on_frame(60, 1);
+    log.on_frame(120, 7);
+    log.on_frame(180, 8);
+    // frames without a stored hash cannot desync
+    log.on_frame(240, 9);
+    assert_eq!(log.desyncs(), 2);
+    assert_eq!(log.into_hashes(), vec![(60, 1), (120, 7), (180, 8), (240, 9)]);
+}
+
+#[test]
+fn player_hands_out_the_keys_frame_by_frame() {
+    let mut headless = Headless::new();
+    let mut player = MoviePlayer::new(&mut headless, vec![1 << 5, 1 << 5 | 1 << 2, 0]);
+    let mut seen = Vec::new();
+    while player.poll_control().is_none() {
+        player.poll_input();
+        let mut events = Vec::new();
+        while let Some(e) = player.poll_key_event() {
+            events.push(e);
+        }
+        seen.push((player.is_key_pressed(5), player.get_pressed_key().cloned(), events));
+        player.end_frame(Screen::new(&[0; DISPLAY_VISIBLE_HEIGHT]));
+    }
+    assert_eq!(seen, vec![
+        (true, Some(5), vec![KeyEvent::Down(5)]),
+        (true, Some(2), vec![KeyEvent::Down(2)]),
+        (false, None, vec![KeyEvent::Up(2), KeyEvent::Up(5)]),
+    ]);
+    // and quits for good at the end
+    assert_eq!(player.poll_control(), Some(Control::Quit));
+}
+
+#[test]
+fn replay_matches_the_recording() {
+    let movie = round_trip(&record(Timing::Ipf));
+    assert_eq!(replay(&movie), 0);
+}
+
+#[test]
+fn other_keys_desync() {
+    let mut movie = round_trip(&record(Timing::Ipf));
+    // the paddle never moves right
+    for k in movie.keys.iter_mut() {
+        *k &= !(1 << 6);
+    }
+    assert!(replay(&movie) > 0);
+}
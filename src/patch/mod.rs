@@ -0,0 +1,326 @@
+use memory::*;
+use utils::*;
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+// IPS and BPS patches, the formats fan fixes and translations of ROMs are
+// shared in. Both are told apart by their header.
+//
+// IPS: "PATCH", records of a 3 byte offset and a 2 byte size followed by
+// the bytes, or of size 0 followed by a 2 byte count and one byte to
+// repeat, then "EOF" and optionally the 3 byte size to truncate to.
+//
+// BPS: "BPS1", the source, target and metadata sizes, the metadata, the
+// actions building the target and the CRC-32s of source, target and patch.
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const IPS_MAX_RECORD: usize = 0xFFFF;
+const BPS_MAGIC: &[u8] = b"BPS1";
+// the three CRC-32s
+const BPS_FOOTER: usize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+}
+
+impl PatchFormat {
+    pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+        if patch.starts_with(IPS_MAGIC) {
+            Some(PatchFormat::Ips)
+        } else if patch.starts_with(BPS_MAGIC) {
+            Some(PatchFormat::Bps)
+        } else {
+            None
+        }
+    }
+
+    // by the extension of a patch file, .ips or .bps
+    pub fn from_path(path: &Path) -> Option<PatchFormat> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "ips" => Some(PatchFormat::Ips),
+            "bps" => Some(PatchFormat::Bps),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn truncated() -> io::Error {
+    invalid("the patch is truncated")
+}
+
+// Nothing larger than the largest memory is ever loaded.
+fn check_target_size(size: usize) -> io::Result<()> {
+    if size > MAX_MEM_SIZE {
+        return Err(invalid(&format!("the patched ROM would be {} bytes, at most {} fit in memory",
+                                    size, MAX_MEM_SIZE)));
+    }
+    Ok(())
+}
+
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => apply_ips(rom, patch),
+        Some(PatchFormat::Bps) => apply_bps(rom, patch),
+        None => Err(invalid("not an IPS or BPS patch")),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.data.len()).ok_or_else(truncated)?;
+        let b = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(b)
+    }
+
+    // big-endian, as IPS has them
+    fn be(&mut self, n: usize) -> io::Result<usize> {
+        Ok(self.bytes(n)?.iter().fold(0, |v, &b| v << 8 | b as usize))
+    }
+
+    // BPS numbers: 7 bits a byte, the last one has the top bit set
+    fn varint(&mut self) -> io::Result<usize> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let b = self.bytes(1)?[0];
+            value = ((b & 0x7F) as usize).checked_mul(shift)
+                .and_then(|v| v.checked_add(value))
+                .ok_or_else(|| invalid("number out of range in the patch"))?;
+            if b & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(|| invalid("number out of range in the patch"))?;
+            value = value.checked_add(shift).ok_or_else(|| invalid("number out of range in the patch"))?;
+        }
+    }
+}
+
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    if !patch.starts_with(IPS_MAGIC) {
+        return Err(invalid("not an IPS patch"));
+    }
+    let mut r = Reader { data: patch, pos: IPS_MAGIC.len() };
+    let mut out = rom.to_vec();
+
+    loop {
+        let head = r.bytes(3)?;
+        if head == IPS_EOF {
+            break;
+        }
+        let offset = head.iter().fold(0, |v, &b| v << 8 | b as usize);
+        let size = r.be(2)?;
+        let (size, fill) = if size == 0 {
+            let count = r.be(2)?;
+            (count, Some(r.bytes(1)?[0]))
+        } else {
+            (size, None)
+        };
+
+        let end = offset + size;
+        check_target_size(end)?;
+        if out.len() < end {
+            out.resize(end, 0);
+        }
+        match fill {
+            Some(b) => out[offset..end].iter_mut().for_each(|o| *o = b),
+            None => out[offset..end].copy_from_slice(r.bytes(size)?),
+        }
+    }
+
+    if r.pos + 3 <= patch.len() {
+        let size = r.be(3)?;
+        out.truncate(size);
+    }
+    Ok(out)
+}
+
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> io::Result<Vec<u8>> {
+    if !patch.starts_with(BPS_MAGIC) || patch.len() < BPS_MAGIC.len() + BPS_FOOTER {
+        return Err(invalid("not a BPS patch"));
+    }
+    let actions_end = patch.len() - BPS_FOOTER;
+    let crc = |at: usize| u32::from_le_bytes([patch[at], patch[at + 1], patch[at + 2], patch[at + 3]]);
+    let (source_crc, target_crc, patch_crc) = (crc(actions_end), crc(actions_end + 4), crc(actions_end + 8));
+
+    if crc32(&patch[..actions_end + 8]) != patch_crc {
+        return Err(invalid("the patch is damaged, its checksum does not match"));
+    }
+    if crc32(rom) != source_crc {
+        return Err(invalid("the patch is for another ROM, the checksum of the ROM does not match"));
+    }
+
+    let mut r = Reader { data: &patch[..actions_end], pos: BPS_MAGIC.len() };
+    let source_size = r.varint()?;
+    let target_size = r.varint()?;
+    let metadata_size = r.varint()?;
+    r.bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(invalid("the patch is for another ROM, the size of the ROM does not match"));
+    }
+    check_target_size(target_size)?;
+
+    let mut out = Vec::with_capacity(target_size);
+    let mut source_rel: usize = 0;
+    let mut target_rel: usize = 0;
+    let out_of_range = || invalid("the patch reads outside of the ROM");
+
+    while r.pos < actions_end {
+        let cmd = r.varint()?;
+        let len = (cmd >> 2) + 1;
+        if out.len() + len > target_size {
+            return Err(invalid("the patch writes past the end of the patched ROM"));
+        }
+        match cmd & 3 {
+            // SourceRead
+            0 => {
+                let at = out.len();
+                out.extend_from_slice(rom.get(at..at + len).ok_or_else(out_of_range)?);
+            },
+            // TargetRead
+            1 => out.extend_from_slice(r.bytes(len)?),
+            // SourceCopy
+            2 => {
+                source_rel = relative(source_rel, r.varint()?).ok_or_else(out_of_range)?;
+                out.extend_from_slice(rom.get(source_rel..source_rel + len).ok_or_else(out_of_range)?);
+                source_rel += len;
+            },
+            // TargetCopy, byte by byte as it may overlap what it writes
+            _ => {
+                target_rel = relative(target_rel, r.varint()?).filter(|&t| t < out.len())
+                    .ok_or_else(out_of_range)?;
+                for _ in 0..len {
+                    let b = out[target_rel];
+                    out.push(b);
+                    target_rel += 1;
+                }
+            },
+        }
+    }
+
+    if out.len() != target_size {
+        return Err(truncated());
+    }
+    if crc32(&out) != target_crc {
+        return Err(invalid("the patched ROM's checksum does not match"));
+    }
+    Ok(out)
+}
+
+// the offsets of SourceCopy and TargetCopy: a sign bit and the distance
+fn relative(base: usize, delta: usize) -> Option<usize> {
+    if delta & 1 != 0 {
+        base.checked_sub(delta >> 1)
+    } else {
+        base.checked_add(delta >> 1)
+    }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let b = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(0x80 | b);
+            return;
+        }
+        out.push(b);
+        value -= 1;
+    }
+}
+
+// Runs of bytes that differ, as (offset, end).
+fn changed_runs(old: &[u8], new: &[u8]) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < new.len() {
+        if old.get(i) == Some(&new[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < new.len() && old.get(i) != Some(&new[i]) {
+            i += 1;
+        }
+        runs.push((start, i));
+    }
+    runs
+}
+
+// An IPS patch turning `old` into `new`, shrinking with the truncation
+// extension if `new` is shorter.
+pub fn create_ips(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut patch = IPS_MAGIC.to_vec();
+    // bytes past the end of `old` always count as changed
+    for (start, end) in changed_runs(old, new) {
+        for chunk_start in (start..end).step_by(IPS_MAX_RECORD) {
+            let chunk = &new[chunk_start..end.min(chunk_start + IPS_MAX_RECORD)];
+            patch.extend_from_slice(&(chunk_start as u32).to_be_bytes()[1..]);
+            patch.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            patch.extend_from_slice(chunk);
+        }
+    }
+
+    patch.extend_from_slice(IPS_EOF);
+    if new.len() < old.len() {
+        patch.extend_from_slice(&(new.len() as u32).to_be_bytes()[1..]);
+    }
+    patch
+}
+
+// A BPS patch turning `old` into `new`: what is unchanged is read from the
+// source, the rest from the patch.
+pub fn create_bps(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut patch = BPS_MAGIC.to_vec();
+    push_varint(&mut patch, old.len());
+    push_varint(&mut patch, new.len());
+    push_varint(&mut patch, 0);
+
+    let mut pos = 0;
+    for (start, end) in changed_runs(old, new).into_iter().chain(Some((new.len(), new.len()))) {
+        if start > pos {
+            push_varint(&mut patch, (start - pos - 1) << 2);
+        }
+        if end > start {
+            push_varint(&mut patch, (end - start - 1) << 2 | 1);
+            patch.extend_from_slice(&new[start..end]);
+        }
+        pos = end;
+    }
+
+    patch.extend_from_slice(&crc32(old).to_le_bytes());
+    patch.extend_from_slice(&crc32(new).to_le_bytes());
+    let crc = crc32(&patch);
+    patch.extend_from_slice(&crc.to_le_bytes());
+    patch
+}
+
+pub fn create_patch(format: PatchFormat, old: &[u8], new: &[u8]) -> Vec<u8> {
+    match format {
+        PatchFormat::Ips => create_ips(old, new),
+        PatchFormat::Bps => create_bps(old, new),
+    }
+}
+
+// Patches picked up next to a ROM: the ROM's path with .bps or .ips in
+// place of its extension, BPS first as it is checked.
+pub fn patch_candidates(rom: &Path) -> Vec<PathBuf> {
+    vec![rom.with_extension("bps"), rom.with_extension("ips")]
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,118 @@
+use super::*;
+
+fn roms() -> (Vec<u8>, Vec<u8>) {
+    let old: Vec<u8> = (0..300).map(|i| (i * 7) as u8).collect();
+    let mut new = old.clone();
+    new[0] = 0xAA;
+    new[100..104].copy_from_slice(b"TEXT");
+    new.extend_from_slice(&[0, 0, 1]);
+    (old, new)
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn ips_round_trip() {
+    let (old, new) = roms();
+    let patch = create_ips(&old, &new);
+    assert_eq!(PatchFormat::detect(&patch), Some(PatchFormat::Ips));
+    assert_eq!(apply_patch(&old, &patch).unwrap(), new);
+}
+
+#[test]
+fn ips_truncates_a_shrinking_rom() {
+    let (old, _) = roms();
+    let new = old[..200].to_vec();
+    assert_eq!(apply_ips(&old, &create_ips(&old, &new)).unwrap(), new);
+}
+
+#[test]
+fn ips_run_length_record() {
+    // 4 times 0x55 at 0002
+    let patch = b"PATCH\x00\x00\x02\x00\x00\x00\x04\x55EOF";
+    assert_eq!(apply_ips(&[0; 4], patch).unwrap(), vec![0, 0, 0x55, 0x55, 0x55, 0x55]);
+}
+
+#[test]
+fn ips_truncated_record_is_an_error() {
+    assert!(apply_ips(&[0; 4], b"PATCH\x00\x00\x02\x00\x04\x55").is_err());
+    assert!(apply_ips(&[0; 4], b"PATCH").is_err());
+}
+
+#[test]
+fn bps_round_trip() {
+    let (old, new) = roms();
+    let patch = create_bps(&old, &new);
+    assert_eq!(PatchFormat::detect(&patch), Some(PatchFormat::Bps));
+    assert_eq!(apply_patch(&old, &patch).unwrap(), new);
+
+    let shorter = old[50..].to_vec();
+    assert_eq!(apply_bps(&old, &create_bps(&old, &shorter)).unwrap(), shorter);
+}
+
+#[test]
+fn bps_checks_the_source() {
+    let (old, new) = roms();
+    let patch = create_bps(&old, &new);
+    let mut other = old.clone();
+    other[5] ^= 1;
+    let err = apply_bps(&other, &patch).unwrap_err();
+    assert!(err.to_string().contains("another ROM"));
+}
+
+#[test]
+fn bps_checks_itself() {
+    let (old, new) = roms();
+    let mut patch = create_bps(&old, &new);
+    let at = patch.len() / 2;
+    patch[at] ^= 0x10;
+    let err = apply_bps(&old, &patch).unwrap_err();
+    assert!(err.to_string().contains("damaged"));
+}
+
+// A patch by another tool: SourceCopy and an overlapping TargetCopy.
+#[test]
+fn bps_copy_actions() {
+    let old = b"ABCD".to_vec();
+    let mut patch = b"BPS1".to_vec();
+    push_varint(&mut patch, 4);
+    push_varint(&mut patch, 7);
+    push_varint(&mut patch, 0);
+    // SourceCopy 2 bytes from 2: "CD"
+    push_varint(&mut patch, (1 << 2) | 2);
+    push_varint(&mut patch, 2 << 1);
+    // TargetCopy 5 bytes from 0: "CDCDC"
+    push_varint(&mut patch, (4 << 2) | 3);
+    push_varint(&mut patch, 0);
+    patch.extend_from_slice(&crc32(&old).to_le_bytes());
+    patch.extend_from_slice(&crc32(b"CDCDCDC").to_le_bytes());
+    let crc = crc32(&patch);
+    patch.extend_from_slice(&crc.to_le_bytes());
+
+    assert_eq!(apply_bps(&old, &patch).unwrap(), b"CDCDCDC".to_vec());
+}
+
+#[test]
+fn varints_round_trip() {
+    for &n in &[0, 1, 127, 128, 129, 16511, 16512, 65536, 1 << 40] {
+        let mut data = Vec::new();
+        push_varint(&mut data, n);
+        assert_eq!(Reader { data: &data, pos: 0 }.varint().unwrap(), n);
+    }
+}
+
+#[test]
+fn oversized_target_is_rejected() {
+    let patch = b"PATCH\xFF\xFF\x00\x00\x00\x00\xFF\x01EOF";
+    assert!(apply_ips(&[], patch).is_err());
+}
+
+#[test]
+fn unknown_format() {
+    assert!(apply_patch(&[], b"UPS1").is_err());
+    assert_eq!(PatchFormat::from_path(Path::new("fix.BPS")), Some(PatchFormat::Bps));
+    assert_eq!(PatchFormat::from_path(Path::new("fix.ups")), None);
+}
@@ -0,0 +1,111 @@
+use memory::*;
+
+use std::str::FromStr;
+
+// How long a pixel keeps glowing after the emulated VRAM switched it off.
+// Only the presentation layer looks at this, VRAM itself is never touched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Persistence {
+    Off,
+    // brightness decays linearly over the given number of frames
+    Fade(u8),
+    // pixel stays fully lit if it was lit in any of the last N frames
+    MaxOfLast(u8),
+}
+
+impl FromStr for Persistence {
+    type Err = String;
+
+    // accepts "off", "fade:N" and "max:N"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "off" {
+            return Ok(Persistence::Off);
+        }
+
+        let mut it = s.splitn(2, ':');
+        let mode = it.next().unwrap_or("");
+        let frames = it.next()
+            .and_then(|n| n.parse::<u8>().ok())
+            .filter(|&n| n > 0)
+            .ok_or(format!("invalid persistence frame count in '{}'", s))?;
+
+        match mode {
+            "fade" => Ok(Persistence::Fade(frames)),
+            "max" => Ok(Persistence::MaxOfLast(frames)),
+            _ => Err(format!("unknown persistence mode '{}'", mode)),
+        }
+    }
+}
+
+pub struct Phosphor {
+    mode: Persistence,
+    // remaining glow per visible pixel, in frames
+    levels: [[u8; DISPLAY_VISIBLE_WIDTH]; DISPLAY_VISIBLE_HEIGHT],
+    // pixels lit in the most recently latched video buffer
+    lit: [[bool; DISPLAY_VISIBLE_WIDTH]; DISPLAY_VISIBLE_HEIGHT],
+}
+
+impl Phosphor {
+    pub fn new(mode: Persistence) -> Self {
+        Phosphor {
+            mode,
+            levels: [[0; DISPLAY_VISIBLE_WIDTH]; DISPLAY_VISIBLE_HEIGHT],
+            lit: [[false; DISPLAY_VISIBLE_WIDTH]; DISPLAY_VISIBLE_HEIGHT],
+        }
+    }
+
+    pub fn mode(&self) -> Persistence {
+        self.mode
+    }
+
+    fn frames(&self) -> u8 {
+        match self.mode {
+            Persistence::Off => 1,
+            Persistence::Fade(n) | Persistence::MaxOfLast(n) => n,
+        }
+    }
+
+    // Takes the screen as it is at the end of a frame, called once per
+    // frame. Like the VIP's display, which shows the frame buffer once
+    // every vblank, it never sees a pixel that is drawn and erased again
+    // within one frame; persistence only bridges pixels that are off for
+    // whole frames.
+    pub fn latch(&mut self, buf: Screen) {
+        let frames = self.frames();
+
+        for y in 0..DISPLAY_VISIBLE_HEIGHT {
+            for x in 0..DISPLAY_VISIBLE_WIDTH {
+                let on = buf.pixel(x, y);
+                self.lit[y][x] = on;
+                if on {
+                    self.levels[y][x] = frames;
+                }
+            }
+        }
+    }
+
+    // Ages every pixel that is currently dark by one frame.
+    pub fn end_frame(&mut self) {
+        for (levels, lit) in self.levels.iter_mut().zip(self.lit.iter()) {
+            for (level, &on) in levels.iter_mut().zip(lit.iter()) {
+                if !on {
+                    *level = level.saturating_sub(1);
+                }
+            }
+        }
+    }
+
+    // Brightness of a visible pixel, 0 is dark and 255 is fully lit.
+    pub fn intensity(&self, x: usize, y: usize) -> u8 {
+        let level = self.levels[y][x] as u32;
+
+        match self.mode {
+            Persistence::Off => if self.lit[y][x] { 255 } else { 0 },
+            Persistence::Fade(n) => (level * 255 / n as u32) as u8,
+            Persistence::MaxOfLast(_) => if level > 0 { 255 } else { 0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,83 @@
+use super::*;
+
+// a screen with only pixel (x, y) lit, or nothing lit
+fn screen(lit: Option<(usize, usize)>) -> [DisplayRow; DISPLAY_VISIBLE_HEIGHT] {
+    let mut rows = [0; DISPLAY_VISIBLE_HEIGHT];
+    if let Some((x, y)) = lit {
+        rows[y] = 1 << (DISPLAY_VISIBLE_WIDTH - 1 - x);
+    }
+    rows
+}
+
+// Latches one frame per entry and returns the brightness of (3, 2) after
+// each of them.
+fn frames(mode: Persistence, lit: &[bool]) -> Vec<u8> {
+    let mut phosphor = Phosphor::new(mode);
+    lit.iter().map(|&on| {
+        let rows = screen(if on { Some((3, 2)) } else { None });
+        phosphor.latch(Screen::new(&rows));
+        phosphor.end_frame();
+        phosphor.intensity(3, 2)
+    }).collect()
+}
+
+#[test]
+fn parse() {
+    assert_eq!("off".parse(), Ok(Persistence::Off));
+    assert_eq!("fade:4".parse(), Ok(Persistence::Fade(4)));
+    assert_eq!("max:2".parse(), Ok(Persistence::MaxOfLast(2)));
+    assert!("fade".parse::<Persistence>().is_err());
+    assert!("fade:0".parse::<Persistence>().is_err());
+    assert!("fade:300".parse::<Persistence>().is_err());
+    assert_eq!("glow:3".parse::<Persistence>(), Err("unknown persistence mode 'glow'".to_string()));
+}
+
+#[test]
+fn off_follows_the_screen() {
+    assert_eq!(frames(Persistence::Off, &[true, false, true]), vec![255, 0, 255]);
+}
+
+#[test]
+fn fade_decays_linearly() {
+    assert_eq!(frames(Persistence::Fade(4), &[true, false, false, false, false]),
+               vec![255, 191, 127, 63, 0]);
+    // lit again, back to full brightness
+    assert_eq!(frames(Persistence::Fade(2), &[true, false, true]), vec![255, 127, 255]);
+}
+
+#[test]
+fn max_of_last_holds_the_pixel() {
+    assert_eq!(frames(Persistence::MaxOfLast(3), &[true, false, false, false, true]),
+               vec![255, 255, 255, 0, 255]);
+}
+
+#[test]
+fn other_pixels_stay_dark() {
+    let mut phosphor = Phosphor::new(Persistence::Fade(3));
+    let rows = screen(Some((3, 2)));
+    phosphor.latch(Screen::new(&rows));
+    assert_eq!(phosphor.intensity(4, 2), 0);
+    assert_eq!(phosphor.intensity(3, 3), 0);
+}
+
+// A sprite drawn and erased within one frame is never latched, the frame
+// is sampled at its end.
+#[test]
+fn pixels_between_frames_are_not_seen() {
+    let mut phosphor = Phosphor::new(Persistence::MaxOfLast(3));
+    let mut display = Display::new();
+    display.apply_sprites(3, 2, &[0x80], false);
+    display.apply_sprites(3, 2, &[0x80], false);
+    phosphor.latch(display.get_video_buf().unwrap());
+    phosphor.end_frame();
+    assert_eq!(phosphor.intensity(3, 2), 0);
+
+    // drawn and left lit for a frame, it is held
+    display.apply_sprites(3, 2, &[0x80], false);
+    phosphor.latch(display.get_video_buf().unwrap());
+    phosphor.end_frame();
+    display.apply_sprites(3, 2, &[0x80], false);
+    phosphor.latch(display.get_video_buf().unwrap());
+    phosphor.end_frame();
+    assert_eq!(phosphor.intensity(3, 2), 255);
+}
@@ -0,0 +1,193 @@
+extern crate serde_json;
+
+use memory::*;
+use stack::*;
+use quirks::*;
+use utils::*;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+// The bundled database is a subset of the community chip-8-database
+// (https://github.com/chip-8/chip-8-database) covering the ROMs in res/.
+// A full checkout of its database/ directory can be loaded instead.
+const BUNDLED_PROGRAMS: &str = include_str!("../../res/chip-8-database/programs.json");
+const BUNDLED_HASHES: &str = include_str!("../../res/chip-8-database/sha1-hashes.json");
+const BUNDLED_PLATFORMS: &str = include_str!("../../res/chip-8-database/platforms.json");
+
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct DbQuirks {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    jump: Option<bool>,
+    logic: Option<bool>,
+    wrap: Option<bool>,
+    vblank: Option<bool>,
+}
+
+impl DbQuirks {
+    // Unlisted quirks are off, as in the database's quirks.json.
+    fn to_quirks(&self, platform: &str) -> Quirks {
+        Quirks {
+            shift: self.shift.unwrap_or(false),
+            memory_increment_by_x: self.memory_increment_by_x.unwrap_or(false),
+            memory_leave_i_unchanged: self.memory_leave_i_unchanged.unwrap_or(false),
+            jump: self.jump.unwrap_or(false),
+            logic: self.logic.unwrap_or(false),
+            memory_wrap: wraps_memory(platform),
+            wrap: self.wrap.unwrap_or(false),
+            vblank: self.vblank.unwrap_or(false),
+            key_held: false,
+        }
+    }
+
+    fn override_with(&self, o: &DbQuirks) -> DbQuirks {
+        DbQuirks {
+            shift: o.shift.or(self.shift),
+            memory_increment_by_x: o.memory_increment_by_x.or(self.memory_increment_by_x),
+            memory_leave_i_unchanged: o.memory_leave_i_unchanged.or(self.memory_leave_i_unchanged),
+            jump: o.jump.or(self.jump),
+            logic: o.logic.or(self.logic),
+            wrap: o.wrap.or(self.wrap),
+            vblank: o.vblank.or(self.vblank),
+        }
+    }
+}
+
+// The VIP and the interpreters derived from it wrap addresses at the end
+// of memory, so does XO-CHIP. The database has no quirk for it.
+fn wraps_memory(platform: &str) -> bool {
+    matches!(platform, "originalChip8" | "hybridVIP" | "chip8x" | "xochip")
+}
+
+fn memory_size(platform: &str) -> usize {
+    if platform == "xochip" { MAX_MEM_SIZE } else { MEM_SIZE }
+}
+
+fn stack_depth(platform: &str) -> usize {
+    match platform {
+        "originalChip8" | "hybridVIP" | "chip8x" => VIP_STACK_DEPTH,
+        _ => SCHIP_STACK_DEPTH,
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Platform {
+    id: String,
+    name: String,
+    default_tickrate: u32,
+    quirks: DbQuirks,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    file: Option<String>,
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, DbQuirks>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Program {
+    title: String,
+    roms: HashMap<String, Rom>,
+}
+
+// Recommended settings for one ROM image.
+#[derive(Clone, Debug)]
+pub struct RomInfo {
+    pub sha1: String,
+    pub title: String,
+    pub file: Option<String>,
+    pub platform: String,
+    pub platform_name: String,
+    pub quirks: Quirks,
+    pub tickrate: u32,
+    // in bytes
+    pub memory_size: usize,
+    // return addresses
+    pub stack_depth: usize,
+    // game action ("up", "a", "player2Down", ...) to CHIP-8 key
+    pub keys: HashMap<String, u8>,
+}
+
+pub struct RomDb {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>,
+    platforms: Vec<Platform>,
+}
+
+fn json_err(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+impl RomDb {
+    pub fn bundled() -> Self {
+        RomDb {
+            programs: serde_json::from_str(BUNDLED_PROGRAMS).unwrap(),
+            hashes: serde_json::from_str(BUNDLED_HASHES).unwrap(),
+            platforms: serde_json::from_str(BUNDLED_PLATFORMS).unwrap(),
+        }
+    }
+
+    // Loads programs.json, sha1-hashes.json and platforms.json from `dir`.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let open = |name: &str| File::open(dir.join(name));
+
+        Ok(RomDb {
+            programs: serde_json::from_reader(open("programs.json")?).map_err(json_err)?,
+            hashes: serde_json::from_reader(open("sha1-hashes.json")?).map_err(json_err)?,
+            platforms: serde_json::from_reader(open("platforms.json")?).map_err(json_err)?,
+        })
+    }
+
+    pub fn lookup_rom(&self, rom: &[u8]) -> Option<RomInfo> {
+        self.lookup(&sha1_hex(rom))
+    }
+
+    pub fn lookup(&self, sha1: &str) -> Option<RomInfo> {
+        let sha1 = sha1.to_lowercase();
+        let program = self.programs.get(*self.hashes.get(&sha1)?)?;
+        let rom = program.roms.get(&sha1)?;
+
+        // the first listed platform is the recommended one, ROMs that only
+        // run with tweaks list their platforms under quirkyPlatforms
+        let (platform_id, overrides) = match rom.platforms.first() {
+            Some(p) => (p.clone(), DbQuirks::default()),
+            None => {
+                let mut quirky: Vec<_> = rom.quirky_platforms.iter().collect();
+                quirky.sort_by(|a, b| a.0.cmp(b.0));
+                let (p, q) = quirky.first()?;
+                ((*p).clone(), (*q).clone())
+            },
+        };
+        let platform = self.platforms.iter().find(|p| p.id == platform_id)?;
+
+        Some(RomInfo {
+            sha1,
+            title: program.title.clone(),
+            file: rom.file.clone(),
+            platform: platform.id.clone(),
+            platform_name: platform.name.clone(),
+            quirks: platform.quirks.override_with(&overrides).to_quirks(&platform.id),
+            tickrate: rom.tickrate.unwrap_or(platform.default_tickrate),
+            memory_size: memory_size(&platform.id),
+            stack_depth: stack_depth(&platform.id),
+            keys: rom.keys.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
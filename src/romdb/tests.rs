@@ -0,0 +1,61 @@
+use super::*;
+use romfile::*;
+
+use std::fs;
+use std::path::PathBuf;
+
+fn res() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("res")
+}
+
+// The bundled database has an entry for every ROM in res/, also those in
+// the archive.
+#[test]
+fn every_bundled_rom_is_known() {
+    let db = RomDb::bundled();
+    let mut roms = Vec::new();
+    for entry in fs::read_dir(res()).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_file() && path.extension().is_none_or(|e| e != "zip") {
+            roms.push((path.display().to_string(), fs::read(&path).unwrap()));
+        }
+    }
+    let archive = res().join("c8games.zip");
+    for e in list_archive(&archive).unwrap() {
+        let rom = load_rom(&RomSpec::Entry(archive.clone(), e.name.clone())).unwrap();
+        roms.push((format!("c8games.zip:{}", e.name), rom));
+    }
+    assert!(roms.len() > 40);
+
+    let unknown: Vec<&str> = roms.iter()
+        .filter(|(_, rom)| db.lookup_rom(rom).is_none())
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(unknown, Vec::<&str>::new());
+}
+
+#[test]
+fn vip_rom_settings() {
+    let info = RomDb::bundled().lookup_rom(include_bytes!("../../res/BRIX")).unwrap();
+    assert_eq!(info.title, "Brix");
+    assert_eq!(info.platform, "originalChip8");
+    assert_eq!(info.tickrate, 15);
+    assert_eq!(info.quirks, Quirks::preset("vip").unwrap());
+    assert_eq!(info.memory_size, MEM_SIZE);
+    assert_eq!(info.stack_depth, VIP_STACK_DEPTH);
+}
+
+#[test]
+fn lookup_ignores_case() {
+    let db = RomDb::bundled();
+    let sha1 = sha1_hex(include_bytes!("../../res/PONG"));
+    assert!(db.lookup(&sha1.to_uppercase()).is_some());
+    assert!(db.lookup("0000000000000000000000000000000000000000").is_none());
+}
+
+#[test]
+fn load_from_a_directory() {
+    let db = RomDb::load(&res().join("chip-8-database")).unwrap();
+    assert!(db.lookup_rom(include_bytes!("../../res/TETRIS")).is_some());
+    assert!(RomDb::load(&res().join("nowhere")).is_err());
+}
@@ -0,0 +1,156 @@
+extern crate zip;
+
+use memory::*;
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+// What a ROM argument on the command line refers to:
+//
+//   game.ch8           a plain ROM file
+//   games.zip          the entries of an archive
+//   games.zip:PONG     one entry of an archive
+#[derive(Clone, Debug, PartialEq)]
+pub enum RomSpec {
+    File(PathBuf),
+    Archive(PathBuf),
+    Entry(PathBuf, String),
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().map(|e| e.eq_ignore_ascii_case("zip")).unwrap_or(false)
+}
+
+impl RomSpec {
+    pub fn parse(s: &str) -> Self {
+        // split at the last ':' after ".zip" so that drive letters and
+        // colons in directory names keep working
+        let lower = s.to_lowercase();
+        if let Some(i) = lower.rfind(".zip:") {
+            let (archive, entry) = s.split_at(i + 4);
+            return RomSpec::Entry(PathBuf::from(archive), entry[1..].to_string());
+        }
+
+        let path = PathBuf::from(s);
+        if is_zip(&path) {
+            RomSpec::Archive(path)
+        } else {
+            RomSpec::File(path)
+        }
+    }
+}
+
+impl RomSpec {
+    // File name of the ROM, without directories or archive name.
+    pub fn name(&self) -> String {
+        match *self {
+            RomSpec::File(ref path) | RomSpec::Archive(ref path) => {
+                path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+            },
+            RomSpec::Entry(_, ref entry) => {
+                entry.rsplit('/').next().unwrap_or(entry).to_string()
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub size: u64,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn zip_err(path: &Path, e: zip::result::ZipError) -> io::Error {
+    match e {
+        zip::result::ZipError::Io(e) => e,
+        e => invalid(format!("{}: {}", path.display(), e)),
+    }
+}
+
+fn open_archive(path: &Path) -> io::Result<zip::ZipArchive<File>> {
+    zip::ZipArchive::new(File::open(path)?).map_err(|e| zip_err(path, e))
+}
+
+// Files in the archive, directories are skipped.
+pub fn list_archive(path: &Path) -> io::Result<Vec<ArchiveEntry>> {
+    let mut archive = open_archive(path)?;
+    let mut entries = Vec::with_capacity(archive.len());
+
+    for i in 0..archive.len() {
+        let f = archive.by_index(i).map_err(|e| zip_err(path, e))?;
+        if f.is_dir() {
+            continue;
+        }
+        entries.push(ArchiveEntry {
+            name: f.name().to_string(),
+            size: f.size(),
+        });
+    }
+
+    Ok(entries)
+}
+
+// The largest memory is the limit here, whether the image fits the memory
+// actually chosen is checked when it is loaded.
+const MAX_IMAGE_SIZE: usize = MAX_MEM_SIZE - EXE_START_ADDR;
+
+fn check_size(name: &str, size: u64) -> io::Result<()> {
+    if size > MAX_IMAGE_SIZE as u64 {
+        return Err(invalid(format!("{} is {} bytes, at most {} fit in memory",
+                                   name, size, MAX_IMAGE_SIZE)));
+    }
+    Ok(())
+}
+
+fn find_entry(path: &Path, entry: &str) -> io::Result<String> {
+    let names: Vec<String> = list_archive(path)?.into_iter().map(|e| e.name).collect();
+
+    names.iter().find(|n| *n == entry)
+        .or_else(|| names.iter().find(|n| n.eq_ignore_ascii_case(entry)))
+        .cloned()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound,
+                                      format!("{} has no entry {}", path.display(), entry)))
+}
+
+// Reads at most one byte more than fits, so oversized input is detected
+// even when the reported size is wrong.
+fn read_exe<R: Read>(name: &str, size: u64, r: R) -> io::Result<Vec<u8>> {
+    check_size(name, size)?;
+
+    let mut exe = Vec::with_capacity(size as usize);
+    r.take(MAX_IMAGE_SIZE as u64 + 1).read_to_end(&mut exe)?;
+    check_size(name, exe.len() as u64)?;
+    Ok(exe)
+}
+
+// Reads the ROM image, rejecting anything that would not fit in memory.
+// Entry names are matched case-insensitively when there is no exact match.
+pub fn load_rom(spec: &RomSpec) -> io::Result<Vec<u8>> {
+    match *spec {
+        RomSpec::File(ref path) => {
+            let f = File::open(path)?;
+            let size = f.metadata()?.len();
+            read_exe(&path.display().to_string(), size, f)
+        },
+        RomSpec::Archive(ref path) => {
+            Err(invalid(format!("{} is an archive, run one of its entries as {}:NAME",
+                                path.display(), path.display())))
+        },
+        RomSpec::Entry(ref path, ref entry) => {
+            let name = find_entry(path, entry)?;
+            let mut archive = open_archive(path)?;
+            let f = archive.by_name(&name).map_err(|e| zip_err(path, e))?;
+            let size = f.size();
+            read_exe(&format!("{}:{}", path.display(), name), size, f)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,78 @@
+use super::*;
+
+use std::env;
+use std::fs;
+
+fn games() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("res").join("c8games.zip")
+}
+
+#[test]
+fn parse_specs() {
+    assert_eq!(RomSpec::parse("res/PONG"), RomSpec::File(PathBuf::from("res/PONG")));
+    assert_eq!(RomSpec::parse("res/c8games.zip"), RomSpec::Archive(PathBuf::from("res/c8games.zip")));
+    assert_eq!(RomSpec::parse("a.ZIP"), RomSpec::Archive(PathBuf::from("a.ZIP")));
+    assert_eq!(RomSpec::parse("res/c8games.zip:PONG"),
+               RomSpec::Entry(PathBuf::from("res/c8games.zip"), "PONG".to_string()));
+    assert_eq!(RomSpec::parse("a.zip:games/PONG"),
+               RomSpec::Entry(PathBuf::from("a.zip"), "games/PONG".to_string()));
+    // colons elsewhere belong to the path
+    assert_eq!(RomSpec::parse("C:/roms/PONG"), RomSpec::File(PathBuf::from("C:/roms/PONG")));
+    assert_eq!(RomSpec::parse("d:1/a.zip:PONG"),
+               RomSpec::Entry(PathBuf::from("d:1/a.zip"), "PONG".to_string()));
+    assert_eq!(RomSpec::parse("x.zip:y/b.zip:PONG"),
+               RomSpec::Entry(PathBuf::from("x.zip:y/b.zip"), "PONG".to_string()));
+}
+
+#[test]
+fn names() {
+    assert_eq!(RomSpec::parse("res/PONG").name(), "PONG");
+    assert_eq!(RomSpec::parse("res/c8games.zip").name(), "c8games.zip");
+    assert_eq!(RomSpec::parse("a.zip:games/PONG").name(), "PONG");
+}
+
+#[test]
+fn list_entries() {
+    let entries = list_archive(&games()).unwrap();
+    assert_eq!(entries.len(), 23);
+    let pong = entries.iter().find(|e| e.name == "PONG").unwrap();
+    assert_eq!(pong.size, 246);
+}
+
+#[test]
+fn load_entries() {
+    let pong = load_rom(&RomSpec::Entry(games(), "PONG".to_string())).unwrap();
+    assert_eq!(pong, include_bytes!("../../res/PONG").to_vec());
+    // with another case only if there is no exact match
+    assert_eq!(load_rom(&RomSpec::Entry(games(), "pong".to_string())).unwrap(), pong);
+}
+
+#[test]
+fn missing_entry() {
+    let err = load_rom(&RomSpec::Entry(games(), "NOPE".to_string())).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert!(err.to_string().ends_with("has no entry NOPE"));
+    assert!(load_rom(&RomSpec::Entry(PathBuf::from("/nonexistent.zip"), "PONG".to_string())).is_err());
+}
+
+#[test]
+fn archive_is_not_a_rom() {
+    let err = load_rom(&RomSpec::Archive(games())).unwrap_err();
+    assert!(err.to_string().contains("is an archive"));
+}
+
+#[test]
+fn oversized_roms_are_rejected() {
+    let path = env::temp_dir().join(format!("chip8-romfile-test-{}.ch8", ::std::process::id()));
+    fs::write(&path, vec![0u8; MAX_IMAGE_SIZE + 1]).unwrap();
+    let result = load_rom(&RomSpec::File(path.clone()));
+    fs::remove_file(&path).unwrap();
+    let err = result.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("at most 65024 fit in memory"));
+
+    // also when the size given up front is wrong
+    let data = vec![0u8; MAX_IMAGE_SIZE + 10];
+    assert!(read_exe("rom", 10, &data[..]).is_err());
+    assert_eq!(read_exe("rom", 10, &data[..MAX_IMAGE_SIZE]).unwrap().len(), MAX_IMAGE_SIZE);
+}
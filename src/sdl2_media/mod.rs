@@ -2,129 +2,431 @@ extern crate sdl2;
 
 use sdl2_media::sdl2::pixels::Color;
 use sdl2_media::sdl2::event::Event;
-use sdl2_media::sdl2::rect::Point;
+use sdl2_media::sdl2::rect::{Point, Rect};
 use sdl2_media::sdl2::keyboard::Keycode;
+use sdl2_media::sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 
 use media_if::*;
+use memory::*;
+use phosphor::*;
+use capture::*;
+
+use std::collections::HashMap;
+
+// Keys the emulator itself listens to, they cannot be bound: quit, reset,
+// pause, recording, screenshot and the speed.
+const HOTKEYS: [Keycode; 7] = [Keycode::Escape, Keycode::F5, Keycode::F6, Keycode::F11,
+                               Keycode::F12, Keycode::Minus, Keycode::Equals];
+
+// Host key to CHIP-8 key bindings. By default keys 0-9 and A-F map to the
+// CHIP-8 key of the same name.
+#[derive(Clone)]
+pub struct Keymap {
+    map: HashMap<Keycode, u8>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let keys = [Keycode::Num0, Keycode::Num1, Keycode::Num2, Keycode::Num3,
+                    Keycode::Num4, Keycode::Num5, Keycode::Num6, Keycode::Num7,
+                    Keycode::Num8, Keycode::Num9, Keycode::A, Keycode::B,
+                    Keycode::C, Keycode::D, Keycode::E, Keycode::F];
+
+        Keymap {
+            map: keys.iter().enumerate().map(|(i, &k)| (k, i as u8)).collect(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn bind(&mut self, kc: Keycode, key: u8) {
+        self.map.insert(kc, key & 0xF);
+    }
+
+    // Binds a chip-8-database game action ("up", "a", "player2Down", ...)
+    // to a host key next to the default bindings.
+    pub fn bind_action(&mut self, action: &str, key: u8) -> bool {
+        let kc = match action {
+            "up" => Keycode::Up,
+            "down" => Keycode::Down,
+            "left" => Keycode::Left,
+            "right" => Keycode::Right,
+            "a" => Keycode::Space,
+            "b" => Keycode::LShift,
+            "player2Up" => Keycode::W,
+            "player2Down" => Keycode::S,
+            "player2Left" => Keycode::Q,
+            "player2Right" => Keycode::R,
+            "player2A" => Keycode::Tab,
+            _ => return false,
+        };
+
+        self.bind(kc, key);
+        true
+    }
+
+    // "HOST=KEY,...", host keys by their SDL name and CHIP-8 keys in hex,
+    // e.g. "W=5,S=8,Space=6"
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        for item in spec.split(',').filter(|i| !i.is_empty()) {
+            let mut it = item.splitn(2, '=');
+            let host = it.next().unwrap_or("");
+            let kc = Keycode::from_name(host)
+                .ok_or_else(|| format!("unknown host key '{}'", host))?;
+            if HOTKEYS.contains(&kc) {
+                return Err(format!("host key '{}' is a hotkey of the emulator, it cannot be bound", host));
+            }
+            let key = it.next()
+                .and_then(|k| u8::from_str_radix(k, 16).ok())
+                .filter(|&k| k < 16)
+                .ok_or_else(|| format!("invalid key binding '{}', expected HOST=0-F", item))?;
+
+            self.bind(kc, key);
+        }
+
+        Ok(())
+    }
+
+    fn get(&self, kc: Keycode) -> Option<u8> {
+        self.map.get(&kc).cloned()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowSettings {
+    // size of one CHIP-8 pixel in screen pixels
+    pub scale: u32,
+    pub fullscreen: bool,
+    pub palette: Palette,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            scale: 10,
+            fullscreen: false,
+            palette: Palette::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioSettings {
+    pub enabled: bool,
+    // 0.0 - 1.0
+    pub volume: f32,
+    // buzzer frequency in Hz
+    pub tone: u32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            enabled: true,
+            volume: 0.25,
+            tone: 440,
+        }
+    }
+}
+
+struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            *x = if self.phase <= 0.5 { self.volume } else { -self.volume };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+fn open_beeper(sdl_ctx: &sdl2::Sdl, audio: &AudioSettings) -> Result<AudioDevice<SquareWave>, String> {
+    let desired = AudioSpecDesired {
+        freq: Some(44100),
+        channels: Some(1),
+        samples: None,
+    };
+
+    sdl_ctx.audio()?.open_playback(None, &desired, |spec| SquareWave {
+        phase_inc: audio.tone as f32 / spec.freq as f32,
+        phase: 0.0,
+        volume: audio.volume.clamp(0.0, 1.0),
+    })
+}
 
 pub struct Sdl2Be {
     _sdl_ctx: sdl2::Sdl,
     _video_ss: sdl2::VideoSubsystem,
     canvas: sdl2::render::WindowCanvas,
     ev: sdl2::EventPump,
+    beeper: Option<AudioDevice<SquareWave>>,
+    beeping: bool,
+    palette: Palette,
+    // quit, pause, reset and speed hotkeys seen by poll_input
+    controls: ControlQueue,
     keypad: [u8; 16],
+    edges: KeyEdges,
+    keymap: Keymap,
+    phosphor: Phosphor,
+    capture: Capture,
+    last_frame: [DisplayRow; DISPLAY_VISIBLE_HEIGHT],
+    // runs of lit pixels, kept to not allocate on every draw
+    runs: Vec<Rect>,
+    frames: u64,
+    max_frames: Option<u64>,
+}
+
+impl Default for Sdl2Be {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Sdl2Be {
     pub fn new() -> Self {
+        Self::with_settings(&WindowSettings::default(), &AudioSettings::default())
+    }
+
+    pub fn with_settings(settings: &WindowSettings, audio: &AudioSettings) -> Self {
         let sdl_context = sdl2::init().unwrap();
         let video_subsystem = sdl_context.video().unwrap();
-        let window = video_subsystem.window("rust-sdl2 demo", 640, 320)
-            .position_centered()
-            .build()
-        .unwrap();
+        let mut builder = video_subsystem.window("rust-sdl2 demo",
+                                                 DISPLAY_VISIBLE_WIDTH as u32 * settings.scale,
+                                                 DISPLAY_VISIBLE_HEIGHT as u32 * settings.scale);
+        builder.position_centered();
+        if settings.fullscreen {
+            builder.fullscreen_desktop();
+        }
+        let window = builder.build().unwrap();
  
         let mut canvas = window.into_canvas().build().unwrap();
-        canvas.set_scale(10.0, 10.0).unwrap();
+        canvas.set_logical_size(DISPLAY_VISIBLE_WIDTH as u32, DISPLAY_VISIBLE_HEIGHT as u32).unwrap();
         let event_pump = sdl_context.event_pump().unwrap();
 
+        // no sound is better than no emulator, e.g. on machines without audio
+        let beeper = if audio.enabled {
+            open_beeper(&sdl_context, audio)
+                .map_err(|e| println!("audio disabled: {}", e))
+                .ok()
+        } else {
+            None
+        };
+
         Sdl2Be {
             _sdl_ctx: sdl_context,
             _video_ss: video_subsystem,
-            canvas: canvas,
+            canvas,
             ev: event_pump, 
+            beeper,
+            beeping: false,
+            palette: settings.palette,
+            controls: ControlQueue::new(),
             keypad: [0; 16],
+            edges: KeyEdges::default(),
+            keymap: Keymap::default(),
+            phosphor: Phosphor::new(Persistence::Off),
+            capture: Capture::default(),
+            last_frame: [0; DISPLAY_VISIBLE_HEIGHT],
+            runs: Vec::with_capacity(DISPLAY_VISIBLE_WIDTH / 2 * DISPLAY_VISIBLE_HEIGHT),
+            frames: 0,
+            max_frames: None,
         }
     }
 
-    fn evnt2code(kc: Keycode) -> Option<u8> {
-        let key = match kc {
-            Keycode::Num0 => Some(0),
-            Keycode::Num1 => Some(1),
-            Keycode::Num2 => Some(2),
-            Keycode::Num3 => Some(3),
-            Keycode::Num4 => Some(4),
-            Keycode::Num5 => Some(5),
-            Keycode::Num6 => Some(6),
-            Keycode::Num7 => Some(7),
-            Keycode::Num8 => Some(8),
-            Keycode::Num9 => Some(9),
-            Keycode::A => Some(0xa),
-            Keycode::B => Some(0xb),
-            Keycode::C => Some(0xc),
-            Keycode::D => Some(0xd),
-            Keycode::E => Some(0xe),
-            Keycode::F => Some(0xf),
-            _ => None,
-        };
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
 
-        key
+    // quit after the given number of emulated frames
+    pub fn set_max_frames(&mut self, frames: u64) {
+        self.max_frames = Some(frames);
     }
-}
 
-fn test_nth_bit(x: u8, nth: u8) -> bool {
-    (x & (1 << (7 - nth))) != 0
-}
+    pub fn capture(&mut self) -> &mut Capture {
+        &mut self.capture
+    }
 
-impl MediaIf for Sdl2Be {
-    fn draw_display(&mut self, buf: &[[u8; 10]]) -> Option<u8> {
-        let mut sdl_ps: Vec<Point> = Vec::with_capacity(32*64);
+    // F12
+    fn take_screenshot(&mut self) {
+        let path = timestamped_path("png");
+        match self.capture.screenshot(&path, Screen::new(&self.last_frame)) {
+            Ok(()) => println!("screenshot saved to {}", path.display()),
+            Err(e) => println!("screenshot failed: {}", e),
+        }
+    }
 
-        for r in 0..buf.len() {
-            let row = buf[r];
-            for c in 0..row.len() {
-                for b in 0..8 {
-                    if test_nth_bit(row[c], b) {
-                        let p = Point::new(((c*8 + b as usize) as u8) as i32, r as i32);
-                        sdl_ps.push(p);
-                    }
+    // F11
+    fn toggle_recording(&mut self) {
+        if self.capture.is_recording() {
+            let frames = self.capture.stop_recording();
+            println!("recording stopped after {} frames", frames);
+            return;
+        }
+
+        let path = timestamped_path("gif");
+        match self.capture.start_recording(&path) {
+            Ok(()) => println!("recording to {}", path.display()),
+            Err(e) => println!("recording failed: {}", e),
+        }
+    }
+
+    pub fn set_persistence(&mut self, mode: Persistence) {
+        self.phosphor = Phosphor::new(mode);
+    }
+
+    // colour between background (0) and foreground (255)
+    fn blend(&self, i: u8) -> Color {
+        let mix = |bg: u8, fg: u8| (bg as i32 + (fg as i32 - bg as i32) * i as i32 / 255) as u8;
+        let (bg, fg) = (self.palette.bg, self.palette.fg);
+        Color::RGB(mix(bg.0, fg.0), mix(bg.1, fg.1), mix(bg.2, fg.2))
+    }
+
+    fn clear_canvas(&mut self) {
+        let (r, g, b) = self.palette.bg;
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+    }
+
+    fn draw_phosphor(&mut self) {
+        for y in 0..DISPLAY_VISIBLE_HEIGHT {
+            for x in 0..DISPLAY_VISIBLE_WIDTH {
+                let i = self.phosphor.intensity(x, y);
+                if i == 0 {
+                    continue;
                 }
+
+                self.canvas.set_draw_color(self.blend(i));
+                self.canvas.draw_point(Point::new(x as i32, y as i32)).unwrap();
             }
         }
+    }
+}
+
+impl VideoSink for Sdl2Be {
+    // With persistence the screen is painted in end_frame, every frame.
+    fn draw_display(&mut self, buf: Screen) -> Option<u8> {
+        if self.phosphor.mode() != Persistence::Off {
+            return Some(0);
+        }
+
+        // one rectangle per run of lit pixels in a row
+        self.runs.clear();
+        let runs = &mut self.runs;
+        buf.for_each_run(|x, y, len| runs.push(Rect::new(x as i32, y as i32, len as u32, 1)));
 
-        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-        self.canvas.draw_points(sdl_ps.as_slice()).unwrap();
+        let (r, g, b) = self.palette.fg;
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.fill_rects(&self.runs).unwrap();
 
         Some(0)
     }
 
     fn clear_display(&mut self) -> Option<u8> {
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        self.canvas.clear();
+        if self.phosphor.mode() == Persistence::Off {
+            self.clear_canvas();
+        }
         Some(0)
     }
 
     fn present_display(&mut self) -> Option<u8> {
+        if self.phosphor.mode() == Persistence::Off {
+            self.canvas.present();
+        }
+        Some(0)
+    }
+
+    fn end_frame(&mut self, buf: Screen) -> Option<u8> {
+        self.last_frame.copy_from_slice(buf.rows());
+        self.frames += 1;
+        self.capture.frame(buf);
+
+        if self.phosphor.mode() == Persistence::Off {
+            return Some(0);
+        }
+
+        // repaint even without new sprites so that released pixels keep fading
+        self.phosphor.latch(buf);
+        self.phosphor.end_frame();
+        self.clear_canvas();
+        self.draw_phosphor();
         self.canvas.present();
         Some(0)
     }
-    
-    fn process_events(&mut self) -> bool {
-        for event in self.ev.poll_iter() {
+}
+
+impl AudioSink for Sdl2Be {
+    fn set_sound(&mut self, on: bool) {
+        if on == self.beeping {
+            return;
+        }
+
+        self.beeping = on;
+        if let Some(ref beeper) = self.beeper {
+            if on {
+                beeper.resume();
+            } else {
+                beeper.pause();
+            }
+        }
+    }
+}
+
+// Keys and hotkeys both come from the event queue: the keypad is updated
+// here and the hotkeys are queued for poll_control.
+impl InputSource for Sdl2Be {
+    fn poll_input(&mut self) {
+        let events: Vec<Event> = self.ev.poll_iter().collect();
+        for event in events {
             match event {
                 Event::Quit { .. } |
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. }
                 => { 
-                    return false;
+                    self.controls.send(Control::Quit);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F5), repeat: false, .. } => {
+                    self.controls.send(Control::Reset);
+                },
+                Event::KeyDown { keycode: Some(Keycode::F6), repeat: false, .. } => {
+                    self.controls.send(Control::Pause);
+                },
+
+                Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } => {
+                    self.take_screenshot();
+                },
+                Event::KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => {
+                    self.toggle_recording();
+                },
+                Event::KeyDown { keycode: Some(Keycode::Minus), .. } => {
+                    self.controls.send(Control::Speed(-1));
+                },
+                Event::KeyDown { keycode: Some(Keycode::Equals), .. } => {
+                    self.controls.send(Control::Speed(1));
                 },
 
                 Event::KeyDown {keycode: Some(keycode), ..} => {
-                    match Sdl2Be::evnt2code(keycode) {
-                        Some(kcode) => self.keypad[kcode as usize] = 1,
-                        None => {},
+                    if let Some(kcode) = self.keymap.get(keycode) {
+                        self.keypad[kcode as usize] = 1;
                     }
                 },
                 Event::KeyUp {keycode: Some(keycode), ..} => {
-                    match Sdl2Be::evnt2code(keycode) {
-                        Some(kcode) => self.keypad[kcode as usize] = 0,
-                        None => {},
+                    if let Some(kcode) = self.keymap.get(keycode) {
+                        self.keypad[kcode as usize] = 0;
                     }
                 },
                 _ => {}
             }
         }
 
-        return true;
+        // from the keypad, so that a movie replays the edges from its
+        // recorded keys
+        let keys = (0..16).filter(|&k| self.keypad[k] == 1).fold(0, |m, k| m | 1 << k);
+        self.edges.update(keys);
     }
     
     fn is_key_pressed(&mut self, key: u8) -> bool {
@@ -134,4 +436,20 @@ impl MediaIf for Sdl2Be {
     fn get_pressed_key(&self) -> Option<&u8> {
         self.keypad.iter().find(|&&s| s == 1)
     }
+
+    fn poll_key_event(&mut self) -> Option<KeyEvent> {
+        self.edges.next_event()
+    }
 }
+
+impl ControlSource for Sdl2Be {
+    fn poll_control(&mut self) -> Option<Control> {
+        if self.max_frames.map(|m| self.frames >= m).unwrap_or(false) {
+            return Some(Control::Quit);
+        }
+        self.controls.poll_control()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,22 @@
+use super::*;
+
+#[test]
+fn keymap_binds_host_keys() {
+    let mut keymap = Keymap::default();
+    keymap.apply("W=5,Space=c").unwrap();
+    assert_eq!(keymap.get(Keycode::W), Some(5));
+    assert_eq!(keymap.get(Keycode::Space), Some(0xC));
+    assert_eq!(keymap.get(Keycode::Num7), Some(7));
+    assert!(keymap.apply("Nope=1").is_err());
+    assert!(keymap.apply("W=10").is_err());
+}
+
+#[test]
+fn hotkeys_cannot_be_bound() {
+    let mut keymap = Keymap::default();
+    for spec in &["-=1", "F12=2", "F5=3", "Escape=0"] {
+        let err = keymap.apply(spec).unwrap_err();
+        assert!(err.contains("hotkey"), "{}: {}", spec, err);
+    }
+    assert_eq!(keymap.get(Keycode::Minus), None);
+}
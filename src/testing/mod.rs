@@ -0,0 +1,49 @@
+use cpu::*;
+use cpu_ops::*;
+use headless_media::*;
+use machine::*;
+use media_if::*;
+use memory::*;
+use movie::*;
+use quirks::*;
+use sprites::*;
+use stack::*;
+
+// the mocks live with the CPU
+pub use chip8_core::testing::*;
+
+// One fuzz case: runs `rom` headless for one frame per entry of `keys`,
+// the keys down in a frame given as bit mask. Returns the fault that
+// stopped the CPU, if any. Panics when an instruction was fetched from
+// outside memory, I left memory or the stack outgrew its depth; any
+// other panic is a bug as well.
+pub fn fuzz_case(rom: &[u8], keys: Vec<u16>, quirks: Quirks, ipf: u32) -> Option<Fault> {
+    let rom = &rom[..rom.len().min(MAX_EXE_SIZE)];
+    let mut mem = Memory::new().load_sprites(SPRITES).load_exe(rom).build();
+    let mut display = Display::new();
+    let mut headless = Headless::new();
+    let mut player = MoviePlayer::new(&mut headless, keys);
+
+    let mut cpu = CPU::new(&mut mem, &mut display, &mut player);
+    cpu.set_quirks(quirks);
+    cpu.set_seed(0);
+    run_checked(&mut cpu, ipf).err()
+}
+
+fn run_checked<M: CpuMemory, V: VideoMemory, F: MediaIf>(cpu: &mut CPU<M, V, F>, ipf: u32) -> Result<(), Fault> {
+    while poll_frontend(cpu, &mut false).is_some() {
+        for _ in 0..ipf {
+            let instruction = cpu.fetch()?;
+            let pc = cpu.pc().wrapping_sub(2);
+            assert!((pc as usize) < MEM_SIZE, "fetched from {:04X}", pc);
+
+            let (id, arg) = cpu.decode(instruction).unwrap();
+            cpu.execute(id, arg)?;
+            assert!((cpu.ireg() as usize) < MEM_SIZE, "I = {:04X}", cpu.ireg());
+            assert!(cpu.stack().len() <= DEFAULT_STACK_DEPTH, "stack depth {}", cpu.stack().len());
+        }
+        cpu.update_timers();
+        cpu.end_frame();
+    }
+    Ok(())
+}
@@ -0,0 +1,270 @@
+use cdp1802::*;
+use cheat::*;
+use cpu_ops::Fault;
+use media_if::*;
+use memory::*;
+use timing::*;
+use utils::*;
+
+// The COSMAC VIP around a CDP1802: RAM from 0000, the 512 byte monitor ROM
+// from 8000, the CDP1861 video chip and the hex keypad. The original
+// CHIP-8 interpreter is loaded at 0000 and the program at 0200 as on the
+// real machine, 0NNN then runs the 1802 code at NNN.
+
+pub const VIP_ROM_ADDR: usize = 0x8000;
+pub const VIP_ROM_SIZE: usize = 0x200;
+pub const INTERPRETER_SIZE: usize = 0x200;
+// RAM is decoded below the ROM and repeats up to it
+pub const MAX_VIP_RAM: usize = VIP_ROM_ADDR;
+// the interpreter's stack, variables and the display buffer at the top of RAM
+pub const INTERPRETER_WORK_AREA: usize = 0x160;
+
+// CDP1861 timing: 262 lines of 14 machine cycles, of which 128 are shown.
+// Every shown line takes 8 bytes by DMA from R0.
+const LINE_CYCLES: u64 = 14;
+const FRAME_LINES: u64 = 262;
+pub const FRAME_CYCLES: u64 = LINE_CYCLES * FRAME_LINES;
+const FIRST_LINE: u64 = 80;
+const DISPLAY_LINES: usize = 128;
+const LINE_BYTES: usize = 8;
+// the interrupt comes ahead of the first DMA, so the routine can set R0
+const INTERRUPT_AT: u64 = FIRST_LINE * LINE_CYCLES - VIP_INTERRUPT_CYCLES;
+// EF1 is asserted for the 4 lines before the display starts and before it ends
+const EF1_LINES: u64 = 4;
+
+struct VipBus<'a> {
+    ram: Vec<u8>,
+    rom: Vec<u8>,
+    // After reset the ROM is also seen from 0000, until the first access
+    // with A15 set.
+    rom_low: bool,
+    key_latch: u8,
+    display_on: bool,
+    ef1: bool,
+    media_if: &'a mut (dyn MediaIf + 'a),
+}
+
+impl<'a> VipBus<'a> {
+    fn rom_byte(&self, addr: u16) -> u8 {
+        self.rom.get(addr as usize % VIP_ROM_SIZE).cloned().unwrap_or(0)
+    }
+}
+
+impl<'a> Bus for VipBus<'a> {
+    fn read(&mut self, addr: u16) -> u8 {
+        if addr as usize >= VIP_ROM_ADDR {
+            self.rom_low = false;
+            return self.rom_byte(addr);
+        }
+        if self.rom_low {
+            return self.rom_byte(addr);
+        }
+        self.ram[addr as usize % self.ram.len()]
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        if addr as usize >= VIP_ROM_ADDR {
+            self.rom_low = false;
+            return;
+        }
+        let len = self.ram.len();
+        self.ram[addr as usize % len] = val;
+    }
+
+    fn output(&mut self, port: u8, val: u8) {
+        match port {
+            1 => self.display_on = false,
+            2 => self.key_latch = val & 0xF,
+            _ => {},
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display_on = true;
+        }
+        0
+    }
+
+    fn flag(&mut self, n: u8) -> bool {
+        match n {
+            1 => self.ef1,
+            // the key last written with OUT 2 is down
+            3 => self.media_if.is_key_pressed(self.key_latch),
+            _ => false,
+        }
+    }
+}
+
+pub struct Vip<'a> {
+    cpu: Cdp1802,
+    bus: VipBus<'a>,
+    // the 1861's lines of the current frame
+    lines: [[u8; LINE_BYTES]; DISPLAY_LINES],
+    display: &'a mut Display,
+    cheats: Cheats,
+    // machine cycles since reset
+    cycles: u64,
+}
+
+impl<'a> Vip<'a> {
+    // `ram` is the whole RAM with the program at 0200, its size a power of two.
+    pub fn new(ram: Vec<u8>, display: &'a mut Display, media_if: &'a mut dyn MediaIf) -> Vip<'a> {
+        assert!(ram.len().is_power_of_two() && ram.len() <= MAX_VIP_RAM, "RAM size {}", ram.len());
+        Vip {
+            cpu: Cdp1802::new(),
+            bus: VipBus {
+                ram,
+                rom: Vec::new(),
+                rom_low: true,
+                key_latch: 0,
+                display_on: false,
+                ef1: false,
+                media_if,
+            },
+            lines: [[0; LINE_BYTES]; DISPLAY_LINES],
+            display,
+            cheats: Cheats::new(),
+            cycles: 0,
+        }
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> &mut Self {
+        assert!(rom.len() <= VIP_ROM_SIZE, "ROM size {}", rom.len());
+        self.bus.rom = rom.to_vec();
+        self
+    }
+
+    pub fn load_interpreter(&mut self, interpreter: &[u8]) -> &mut Self {
+        assert!(interpreter.len() <= INTERPRETER_SIZE, "interpreter size {}", interpreter.len());
+        self.bus.ram[..interpreter.len()].clone_from_slice(interpreter);
+        self
+    }
+
+    // Writes the frozen values now and again after every frame. RAM
+    // repeats, so every address lands in it.
+    pub fn set_cheats(&mut self, cheats: Cheats) {
+        self.cheats = cheats;
+        self.write_cheats();
+    }
+
+    fn write_cheats(&mut self) {
+        let len = self.bus.ram.len();
+        for c in self.cheats.iter() {
+            self.bus.ram[c.addr as usize % len] = c.value;
+        }
+    }
+
+    pub fn cpu(&self) -> &Cdp1802 {
+        &self.cpu
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.bus.ram
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn poll_input(&mut self) {
+        self.bus.media_if.poll_input()
+    }
+
+    pub fn poll_control(&mut self) -> Option<Control> {
+        self.bus.media_if.poll_control()
+    }
+
+    // The VIP's reset switch: the 1802 starts over in the monitor ROM,
+    // which runs the interpreter at 0000 again. RAM is kept.
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+        self.bus.rom_low = true;
+        self.bus.display_on = false;
+        self.bus.media_if.set_sound(false);
+    }
+
+    // Runs the 1802 for one frame of the 1861, with the interrupt and the
+    // DMA of every shown line taken between instructions as the 1802 does.
+    pub fn run_frame(&mut self) -> Result<(), Fault> {
+        // an instruction that ran over the end of the last frame has
+        // taken the first cycles of this one
+        let start = self.cycles - self.cycles % FRAME_CYCLES;
+        let end = start + FRAME_CYCLES;
+        let mut interrupted = false;
+        let mut next_line = 0;
+        self.lines = [[0; LINE_BYTES]; DISPLAY_LINES];
+
+        while self.cycles < end {
+            let pos = self.cycles - start;
+            let line = pos / LINE_CYCLES;
+            let last = FIRST_LINE + DISPLAY_LINES as u64;
+            self.bus.ef1 = (FIRST_LINE - EF1_LINES..FIRST_LINE).contains(&line)
+                || (last - EF1_LINES..last).contains(&line);
+
+            if self.bus.display_on {
+                if !interrupted && (INTERRUPT_AT..FIRST_LINE * LINE_CYCLES).contains(&pos)
+                    && self.cpu.interrupt() {
+                    interrupted = true;
+                    self.cycles += INTERRUPT_CYCLES as u64;
+                    continue;
+                }
+
+                if (FIRST_LINE..last).contains(&line) {
+                    let shown = (line - FIRST_LINE) as usize;
+                    if shown >= next_line {
+                        for b in 0..LINE_BYTES {
+                            self.lines[shown][b] = self.cpu.dma_out(&mut self.bus);
+                        }
+                        self.cycles += (LINE_BYTES as u32 * DMA_CYCLES) as u64;
+                        next_line = shown + 1;
+                        continue;
+                    }
+                }
+            }
+
+            self.cycles += self.cpu.step(&mut self.bus)? as u64;
+        }
+
+        self.end_frame();
+        self.write_cheats();
+        Ok(())
+    }
+
+    // The CHIP-8 interpreter shows every row on 4 lines, the screen has
+    // the first of them.
+    fn end_frame(&mut self) {
+        let mut rows = [0; DISPLAY_VISIBLE_HEIGHT];
+        let repeat = DISPLAY_LINES / DISPLAY_VISIBLE_HEIGHT;
+        for (r, row) in rows.iter_mut().enumerate() {
+            *row = DisplayRow::from_be_bytes(self.lines[r * repeat]);
+        }
+        self.display.load_rows(&rows);
+
+        let media_if = &mut self.bus.media_if;
+        let dirty = self.display.take_dirty();
+        let screen = self.display.get_video_buf().unwrap();
+        if dirty {
+            media_if.clear_display();
+            media_if.draw_display(screen);
+            media_if.present_display();
+        }
+        media_if.end_frame(screen);
+        // Q drives the VIP's tone generator
+        media_if.set_sound(self.cpu.q());
+    }
+
+    // hash over the 1802, RAM and the screen, used to detect movie desyncs
+    pub fn state_hash(&mut self) -> u64 {
+        let mut h = Fnv64::default();
+        h.write(&self.cpu.state());
+        h.write(&self.bus.ram);
+        for row in self.display.get_video_buf().unwrap().rows() {
+            h.write(&row.to_be_bytes());
+        }
+        h.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,160 @@
+use super::*;
+use testing::*;
+
+// A stand-in for the monitor ROM: it jumps to 8003, points R1 at the
+// interrupt routine, the stack to 0ECF and the display to page 0F, turns
+// the display on and runs `main_loop` at 801B with P = 3, R0 being the
+// DMA pointer. The interrupt routine is the VIP's: every row
+// of 8 bytes is shown on 4 lines by setting R0 back after 3 of the DMAs.
+fn monitor(main_loop: &[u8]) -> Vec<u8> {
+    let mut rom = vec![
+        0xC0, 0x80, 0x03,               // LBR 8003
+        0xF8, 0x81, 0xB1, 0xF8, 0x02, 0xA1, // R1 = 8102
+        0xF8, 0x0E, 0xB2, 0xF8, 0xCF, 0xA2, // R2 = 0ECF
+        0xF8, 0x0F, 0xBB,               // RB.1 = 0F
+        0xE2,                           // SEX 2
+        0x69,                           // INP 1, display on
+        0xF8, 0x80, 0xB3, 0xF8, 0x1B, 0xA3, 0xD3, // SEP 3 at 801B
+    ];
+    rom.extend_from_slice(main_loop);
+    rom.resize(0x100, 0);
+    rom.extend_from_slice(&[
+        0x72, 0x70,                     // 8100: LDXA, RET
+        0x22, 0x78, 0x22, 0x52,         // 8102: save T and D
+        0xF8, 0x00, 0xA0, 0x9B, 0xB0,   // R0 = RB.1 00
+        0xE2, 0xE2, 0xE2,               // wait for the first DMA
+        0x80, 0xE2, 0xE2,               // 810E: GLO 0
+        0x20, 0xA0, 0xE2,               // back to the start of the row
+        0x20, 0xA0, 0xE2,
+        0x20, 0xA0, 0x3C, 0x0E,         // BN1 810E
+        0x30, 0x00,                     // BR 8100
+    ]);
+    rom
+}
+
+// loops at 801B
+const IDLE: [u8; 2] = [0x30, 0x1B];
+
+fn ram_with_display() -> Vec<u8> {
+    let mut ram = vec![0; 0x1000];
+    for (i, b) in ram[0xF00..].iter_mut().enumerate() {
+        *b = (i as u8).wrapping_mul(37) ^ 0x5A;
+    }
+    ram
+}
+
+fn expected_rows(ram: &[u8]) -> Vec<DisplayRow> {
+    ram[0xF00..].chunks(8)
+        .map(|c| DisplayRow::from_be_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]))
+        .collect()
+}
+
+fn run_frames(ram: Vec<u8>, rom: &[u8], media: &mut MockMedia, frames: usize) -> (Display, Vec<u8>, Cdp1802) {
+    let mut display = Display::new();
+    let (ram, cpu) = {
+        let mut vip = Vip::new(ram, &mut display, media);
+        vip.load_rom(rom);
+        for _ in 0..frames {
+            vip.run_frame().unwrap();
+        }
+        (vip.ram().to_vec(), vip.cpu().clone())
+    };
+    (display, ram, cpu)
+}
+
+#[test]
+fn rom_runs_from_0000_after_reset() {
+    let mut media = MockMedia::new();
+    let (_, _, cpu) = run_frames(vec![0; 0x1000], &monitor(&IDLE), &mut media, 1);
+    assert_eq!(cpu.r(1), 0x8102);
+    assert_eq!(cpu.r(0xB) >> 8, 0x0F);
+}
+
+#[test]
+fn dma_shows_every_row_of_the_display_page() {
+    let ram = ram_with_display();
+    let expected = expected_rows(&ram);
+    // the interrupt is taken a cycle later after the 3 cycle NOP
+    for main_loop in &[&IDLE[..], &[0xC4, 0x30, 0x1B]] {
+        let mut media = MockMedia::new();
+        let (mut display, _, _) = run_frames(ram.clone(), &monitor(main_loop), &mut media, 2);
+        assert_eq!(display.get_video_buf().unwrap().rows(), &expected[..]);
+    }
+}
+
+#[test]
+fn interrupt_returns_to_the_program() {
+    let mut media = MockMedia::new();
+    let (_, ram, cpu) = run_frames(ram_with_display(), &monitor(&IDLE), &mut media, 3);
+    assert_eq!(cpu.p(), 3);
+    assert!(cpu.ie());
+    assert_eq!(cpu.pc(), 0x801B);
+    // the stack is back where it was
+    assert_eq!(cpu.r(2), 0x0ECF);
+    assert_eq!(ram[0xECE], 0x23);
+}
+
+#[test]
+fn frames_are_presented_when_they_change() {
+    let mut media = MockMedia::new();
+    run_frames(ram_with_display(), &monitor(&IDLE), &mut media, 3);
+    let draws = media.calls().iter().filter(|c| **c == MediaCall::DrawDisplay).count();
+    let frames = media.calls().iter().filter(|c| **c == MediaCall::EndFrame).count();
+    assert_eq!((draws, frames), (1, 3));
+}
+
+#[test]
+fn display_off_shows_nothing() {
+    // OUT 1 right after the display went on
+    let rom = monitor(&[0x61, 0x30, 0x1C]);
+    let mut media = MockMedia::new();
+    let (mut display, _, cpu) = run_frames(ram_with_display(), &rom, &mut media, 2);
+    assert!(display.get_video_buf().unwrap().rows().iter().all(|&r| r == 0));
+    // and never interrupts
+    assert_eq!(cpu.p(), 3);
+    assert_eq!(cpu.r(2), 0x0ED0);
+}
+
+// LDI 7, STR 2, OUT 2, DEC 2, B3 to SEQ, else loop from the start
+const KEY_7_SETS_Q: [u8; 12] = [0xF8, 0x07, 0x52, 0x62, 0x22, 0x36, 0x24, 0x30, 0x1B,
+                                0x7B, 0x30, 0x25];
+
+#[test]
+fn keypad_latch_and_ef3() {
+    let rom = monitor(&KEY_7_SETS_Q);
+    let mut media = MockMedia::new();
+    media.set_keys(1 << 7);
+    let (_, _, cpu) = run_frames(vec![0; 0x1000], &rom, &mut media, 1);
+    assert!(cpu.q());
+    assert!(media.calls().contains(&MediaCall::IsKeyPressed(7)));
+    assert!(media.calls().contains(&MediaCall::SetSound(true)));
+}
+
+#[test]
+fn key_up_leaves_ef3_clear() {
+    let rom = monitor(&KEY_7_SETS_Q);
+    let mut media = MockMedia::new();
+    media.set_keys(1 << 6);
+    let (_, _, cpu) = run_frames(vec![0; 0x1000], &rom, &mut media, 1);
+    assert!(!cpu.q());
+}
+
+#[test]
+fn ram_repeats_below_the_rom() {
+    // R4 = 1000, STR 4 of 42 lands at 0000 of 4K
+    let rom = monitor(&[0xF8, 0x10, 0xB4, 0xF8, 0x42, 0x54, 0x30, 0x21]);
+    let mut media = MockMedia::new();
+    let (_, ram, _) = run_frames(vec![0; 0x1000], &rom, &mut media, 1);
+    assert_eq!(ram[0], 0x42);
+}
+
+#[test]
+fn frame_takes_the_1861_cycles() {
+    let mut media = MockMedia::new();
+    let mut display = Display::new();
+    let mut vip = Vip::new(vec![0; 0x1000], &mut display, &mut media);
+    vip.load_rom(&monitor(&IDLE));
+    vip.run_frame().unwrap();
+    vip.run_frame().unwrap();
+    assert!(vip.cycles() >= 2 * FRAME_CYCLES && vip.cycles() < 2 * FRAME_CYCLES + 3);
+}
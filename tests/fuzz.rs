@@ -0,0 +1,78 @@
+extern crate proptest;
+extern crate chip8_opcode;
+
+use chip8_opcode::memory::*;
+use chip8_opcode::quirks::*;
+use chip8_opcode::testing::*;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use std::fs;
+use std::path::Path;
+
+// Random and mutated ROMs with random input must never panic: the CPU
+// either runs or stops with a Fault. fuzz_case checks PC, I and the stack.
+// See fuzz/ for a cargo-fuzz target with the same checks.
+
+const FRAMES: usize = 20;
+const SPEED: u32 = 50;
+
+// ROMs in res/ as seeds
+fn res_roms() -> Vec<Vec<u8>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("res");
+    let mut roms: Vec<(String, Vec<u8>)> = fs::read_dir(dir).unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .filter(|e| e.path().extension().is_none_or(|x| x != "zip" && x != "md"))
+        .map(|e| (e.file_name().to_string_lossy().into_owned(), fs::read(e.path()).unwrap()))
+        .filter(|(_, rom)| !rom.is_empty() && rom.len() <= MAX_EXE_SIZE)
+        .collect();
+    roms.sort();
+    roms.into_iter().map(|(_, rom)| rom).collect()
+}
+
+fn quirks() -> impl Strategy<Value = Quirks> {
+    any::<[bool; 9]>().prop_map(|b| Quirks {
+        shift: b[0],
+        memory_increment_by_x: b[1],
+        memory_leave_i_unchanged: b[2],
+        jump: b[3],
+        logic: b[4],
+        memory_wrap: b[5],
+        wrap: b[6],
+        vblank: b[7],
+        key_held: b[8],
+    })
+}
+
+fn keys() -> impl Strategy<Value = Vec<u16>> {
+    vec(any::<u16>(), 1..FRAMES)
+}
+
+proptest! {
+    #[test]
+    fn random_roms(rom in vec(any::<u8>(), 0..1024), keys in keys(), quirks in quirks()) {
+        fuzz_case(&rom, keys, quirks, SPEED);
+    }
+
+    #[test]
+    fn mutated_roms(seed in any::<prop::sample::Index>(),
+                    edits in vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+                    keys in keys(), quirks in quirks()) {
+        let roms = res_roms();
+        let mut rom = seed.get(&roms).clone();
+        for (at, byte) in edits {
+            let i = at.index(rom.len());
+            rom[i] = byte;
+        }
+        fuzz_case(&rom, keys, quirks, SPEED);
+    }
+}
+
+#[test]
+fn res_roms_run() {
+    for rom in res_roms() {
+        fuzz_case(&rom, vec![0; 60], Quirks::default(), 10);
+    }
+}
@@ -0,0 +1,171 @@
+extern crate chip8_opcode;
+
+use chip8_opcode::headless_media::*;
+use chip8_opcode::machine::*;
+use chip8_opcode::media_if::*;
+use chip8_opcode::memory::*;
+use chip8_opcode::sprites::*;
+use chip8_opcode::testing::*;
+use chip8_opcode::timing::*;
+
+use std::thread;
+
+const ROMS: [&[u8]; 4] = [
+    include_bytes!("../res/PONG"),
+    include_bytes!("../res/BRIX"),
+    include_bytes!("../res/INVADERS"),
+    include_bytes!("../res/TETRIS"),
+];
+const FRAMES: u64 = 300;
+
+fn machine<F: MediaIf>(rom: &[u8], frontend: F) -> Machine<F> {
+    let mem = Memory::new().load_sprites(SPRITES).load_exe(rom).build();
+    let mut m = Machine::new(mem, frontend);
+    m.set_seed(1);
+    m
+}
+
+fn final_screen(rom: &[u8]) -> u64 {
+    let mut headless = Headless::new();
+    headless.set_max_frames(FRAMES);
+    let mut m = machine(rom, headless);
+    m.run().unwrap();
+    screen_hash(m.display().get_video_buf().unwrap())
+}
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn machine_is_send() {
+    assert_send::<Machine<Headless>>();
+    assert_send::<Machine<MockMedia>>();
+}
+
+#[test]
+fn machines_run_in_parallel_threads() {
+    let expected: Vec<u64> = ROMS.iter().map(|rom| final_screen(rom)).collect();
+
+    let workers: Vec<_> = ROMS.iter()
+        .map(|&rom| thread::spawn(move || final_screen(rom)))
+        .collect();
+    let hashes: Vec<u64> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+    assert_eq!(hashes, expected);
+}
+
+#[test]
+fn machine_moves_to_another_thread() {
+    let mut m = machine(ROMS[1], MockMedia::new());
+    for _ in 0..30 {
+        m.run_frame().unwrap();
+    }
+    let hash = thread::spawn(move || {
+        for _ in 0..30 {
+            m.run_frame().unwrap();
+        }
+        m.state_hash()
+    }).join().unwrap();
+
+    let mut here = machine(ROMS[1], MockMedia::new());
+    for _ in 0..60 {
+        here.run_frame().unwrap();
+    }
+    assert_eq!(hash, here.state_hash());
+}
+
+#[test]
+fn clone_is_a_snapshot() {
+    let mut m = machine(ROMS[2], MockMedia::new());
+    for _ in 0..60 {
+        m.run_frame().unwrap();
+    }
+    let mut snapshot = m.clone();
+    for _ in 0..60 {
+        m.run_frame().unwrap();
+        snapshot.run_frame().unwrap();
+    }
+    assert_eq!(m.state_hash(), snapshot.state_hash());
+
+    // and independent of the original
+    snapshot.memory().set_u8(0x300, 0xAA);
+    assert_ne!(m.state_hash(), snapshot.state_hash());
+}
+
+#[test]
+fn machines_in_a_vec() {
+    let mut machines: Vec<_> = ROMS.iter().map(|rom| machine(rom, MockMedia::new())).collect();
+    for m in &mut machines {
+        m.run_frame().unwrap();
+    }
+    let (mem, _, media) = machines.pop().unwrap().into_parts();
+    assert_eq!(mem.size(), MEM_SIZE);
+    assert_eq!(media.frame(), 1);
+}
+
+#[test]
+fn headless_video_with_scripted_input() {
+    // BRIX moves the paddle left with key 4
+    let mut keys = vec![0u16; 60];
+    keys.extend(vec![1 << 4; 60]);
+    let scripted = |keys: Vec<u16>| Frontend::new(Headless::new(), (), ScriptedInput::new(keys), ());
+
+    let mut still = machine(ROMS[1], scripted(vec![0; 120]));
+    let mut moved = machine(ROMS[1], scripted(keys));
+    // run_frame leaves polling the input to the caller
+    for _ in 0..120 {
+        still.frontend().poll_input();
+        moved.frontend().poll_input();
+        still.run_frame().unwrap();
+        moved.run_frame().unwrap();
+    }
+    assert!(moved.frontend().input.is_done());
+    assert_eq!(moved.frontend().video.frames(), 120);
+    assert_ne!(still.state_hash(), moved.state_hash());
+}
+
+#[test]
+fn controls_pause_reset_and_quit() {
+    let mut queue = ControlQueue::new();
+    queue.send(Control::Pause);
+    let mut m = machine(ROMS[0], Frontend::new(Headless::new(), (), (), queue));
+    m.frontend().control.send(Control::Quit);
+    m.run().unwrap();
+    // paused all along, no frame ran
+    assert_eq!(m.frontend().video.frames(), 0);
+    assert_eq!(m.cpu().pc(), 0x200);
+
+    let mut m = machine(ROMS[0], Frontend::new(Headless::new(), (), (), ControlQueue::new()));
+    let fresh = m.state_hash();
+    m.run_frame().unwrap();
+    m.frontend().control.send(Control::Reset);
+    m.frontend().control.send(Control::Quit);
+    m.run().unwrap();
+    // the screen is cleared, so the reset machine matches a fresh one
+    assert_eq!(m.state_hash(), fresh);
+}
+
+// Hands out its controls one poll_frontend at a time, None ends a poll.
+struct ControlScript(Vec<Option<Control>>);
+
+impl ControlSource for ControlScript {
+    fn poll_control(&mut self) -> Option<Control> {
+        if self.0.is_empty() {
+            return Some(Control::Quit);
+        }
+        self.0.remove(0)
+    }
+}
+
+#[test]
+fn speed_changes_are_clamped() {
+    let run = |delta: i32| {
+        let script = ControlScript(vec![Some(Control::Speed(delta)), None]);
+        let mut m = machine(ROMS[0], Frontend::new(Headless::new(), (), (), script));
+        m.set_speed(10, Timing::Ipf);
+        m.run().unwrap();
+        assert_eq!(m.frontend().video.frames(), 1);
+        m.speed()
+    };
+    assert_eq!(run(5), 15);
+    assert_eq!(run(-50), 1);
+    assert_eq!(run(5000), MAX_SPEED);
+}
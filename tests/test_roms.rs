@@ -0,0 +1,142 @@
+extern crate chip8_opcode;
+
+use chip8_opcode::cpu::*;
+use chip8_opcode::cpu_ops::*;
+use chip8_opcode::headless_media::*;
+use chip8_opcode::machine::*;
+use chip8_opcode::memory::*;
+use chip8_opcode::movie::*;
+use chip8_opcode::sprites::*;
+
+// Timendus' test suite, see res/tests/README.md
+const SUITE: &[u8] = include_bytes!("../res/tests/chip8-test-suite.ch8");
+
+const SPEED: u32 = 15;
+const SEED: u64 = 1;
+
+const KEY_5: u16 = 1 << 5;
+
+struct Run {
+    // 0x1FF
+    test: u8,
+    // 0x1FE
+    option: u8,
+    quirks: &'static str,
+    frames: usize,
+    // keys held from this frame on
+    hold: Option<(usize, u16)>,
+    // and released from this one
+    release: Option<usize>,
+}
+
+impl Run {
+    fn new(test: u8, option: u8, quirks: &'static str, frames: usize) -> Run {
+        Run { test, option, quirks, frames, hold: None, release: None }
+    }
+
+    fn hold(mut self, from: usize, keys: u16) -> Run {
+        self.hold = Some((from, keys));
+        self
+    }
+
+    fn release(mut self, at: usize) -> Run {
+        self.release = Some(at);
+        self
+    }
+
+    // Runs the suite headless and returns the final screen.
+    fn screen(&self) -> Display {
+        let mut mem = Memory::new().load_sprites(SPRITES).load_exe(SUITE).build();
+        mem.set_u8(0x1FF, self.test);
+        mem.set_u8(0x1FE, self.option);
+
+        let keys = (0..self.frames)
+            .map(|f| match self.hold {
+                Some((from, keys)) if f >= from && self.release.is_none_or(|r| f < r) => keys,
+                _ => 0,
+            })
+            .collect();
+
+        let mut display = Display::new();
+        let mut headless = Headless::new();
+        {
+            let mut player = MoviePlayer::new(&mut headless, keys);
+            let mut cpu = CPU::new(&mut mem, &mut display, &mut player);
+            cpu.set_quirks(self.quirks.parse().unwrap());
+            cpu.set_seed(SEED);
+
+            while poll_frontend(&mut cpu, &mut false).is_some() {
+                for _ in 0..SPEED {
+                    let instruction = cpu.fetch().unwrap();
+                    let (id, arg) = cpu.decode(instruction).unwrap();
+                    cpu.execute(id, arg).unwrap();
+                }
+                cpu.update_timers();
+                cpu.end_frame();
+            }
+        }
+        display
+    }
+
+    fn check(&self, golden: u64) {
+        let mut display = self.screen();
+        let buf = display.get_video_buf().unwrap();
+        let hash = screen_hash(buf);
+
+        assert!(hash == golden, "screen hash {:016x}, expected {:016x}\n{}",
+                hash, golden, ascii(buf));
+    }
+}
+
+// The visible screen, to see which check failed
+fn ascii(buf: Screen) -> String {
+    let mut out = String::new();
+    for y in 0..buf.height() {
+        for x in 0..buf.width() {
+            out.push(if buf.pixel(x, y) { '#' } else { '.' });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[test]
+fn ibm_logo() {
+    Run::new(1, 0, "legacy", 60).check(0x97977f55485e4188);
+}
+
+#[test]
+fn corax_opcodes() {
+    Run::new(2, 0, "legacy", 120).check(0x66fa30748d4da4e5);
+}
+
+#[test]
+fn flags() {
+    Run::new(3, 0, "vip", 120).check(0x09904e73e8d8cc71);
+}
+
+#[test]
+fn quirks_chip8() {
+    Run::new(4, 1, "vip", 600).check(0x81a26e2623bd5633);
+}
+
+#[test]
+fn quirks_schip() {
+    Run::new(4, 2, "schip", 600).check(0x4d16f0e72197f139);
+}
+
+#[test]
+fn keypad_ex9e_down() {
+    Run::new(5, 1, "legacy", 120).hold(30, KEY_5).check(0x05ae32dbb7453285);
+}
+
+#[test]
+fn keypad_exa1_up() {
+    Run::new(5, 2, "legacy", 120).hold(30, KEY_5).check(0x98c03b87d4352085);
+}
+
+// FX0A must wait for the key to be released
+#[test]
+fn keypad_fx0a_getkey() {
+    Run::new(5, 3, "legacy", 120).hold(30, KEY_5).release(45).check(0x3574d1c500723e02);
+}